@@ -1,25 +1,28 @@
 use anyhow::{anyhow, Result};
 use chrono::{DateTime, Local, Utc};
+use futures_util::stream::{self, StreamExt};
 use serde_json::{json, Value};
-use std::fs;
+use std::collections::HashMap;
+use std::future::Future;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
-
-use crate::audit::append_ledger;
-use crate::embedding_index::{build_knowledge_index, search_knowledge_index};
-use crate::embeddings::EmbeddingClient;
-use crate::git_utils::git_commit;
-use crate::knowledge::{apply_patch, read_doc, KnowledgePatch};
-use crate::engine::{ChatResponse, Engine};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{oneshot, Notify};
+
+use crate::config::ToolPolicy;
+use crate::engine::{ChatResponse, Engine, ToolCall};
 use crate::thread_store::{
-    append_event, build_event, build_event_with_engine, create_thread, read_thread, EventType, Role,
+    append_event, build_event, build_event_with_engine, read_thread, EventType, Role, ThreadEvent,
 };
-use crate::vault::init_vault;
+use crate::tools::artifact::spill;
+use crate::tools::ToolRegistry;
+use crate::usage::Usage;
 
 /// Events emitted during an agent run for live streaming to clients.
 #[derive(Debug, Clone)]
 pub enum AgentEvent {
+    /// Fragment of assistant text, streamed as the model produces it
+    Delta { text: String },
     /// Tool call started — includes the arguments so callers can show what's happening
     ToolCallStart {
         tool_name: String,
@@ -32,19 +35,137 @@ pub enum AgentEvent {
     },
     /// Final assistant message content
     FinalContent { content: String },
+    /// A transient LLM error (rate limit, overload, timeout) is being retried
+    Retry {
+        label: String,
+        attempt: u32,
+        max_retries: u32,
+        delay_ms: u64,
+        reason: String,
+    },
+    /// The engine failed and a fallback chain switched to the next one
+    EngineFallback {
+        from: String,
+        to: String,
+        model: String,
+        reason: String,
+    },
     /// Deep think background task completed
     DeepThinkComplete { monologue: String },
+    /// A tool call with an "ask" policy is waiting for session.approve/deny
+    ApprovalRequired {
+        approval_id: String,
+        tool_name: String,
+        arguments: Value,
+    },
+}
+
+/// Who answers tool calls whose policy is "ask". Without one they are denied.
+#[derive(Clone)]
+pub enum Approver {
+    /// Prompt on the terminal (direct REPL).
+    Terminal,
+    /// Park the call until the gateway resolves it.
+    Gateway(Arc<PendingApprovals>),
 }
 
+/// Tool calls waiting for a gateway client to approve or deny them.
+#[derive(Default)]
+pub struct PendingApprovals {
+    waiting: Mutex<HashMap<String, oneshot::Sender<bool>>>,
+}
+
+impl PendingApprovals {
+    fn register(&self, approval_id: &str) -> oneshot::Receiver<bool> {
+        let (tx, rx) = oneshot::channel();
+        self.waiting.lock().unwrap().insert(approval_id.to_string(), tx);
+        rx
+    }
+
+    /// Drop a call nobody will wait on any more, e.g. after a cancelled run.
+    fn forget(&self, approval_id: &str) {
+        self.waiting.lock().unwrap().remove(approval_id);
+    }
+
+    /// Resolve a waiting call. False if nothing is waiting on `approval_id`.
+    pub fn resolve(&self, approval_id: &str, approved: bool) -> bool {
+        match self.waiting.lock().unwrap().remove(approval_id) {
+            Some(tx) => tx.send(approved).is_ok(),
+            None => false,
+        }
+    }
+}
+
+/// Stops an agent run. The loop checks it between turns, races it against
+/// LLM calls and approvals, and starts no more tool calls once it fires;
+/// long-running tools poll it too.
+#[derive(Clone, Default)]
+pub struct CancelToken(Arc<CancelState>);
+
+#[derive(Default)]
+struct CancelState {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::SeqCst);
+        self.0.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::SeqCst)
+    }
+
+    async fn cancelled(&self) {
+        // Registered before the check so a cancel in between still wakes us
+        let notified = self.0.notify.notified();
+        if self.is_cancelled() {
+            return;
+        }
+        notified.await;
+    }
+
+    /// Run `fut` unless the token fires first, in which case it is dropped
+    /// and the result is `Cancelled`.
+    pub async fn guard<T>(&self, fut: impl Future<Output = Result<T>>) -> Result<T> {
+        tokio::select! {
+            biased;
+            _ = self.cancelled() => Err(Cancelled.into()),
+            result = fut => result,
+        }
+    }
+}
+
+/// Error returned by a run that was cancelled; test with `err.is::<Cancelled>()`.
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("run cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+#[derive(Clone)]
 pub struct AgentConfig {
     pub vault_path: PathBuf,
     pub thread_path: PathBuf,
     pub max_turns: usize,
     pub allow_commit: bool,
+    /// Tools the agent may call; `tool_filter` narrows this further.
+    pub tools: Arc<ToolRegistry>,
     /// If set, only expose these tools (by name). If None, expose all.
     pub tool_filter: Option<Vec<String>>,
     /// Optional channel for streaming events to gateway clients.
-    pub event_sink: Option<std::sync::mpsc::Sender<AgentEvent>>,
+    pub event_sink: Option<tokio::sync::mpsc::UnboundedSender<AgentEvent>>,
+    /// Answers "ask" tool policies; None denies them.
+    pub approver: Option<Approver>,
+    /// Fired to stop the run early.
+    pub cancel: CancelToken,
     /// Flag indicating whether a deep_think background task is running.
     pub deep_think_running: Arc<AtomicBool>,
     /// Engine name for per-message attribution (e.g. "openai", "anthropic").
@@ -53,39 +174,60 @@ pub struct AgentConfig {
     pub model_name: Option<String>,
 }
 
-pub fn run_agent_loop(
+pub async fn run_agent_loop(
     config: &AgentConfig,
     initial_messages: Vec<Value>,
     client: &dyn Engine,
 ) -> Result<Vec<Value>> {
-    let all_tools = tool_schemas();
-    let tools: Vec<Value> = match &config.tool_filter {
-        Some(names) => all_tools
-            .into_iter()
-            .filter(|t| {
-                t.get("function")
-                    .and_then(|f| f.get("name"))
-                    .and_then(|n| n.as_str())
-                    .map(|n| names.iter().any(|allowed| allowed == n))
-                    .unwrap_or(false)
-            })
-            .collect(),
-        None => all_tools,
-    };
+    let tools = config.tools.schemas(config.tool_filter.as_deref());
     let mut messages = initial_messages;
 
     for turn in 0..config.max_turns {
-        let response = client.chat(&messages, &tools)?;
+        if config.cancel.is_cancelled() {
+            return Err(Cancelled.into());
+        }
+        let response = match config.event_sink {
+            Some(ref sink) => {
+                config
+                    .cancel
+                    .guard(client.chat_stream(&messages, &tools, &mut |text| {
+                        let _ = sink.send(AgentEvent::Delta { text: text.to_string() });
+                    }))
+                    .await?
+            }
+            None => {
+                // Direct/CLI mode: stream text straight to stdout
+                let mut streamed = false;
+                let response = config
+                    .cancel
+                    .guard(client.chat_stream(&messages, &tools, &mut |text| {
+                        use std::io::Write;
+                        print!("{text}");
+                        let _ = std::io::stdout().flush();
+                        streamed = true;
+                    }))
+                    .await;
+                if streamed {
+                    println!();
+                }
+                response?
+            }
+        };
+
+        // Recorded on the first event this response produces
+        let mut usage = response.usage;
+        // A fallback chain may have answered from a different engine than configured
+        let (engine_name, model_name) = match client.active_engine() {
+            Some(kind) => (Some(kind.as_str().to_string()), Some(client.model().to_string())),
+            None => (config.engine_name.clone(), config.model_name.clone()),
+        };
 
         if response.tool_calls.is_empty() {
             let content = response.content.unwrap_or_default();
             if let Some(ref sink) = config.event_sink {
-                // Streaming to gateway — don't print locally
                 let _ = sink.send(AgentEvent::FinalContent { content: content.clone() });
-            } else if !content.is_empty() {
-                println!("{content}");
             }
-            let event = build_event_with_engine(
+            let mut event = build_event_with_engine(
                 None,
                 EventType::AssistantMessage,
                 Role::Assistant,
@@ -94,9 +236,10 @@ pub fn run_agent_loop(
                 None,
                 None,
                 None,
-                config.engine_name.clone(),
-                config.model_name.clone(),
+                engine_name,
+                model_name,
             );
+            attach_usage(&mut event, usage.take(), client.model());
             append_event(&config.thread_path, event)?;
             messages.push(json!({"role": "assistant", "content": content}));
             break;
@@ -105,78 +248,105 @@ pub fn run_agent_loop(
         let tool_call_payload = tool_calls_payload(&response)?;
         messages.push(json!({"role": "assistant", "tool_calls": tool_call_payload}));
 
-        for call in response.tool_calls {
-            if let Some(ref sink) = config.event_sink {
-                let _ = sink.send(AgentEvent::ToolCallStart {
-                    tool_name: call.name.clone(),
-                    arguments: call.arguments.clone(),
-                });
-            } else {
-                // Debug: show tool calls in direct/CLI mode
-                let detail = call.arguments.get("query").and_then(|v| v.as_str())
-                    .or_else(|| call.arguments.get("doc_path").and_then(|v| v.as_str()))
-                    .or_else(|| call.arguments.get("prompt").and_then(|v| v.as_str()).map(|s| &s[..s.len().min(80)]));
-                match detail {
-                    Some(d) => eprintln!("[{}: {}]", call.name, d),
-                    None => eprintln!("[{}]", call.name),
+        // Consecutive read-only calls run together; anything else runs alone.
+        // Events and tool messages are written in call order either way.
+        let mut calls = response.tool_calls.into_iter().peekable();
+        while let Some(first) = calls.next() {
+            let mut batch = vec![first];
+            if is_parallel_safe(config, &batch[0]) {
+                while let Some(call) = calls.next_if(|c| is_parallel_safe(config, c)) {
+                    batch.push(call);
                 }
             }
-            let reason = call
-                .arguments
-                .get("reason")
-                .and_then(|val| val.as_str())
-                .unwrap_or("llm_tool_call")
-                .to_string();
-
-            let tool_call_event = build_event_with_engine(
-                None,
-                EventType::ToolCall,
-                Role::Assistant,
-                None,
-                Some(call.name.clone()),
-                Some(call.arguments.clone()),
-                None,
-                Some(reason),
-                config.engine_name.clone(),
-                config.model_name.clone(),
-            );
-            append_event(&config.thread_path, tool_call_event)?;
 
-            let result = execute_tool(
-                &call.name,
-                &call.arguments,
-                config,
-            );
-            let result_value = match result {
-                Ok(data) => json!({"status": "ok", "data": data}),
-                Err(err) => json!({"status": "error", "error": err.to_string()}),
-            };
+            let mut refusals = Vec::with_capacity(batch.len());
+            for call in &batch {
+                announce_tool_call(config, call);
+                let reason = call
+                    .arguments
+                    .get("reason")
+                    .and_then(|val| val.as_str())
+                    .unwrap_or("llm_tool_call")
+                    .to_string();
+
+                let mut tool_call_event = build_event_with_engine(
+                    None,
+                    EventType::ToolCall,
+                    Role::Assistant,
+                    None,
+                    Some(call.name.clone()),
+                    Some(call.arguments.clone()),
+                    None,
+                    Some(reason),
+                    engine_name.clone(),
+                    model_name.clone(),
+                );
+                attach_usage(&mut tool_call_event, usage.take(), client.model());
+                append_event(&config.thread_path, tool_call_event)?;
+
+                // Malformed calls are bounced before anyone is asked to approve them
+                let refusal = match invalid_arguments(config, call) {
+                    Some(result) => Some(result),
+                    None => authorize(config, call)
+                        .await?
+                        .map(|reason| json!({"status": "error", "error": reason})),
+                };
+                refusals.push(refusal);
+            }
+
+            // On cancel, calls that haven't started are skipped. One already on
+            // a blocking thread can't be stopped from here, so it is awaited and
+            // its real outcome recorded; long-running tools poll the token to
+            // end early. `buffered` yields in input order, whatever order the
+            // calls finish in
+            let runs: Vec<_> = batch
+                .iter()
+                .zip(refusals)
+                .map(|(call, refusal)| {
+                    let run = run_tool(config.clone(), call.clone());
+                    let cancel = config.cancel.clone();
+                    async move {
+                        match refusal {
+                            Some(result) => result,
+                            None if cancel.is_cancelled() => json!({"status": "error", "error": "cancelled"}),
+                            None => run.await,
+                        }
+                    }
+                })
+                .collect();
+            let results = stream::iter(runs).buffered(MAX_PARALLEL_TOOLS).collect::<Vec<Value>>().await;
+            let cancelled = config.cancel.is_cancelled();
+
+            for (call, result_value) in batch.into_iter().zip(results) {
+                if let Some(ref sink) = config.event_sink {
+                    let _ = sink.send(AgentEvent::ToolCallResult {
+                        tool_name: call.name.clone(),
+                        result: result_value.clone(),
+                    });
+                }
 
-            if let Some(ref sink) = config.event_sink {
-                let _ = sink.send(AgentEvent::ToolCallResult {
-                    tool_name: call.name.clone(),
-                    result: result_value.clone(),
-                });
-            }
+                let tool_result_event = build_event(
+                    None,
+                    EventType::ToolResult,
+                    Role::Tool,
+                    None,
+                    Some(call.name.clone()),
+                    None,
+                    Some(result_value.clone()),
+                    None,
+                );
+                append_event(&config.thread_path, tool_result_event)?;
 
-            let tool_result_event = build_event(
-                None,
-                EventType::ToolResult,
-                Role::Tool,
-                None,
-                Some(call.name.clone()),
-                None,
-                Some(result_value.clone()),
-                None,
-            );
-            append_event(&config.thread_path, tool_result_event)?;
-
-            let tool_output = serde_json::to_string(&result_value)?;
-            messages.push(json!({
-                "role": "tool",
-                "tool_call_id": call.id,
-                "content": tool_output
-            }));
+                let tool_output = serde_json::to_string(&result_value)?;
+                messages.push(json!({
+                    "role": "tool",
+                    "tool_call_id": call.id,
+                    "content": tool_output
+                }));
+            }
+            if cancelled {
+                return Err(Cancelled.into());
+            }
         }
 
         if turn == config.max_turns - 1 {
@@ -187,619 +357,258 @@ pub fn run_agent_loop(
     Ok(messages)
 }
 
-pub fn with_datetime(ts: DateTime<Utc>, content: &str) -> String {
-    let local = ts.with_timezone(&Local).format("%Y-%m-%dT%H:%M:%S");
-    if content.is_empty() {
-        format!("[{local}]")
-    } else {
-        format!("[{local}] {content}")
-    }
+/// Upper bound on read-only tool calls running at the same time.
+const MAX_PARALLEL_TOOLS: usize = 4;
+
+/// Read-only tools only touch the vault, so several calls can run at once.
+fn is_parallel_safe(config: &AgentConfig, call: &ToolCall) -> bool {
+    config
+        .tools
+        .get(&call.name, config.tool_filter.as_deref())
+        .is_some_and(|tool| tool.read_only())
 }
 
-fn tool_calls_payload(response: &ChatResponse) -> Result<Vec<Value>> {
-    let mut payload = Vec::new();
-    for call in &response.tool_calls {
-        let args = serde_json::to_string(&call.arguments)?;
-        payload.push(json!({
-            "id": call.id,
-            "type": "function",
-            "function": {
-                "name": call.name,
-                "arguments": args
-            }
-        }));
+/// Tell the client (or the terminal, in direct mode) that a tool call is starting.
+fn announce_tool_call(config: &AgentConfig, call: &ToolCall) {
+    if let Some(ref sink) = config.event_sink {
+        let _ = sink.send(AgentEvent::ToolCallStart {
+            tool_name: call.name.clone(),
+            arguments: call.arguments.clone(),
+        });
+    } else {
+        // Debug: show tool calls in direct/CLI mode
+        let detail = config
+            .tools
+            .get(&call.name, None)
+            .and_then(|tool| tool.describe_call(&call.arguments));
+        match detail {
+            Some(d) => eprintln!("[{}: {}]", call.name, d),
+            None => eprintln!("[{}]", call.name),
+        }
     }
-    Ok(payload)
 }
 
-pub fn tool_schemas() -> Vec<Value> {
-    vec![
-        json!({
-            "type": "function",
-            "function": {
-                "name": "vault_init",
-                "description": "Initialize a J vault directory with required structure.",
-                "parameters": {
-                    "type": "object",
-                    "properties": {
-                        "path": { "type": "string", "description": "Vault directory path." },
-                        "reason": { "type": "string" }
-                    },
-                    "required": ["reason"]
-                }
-            }
-        }),
-        json!({
-            "type": "function",
-            "function": {
-                "name": "thread_create",
-                "description": "Create a new thread file in the vault.",
-                "parameters": {
-                    "type": "object",
-                    "properties": {
-                        "vault": { "type": "string", "description": "Vault path." },
-                        "thread_id": { "type": "string" },
-                        "date": { "type": "string", "description": "YYYY-MM-DD" },
-                        "reason": { "type": "string" }
-                    },
-                    "required": ["reason"]
-                }
-            }
-        }),
-        json!({
-            "type": "function",
-            "function": {
-                "name": "thread_read",
-                "description": "Read events from a thread file.",
-                "parameters": {
-                    "type": "object",
-                    "properties": {
-                        "thread": { "type": "string", "description": "Thread file path." },
-                        "offset": { "type": "integer" },
-                        "limit": { "type": "integer" },
-                        "reason": { "type": "string" }
-                    },
-                    "required": ["reason"]
-                }
-            }
-        }),
-        json!({
-            "type": "function",
-            "function": {
-                "name": "thread_append",
-                "description": "Append an event to a thread file.",
-                "parameters": {
-                    "type": "object",
-                    "properties": {
-                        "thread": { "type": "string", "description": "Thread file path." },
-                        "event_type": { "type": "string", "description": "user_message|assistant_message|tool_call|tool_result|system_note|attachment_added" },
-                        "role": { "type": "string", "description": "user|assistant|tool|system" },
-                        "content": { "type": "string" },
-                        "content_json": { "type": "object" },
-                        "tool_name": { "type": "string" },
-                        "tool_args": { "type": "object" },
-                        "tool_result": { "type": "object" },
-                        "reason": { "type": "string" }
-                    },
-                    "required": ["thread", "event_type", "role", "reason"]
-                }
-            }
-        }),
-        json!({
-            "type": "function",
-            "function": {
-                "name": "knowledge_apply",
-                "description": "Create or update a knowledge document. The patch object controls what gets written. IMPORTANT: use body_append to write body content; without it the doc will have an empty body.",
-                "parameters": {
-                    "type": "object",
-                    "properties": {
-                        "patch": {
-                            "type": "object",
-                            "properties": {
-                                "doc_path": { "type": "string", "description": "Path relative to vault root, e.g. summaries/sources/my-doc.md or knowledge/projects/foo.md" },
-                                "title": { "type": "string", "description": "Document title (required for new docs)" },
-                                "type": { "type": "string", "description": "Doc type: source_summary, project, person, preference, system (required for new docs)" },
-                                "status": { "type": "string", "description": "e.g. active (default)" },
-                                "confidence": { "type": "number", "description": "0.0-1.0 confidence score" },
-                                "tags_add": { "type": "array", "items": { "type": "string" }, "description": "Tags to add" },
-                                "tags_remove": { "type": "array", "items": { "type": "string" }, "description": "Tags to remove" },
-                                "body_append": { "type": "string", "description": "Markdown content to write as the document body. THIS IS HOW YOU WRITE CONTENT. Without it the doc will be empty." },
-                                "sources_add": { "type": "array", "items": { "type": "object", "properties": { "thread_id": { "type": "string" }, "event_ids": { "type": "array", "items": { "type": "string" } } } }, "description": "Source references (optional)" },
-                                "supersedes_add": { "type": "array", "items": { "type": "string" }, "description": "IDs of docs this supersedes" },
-                                "summary": { "type": "string", "description": "One-line description of the entire document (not the change). Max 150 chars. Required for new docs, updates the existing summary on existing docs." }
-                            },
-                            "required": ["doc_path"]
-                        },
-                        "author": { "type": "string" },
-                        "reason": { "type": "string" },
-                        "change_summary": { "type": "string", "description": "One-line description of what this specific mutation does. Max 150 chars. Example: 'Created project doc for J Gateway with tech stack and architecture'" },
-                        "proposal_id": { "type": "string" },
-                        "commit": { "type": "boolean" }
-                    },
-                    "required": ["patch", "author", "reason"]
-                }
-            }
-        }),
-        json!({
-            "type": "function",
-            "function": {
-                "name": "knowledge_read",
-                "description": "Read a knowledge document from the vault.",
-                "parameters": {
-                    "type": "object",
-                    "properties": {
-                        "doc_path": { "type": "string", "description": "Path relative to the vault root, e.g. knowledge/prefs/interaction.md" },
-                        "include_body": { "type": "boolean", "description": "Include body content (default true)." },
-                        "reason": { "type": "string" }
-                    },
-                    "required": ["doc_path", "reason"]
-                }
-            }
-        }),
-        json!({
-            "type": "function",
-            "function": {
-                "name": "knowledge_search",
-                "description": "Search knowledge documents for a substring match.",
-                "parameters": {
-                    "type": "object",
-                    "properties": {
-                        "query": { "type": "string" },
-                        "mode": { "type": "string", "description": "auto|vector|substring (default auto)" },
-                        "limit": { "type": "integer" },
-                        "reason": { "type": "string" }
-                    },
-                    "required": ["query", "reason"]
-                }
-            }
-        }),
-        json!({
-            "type": "function",
-            "function": {
-                "name": "knowledge_index",
-                "description": "Build or rebuild the knowledge embedding index.",
-                "parameters": {
-                    "type": "object",
-                    "properties": {
-                        "reason": { "type": "string" }
-                    },
-                    "required": ["reason"]
-                }
-            }
-        }),
-        json!({
-            "type": "function",
-            "function": {
-                "name": "draw",
-                "description": "Draw an image onto the user's canvas using rcast. Accepts a URL or local file path. Use this to show images, diagrams, or visual content to the user.",
-                "parameters": {
-                    "type": "object",
-                    "properties": {
-                        "source": { "type": "string", "description": "URL or local file path of the image to display." },
-                        "overlay": { "type": "boolean", "description": "If true, overlay on existing canvas instead of clearing." },
-                        "reason": { "type": "string" }
-                    },
-                    "required": ["source", "reason"]
-                }
-            }
-        }),
-        json!({
-            "type": "function",
-            "function": {
-                "name": "generate_image",
-                "description": "Generate an image using flux2 and store it in the vault media directory. Path should use descriptive folders for uniqueness (e.g. 'diagrams/arch-v2.png', 'food/pepperoni-pizza.png'). Returns error if path already exists.",
-                "parameters": {
-                    "type": "object",
-                    "properties": {
-                        "prompt": { "type": "string", "description": "Text description of the image to generate." },
-                        "path": { "type": "string", "description": "Relative path within media/ (e.g. 'food/pizza.png'). Must end with .png. Intermediate directories are created automatically." },
-                        "reason": { "type": "string" }
-                    },
-                    "required": ["prompt", "path", "reason"]
-                }
-            }
-        }),
-        json!({
-            "type": "function",
-            "function": {
-                "name": "deep_think",
-                "description": "Trigger deep thinking. Calls a slower model to reflect on the conversation, search knowledge, and produce inner monologue. The result is appended to the thread as internal context visible on your next turn. Use when the conversation would benefit from deeper analysis, pattern recognition, or knowledge retrieval. The inner monologue will NOT be shown to the user.",
-                "parameters": {
-                    "type": "object",
-                    "properties": {
-                        "prompt": {
-                            "type": "string",
-                            "description": "What to think about. If omitted, reflects on the overall conversation."
-                        },
-                        "reason": { "type": "string" }
-                    },
-                    "required": ["reason"]
-                }
-            }
-        }),
-    ]
+/// Error result for a call whose arguments don't match the tool's schema.
+/// Lists each problem with its JSON pointer so the model can correct the call.
+fn invalid_arguments(config: &AgentConfig, call: &ToolCall) -> Option<Value> {
+    // Unknown tools are reported by run_tool
+    let tool = config.tools.get(&call.name, config.tool_filter.as_deref())?;
+    let violations = tool.validate(&call.arguments);
+    if violations.is_empty() {
+        return None;
+    }
+    let details: Vec<Value> = violations
+        .iter()
+        .map(|v| json!({"pointer": v.pointer, "message": v.message}))
+        .collect();
+    Some(json!({
+        "status": "error",
+        "error": format!("invalid arguments for {}: {}", call.name, crate::schema::describe(&violations)),
+        "violations": details,
+    }))
 }
 
-fn execute_tool(
-    name: &str,
-    args: &Value,
-    config: &AgentConfig,
-) -> Result<Value> {
-    let vault = &config.vault_path;
-    let thread_path = &config.thread_path;
-    let allow_commit = config.allow_commit;
-    match name {
-        "vault_init" => {
-            let path = args
-                .get("path")
-                .and_then(|val| val.as_str())
-                .map(PathBuf::from)
-                .unwrap_or_else(|| vault.to_path_buf());
-            init_vault(&path)?;
-            Ok(json!({ "vault_path": path }))
-        }
-        "thread_create" => {
-            let vault_path = args
-                .get("vault")
-                .and_then(|val| val.as_str())
-                .map(PathBuf::from)
-                .unwrap_or_else(|| vault.to_path_buf());
-            let thread_id = args
-                .get("thread_id")
-                .and_then(|val| val.as_str())
-                .map(|s| s.to_string());
-            let date = args
-                .get("date")
-                .and_then(|val| val.as_str())
-                .map(|s| chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d"))
-                .transpose()?;
-            let path = create_thread(&vault_path, thread_id, date, None)?;
-            Ok(json!({ "thread_path": path }))
-        }
-        "thread_read" => {
-            let path = args
-                .get("thread")
-                .and_then(|val| val.as_str())
-                .map(PathBuf::from)
-                .unwrap_or_else(|| thread_path.to_path_buf());
-            let offset = args.get("offset").and_then(|val| val.as_u64()).map(|v| v as usize);
-            let limit = args.get("limit").and_then(|val| val.as_u64()).map(|v| v as usize);
-            let lines = read_thread(&path, offset, limit)?;
-            Ok(json!({ "count": lines.len(), "lines": lines }))
-        }
-        "thread_append" => {
-            let path = args
-                .get("thread")
-                .and_then(|val| val.as_str())
-                .map(PathBuf::from)
-                .unwrap_or_else(|| thread_path.to_path_buf());
-            let event_type = args
-                .get("event_type")
-                .and_then(|val| val.as_str())
-                .ok_or_else(|| anyhow!("event_type required"))?;
-            let role = args
-                .get("role")
-                .and_then(|val| val.as_str())
-                .ok_or_else(|| anyhow!("role required"))?;
-            let event_type = parse_event_type(event_type)?;
-            let role = parse_role(role)?;
-
-            let content = args.get("content").and_then(|val| val.as_str()).map(|s| Value::String(s.to_string()));
-            let content_json = args.get("content_json").cloned();
-            let content_value = content_json.or(content);
-
-            let tool_name = args.get("tool_name").and_then(|val| val.as_str()).map(|s| s.to_string());
-            let tool_args = args.get("tool_args").cloned();
-            let tool_result = args.get("tool_result").cloned();
-            let reason = args.get("reason").and_then(|val| val.as_str()).map(|s| s.to_string());
-
-            let event = build_event(None, event_type, role, content_value, tool_name, tool_args, tool_result, reason);
-            append_event(&path, event)?;
-            Ok(json!({"thread_path": path}))
-        }
-        "knowledge_apply" => {
-            let patch_value = args
-                .get("patch")
-                .ok_or_else(|| anyhow!("patch required"))?
-                .clone();
-            let patch: KnowledgePatch = serde_json::from_value(patch_value)?;
-            let author = args
-                .get("author")
-                .and_then(|val| val.as_str())
-                .unwrap_or("assistant");
-            let reason = args
-                .get("reason")
-                .and_then(|val| val.as_str())
-                .unwrap_or("tool_call");
-            let proposal_id = args
-                .get("proposal_id")
-                .and_then(|val| val.as_str())
-                .map(|s| s.to_string());
-            let commit = args.get("commit").and_then(|val| val.as_bool()).unwrap_or(false);
-            if commit && !allow_commit {
-                return Err(anyhow!("commit requested but allow_commit is false"));
-            }
-
-            let change_summary = args
-                .get("change_summary")
-                .and_then(|val| val.as_str())
-                .unwrap_or("");
-            let result = apply_patch(vault, patch, author, reason, proposal_id.clone(), change_summary)?;
-            let ledger_path = vault.join("audit/ledger.jsonl");
-            append_ledger(&ledger_path, &result.ledger_entry)?;
-
-            if commit {
-                let repo_root = PathBuf::from(".");
-                let message = match &proposal_id {
-                    Some(id) => format!("{id}: {reason}"),
-                    None => format!("memory: {reason}"),
-                };
-                git_commit(&repo_root, &[result.doc_path.clone(), ledger_path.clone()], &message)?;
-            }
-
-            Ok(json!({
-                "doc_path": result.doc_path,
-                "ledger_id": result.ledger_entry.ledger_id
-            }))
+/// Apply the tool's policy. Returns why the call may not run, or None if it
+/// may. Denials, answers to "ask" and approvals cut short by a cancel are
+/// recorded on the thread.
+pub async fn authorize(config: &AgentConfig, call: &ToolCall) -> Result<Option<String>> {
+    // Unknown tools are reported by run_tool
+    let Some(tool) = config.tools.get(&call.name, config.tool_filter.as_deref()) else {
+        return Ok(None);
+    };
+    let policy = crate::config::current().permissions.policy(&call.name, tool.permission());
+    // `approved` is None when the run was cancelled while waiting for an answer
+    let (approved, by, approval_id) = match (policy, &config.approver) {
+        (ToolPolicy::Allow, _) => return Ok(None),
+        (ToolPolicy::Deny, _) | (ToolPolicy::Ask, None) => (Some(false), "policy", None),
+        (ToolPolicy::Ask, Some(Approver::Terminal)) => {
+            let (name, args, cancel) = (call.name.clone(), call.arguments.clone(), config.cancel.clone());
+            // The prompt watches the token itself: a read left running after
+            // a cancel would take the next REPL line as its answer
+            let answer = tokio::task::spawn_blocking(move || confirm_on_terminal(&name, &args, &cancel)).await??;
+            (answer, "user", None)
         }
-        "knowledge_read" => {
-            let doc_path = args
-                .get("doc_path")
-                .and_then(|val| val.as_str())
-                .ok_or_else(|| anyhow!("doc_path required"))?;
-            let include_body = args
-                .get("include_body")
-                .and_then(|val| val.as_bool())
-                .unwrap_or(true);
-            let full_path = vault.join(doc_path);
-            if !full_path.starts_with(vault) {
-                return Err(anyhow!("doc_path must be within vault"));
+        (ToolPolicy::Ask, Some(Approver::Gateway(pending))) => {
+            let approval_id = format!("apr_{}", ulid::Ulid::new());
+            let answer = pending.register(&approval_id);
+            if let Some(ref sink) = config.event_sink {
+                let _ = sink.send(AgentEvent::ApprovalRequired {
+                    approval_id: approval_id.clone(),
+                    tool_name: call.name.clone(),
+                    arguments: call.arguments.clone(),
+                });
             }
-            let doc = read_doc(&full_path)?;
-            if include_body {
-                Ok(json!({ "doc_path": doc_path, "front_matter": doc.front_matter, "body": doc.body }))
-            } else {
-                Ok(json!({ "doc_path": doc_path, "front_matter": doc.front_matter }))
+            // A dropped sender (the session went away) counts as a denial
+            let answer = config.cancel.guard(async { Ok(answer.await.unwrap_or(false)) }).await.ok();
+            if answer.is_none() {
+                // Otherwise a late session.approve would find a stale entry
+                pending.forget(&approval_id);
             }
+            (answer, "user", Some(approval_id))
         }
-        "knowledge_search" => {
-            let query = args
-                .get("query")
-                .and_then(|val| val.as_str())
-                .ok_or_else(|| anyhow!("query required"))?
-                .to_lowercase();
-            let limit = args.get("limit").and_then(|val| val.as_u64()).unwrap_or(10) as usize;
-            let mode = args
-                .get("mode")
-                .and_then(|val| val.as_str())
-                .unwrap_or("auto");
-
-            if mode == "vector" || mode == "auto" {
-                if let Ok(client) = EmbeddingClient::from_env() {
-                    if let Ok(hits) = search_knowledge_index(vault, &client, &query, limit) {
-                        let items: Vec<Value> = hits
-                            .into_iter()
-                            .map(|hit| {
-                                json!({
-                                    "doc_path": hit.doc_path,
-                                    "chunk_id": hit.chunk_id,
-                                    "score": hit.score,
-                                    "excerpt": hit.excerpt
-                                })
-                            })
-                            .collect();
-                        return Ok(json!({ "mode": "vector", "count": items.len(), "matches": items }));
-                    }
-                }
-                if mode == "vector" {
-                    return Err(anyhow!("vector search unavailable (missing index or provider)"));
-                }
-            }
+    };
 
-            let root = vault.join("knowledge");
-            let mut matches = Vec::new();
-            for path in walk_markdown(&root)? {
-                let content = fs::read_to_string(&path)?;
-                let haystack = content.to_lowercase();
-                if let Some(idx) = haystack.find(&query) {
-                    let excerpt = excerpt_at(&content, idx, 80);
-                    let rel = path.strip_prefix(vault).unwrap_or(&path).to_string_lossy().to_string();
-                    matches.push(json!({
-                        "doc_path": rel,
-                        "excerpt": excerpt
-                    }));
-                    if matches.len() >= limit {
-                        break;
-                    }
-                }
-            }
-            Ok(json!({ "mode": "substring", "count": matches.len(), "matches": matches }))
-        }
-        "draw" => {
-            let source = args
-                .get("source")
-                .and_then(|v| v.as_str())
-                .ok_or_else(|| anyhow!("draw requires 'source' (URL or file path)"))?;
-
-            // If source looks like a URL, download to a temp file first.
-            let file_path = if source.starts_with("http://") || source.starts_with("https://") {
-                let resp = reqwest::blocking::get(source)?;
-                if !resp.status().is_success() {
-                    return Err(anyhow!("failed to download {source}: HTTP {}", resp.status()));
-                }
-                let bytes = resp.bytes()?;
-                let ext = source.rsplit('.').next().unwrap_or("png");
-                let tmp = std::env::temp_dir().join(format!("j_draw.{ext}"));
-                fs::write(&tmp, &bytes)?;
-                tmp
-            } else if Path::new(source).is_absolute() {
-                PathBuf::from(source)
-            } else {
-                // Resolve relative paths against the vault root
-                vault.join(source)
-            };
+    let decision = match approved {
+        Some(true) => "approved",
+        Some(false) => "denied",
+        None => "cancelled",
+    };
+    let event = build_event(
+        None,
+        EventType::PermissionDecision,
+        Role::System,
+        Some(json!({
+            "decision": decision,
+            "policy": policy.as_str(),
+            "by": by,
+            "approval_id": approval_id,
+        })),
+        Some(call.name.clone()),
+        None,
+        None,
+        None,
+    );
+    append_event(&config.thread_path, event)?;
+    let Some(approved) = approved else {
+        return Err(Cancelled.into());
+    };
 
-            let mut cmd = std::process::Command::new("rcast");
-            cmd.arg("draw").arg(&file_path);
+    Ok(match (approved, policy) {
+        (true, _) => None,
+        (false, ToolPolicy::Ask) if by == "user" => Some(format!("{} was denied by the user", call.name)),
+        (false, ToolPolicy::Ask) => Some(format!("{} needs approval and nobody can approve it here", call.name)),
+        (false, _) => Some(format!("{} is denied by policy", call.name)),
+    })
+}
 
-            if let Some(true) = args.get("overlay").and_then(|v| v.as_bool()) {
-                cmd.arg("--overlay");
-            }
+/// Ask on the terminal. None if `cancel` fires before the user answers.
+fn confirm_on_terminal(name: &str, args: &Value, cancel: &CancelToken) -> Result<Option<bool>> {
+    use std::io::Write;
+    eprint!("Allow {name} {args}? [y/N] ");
+    std::io::stderr().flush()?;
+    let Some(answer) = read_line_cancellable(cancel)? else {
+        eprintln!();
+        return Ok(None);
+    };
+    Ok(Some(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")))
+}
 
-            let output = cmd.output()?;
-            if output.status.success() {
-                Ok(json!({ "drawn": source }))
-            } else {
-                let stderr = String::from_utf8_lossy(&output.stderr);
-                Err(anyhow!("rcast draw failed: {stderr}"))
-            }
+/// One line from stdin, or None once `cancel` fires. Reads the descriptor a
+/// byte at a time between short polls so nothing past the line is consumed.
+#[cfg(unix)]
+fn read_line_cancellable(cancel: &CancelToken) -> Result<Option<String>> {
+    let mut line = Vec::new();
+    loop {
+        let mut fds = libc::pollfd { fd: libc::STDIN_FILENO, events: libc::POLLIN, revents: 0 };
+        let ready = unsafe { libc::poll(&mut fds, 1, 100) };
+        if cancel.is_cancelled() {
+            return Ok(None);
         }
-        "knowledge_index" => {
-            let client = EmbeddingClient::from_env()?;
-            let stats = build_knowledge_index(vault, &client)?;
-            Ok(json!({
-                "doc_count": stats.doc_count,
-                "chunk_count": stats.chunk_count,
-                "index_path": stats.index_path,
-                "provider": stats.provider,
-                "model": stats.model
-            }))
+        if ready == 0 {
+            continue;
         }
-        "generate_image" => {
-            let prompt = args
-                .get("prompt")
-                .and_then(|v| v.as_str())
-                .ok_or_else(|| anyhow!("generate_image requires 'prompt'"))?;
-            let rel_path = args
-                .get("path")
-                .and_then(|v| v.as_str())
-                .ok_or_else(|| anyhow!("generate_image requires 'path'"))?;
-
-            // Validate path: no .., no leading /, no backslash, must end with .png
-            if rel_path.contains("..")
-                || rel_path.starts_with('/')
-                || rel_path.contains('\\')
-                || rel_path.contains('\0')
-                || !rel_path.ends_with(".png")
-            {
-                return Err(anyhow!(
-                    "invalid path: must be relative, no '..', and end with .png"
-                ));
-            }
-            // Additional check: only allow alphanumeric, dash, underscore, slash, dot
-            if !rel_path
-                .chars()
-                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '/' | '.'))
-            {
-                return Err(anyhow!(
-                    "invalid path: only alphanumeric, dash, underscore, slash allowed"
-                ));
-            }
-
-            let media_dir = vault.join("media");
-            let full_path = media_dir.join(rel_path);
-
-            // Verify resolved path is within media/
-            if let Ok(canonical_parent) = full_path.parent().unwrap_or(&media_dir).canonicalize() {
-                let canonical_media = media_dir.canonicalize().unwrap_or_else(|_| media_dir.clone());
-                if !canonical_parent.starts_with(&canonical_media) {
-                    return Err(anyhow!("path escapes media directory"));
+        let mut byte = 0u8;
+        let read = if ready > 0 {
+            unsafe { libc::read(libc::STDIN_FILENO, (&raw mut byte).cast(), 1) }
+        } else {
+            -1
+        };
+        match read {
+            0 => break,
+            1 if byte == b'\n' => break,
+            1 => line.push(byte),
+            _ => {
+                // Ctrl-C interrupts poll; the token check above then stops us
+                let err = std::io::Error::last_os_error();
+                if err.kind() != std::io::ErrorKind::Interrupted {
+                    return Err(err.into());
                 }
             }
+        }
+    }
+    Ok(Some(String::from_utf8_lossy(&line).into_owned()))
+}
 
-            // Check existence
-            if full_path.exists() {
-                return Err(anyhow!("exists: media/{rel_path}"));
-            }
+/// Without poll the read can't be interrupted; a cancel is noticed once the
+/// user answers, and the answer is dropped.
+#[cfg(not(unix))]
+fn read_line_cancellable(cancel: &CancelToken) -> Result<Option<String>> {
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok((!cancel.is_cancelled()).then_some(answer))
+}
 
-            // Create parent directories
-            if let Some(parent) = full_path.parent() {
-                fs::create_dir_all(parent)?;
-            }
+/// Execute one tool call and wrap the outcome as the tool result payload.
+async fn run_tool(config: AgentConfig, call: ToolCall) -> Value {
+    let Some(tool) = config.tools.get(&call.name, config.tool_filter.as_deref()).cloned() else {
+        return json!({"status": "error", "error": format!("unknown tool: {}", call.name)});
+    };
+    // Tools do file, git and embedding I/O; keep them off the async workers
+    let result = tokio::task::spawn_blocking(move || {
+        let data = tool.execute(&call.arguments, &config)?;
+        // Oversized output goes to an artifact so it stays out of the context and thread
+        let spilled = match tool.output_budget() {
+            Some(budget) => spill(&config.vault_path, &call.name, &data, budget)?,
+            None => None,
+        };
+        Ok(spilled.unwrap_or(data))
+    })
+    .await
+    .map_err(|e| anyhow!("tool task panicked: {e}"))
+    .and_then(|r| r);
+    match result {
+        Ok(data) => json!({"status": "ok", "data": data}),
+        Err(err) => json!({"status": "error", "error": err.to_string()}),
+    }
+}
 
-            // Run flux2
-            let output = std::process::Command::new("flux2")
-                .arg(prompt)
-                .arg(&full_path)
-                .output();
-
-            match output {
-                Ok(out) if out.status.success() => {
-                    if full_path.exists() {
-                        Ok(json!({ "path": format!("media/{rel_path}") }))
-                    } else {
-                        Err(anyhow!("flux2 completed but output file not found"))
-                    }
-                }
-                Ok(out) => {
-                    // Clean up partial file
-                    let _ = fs::remove_file(&full_path);
-                    let stderr = String::from_utf8_lossy(&out.stderr);
-                    Err(anyhow!("flux2 failed: {stderr}"))
-                }
-                Err(e) => {
-                    let _ = fs::remove_file(&full_path);
-                    if e.kind() == std::io::ErrorKind::NotFound {
-                        Err(anyhow!("flux2 not found"))
-                    } else {
-                        Err(anyhow!("flux2 error: {e}"))
-                    }
-                }
-            }
-        }
-        "deep_think" => {
-            let prompt = args.get("prompt").and_then(|v| v.as_str()).unwrap_or("").to_string();
-            let reason = args
-                .get("reason")
-                .and_then(|v| v.as_str())
-                .unwrap_or("deep_think")
-                .to_string();
-
-            // Check if already running
-            if config.deep_think_running.compare_exchange(
-                false, true, Ordering::SeqCst, Ordering::SeqCst,
-            ).is_err() {
-                return Ok(json!({ "status": "already_running" }));
-            }
+/// Record token usage and its cost on the event produced by an LLM call.
+/// Fills in the model when the caller didn't set one, so the call can be priced.
+pub fn attach_usage(
+    event: &mut ThreadEvent,
+    usage: Option<Usage>,
+    model: &str,
+) {
+    if let Some(usage) = usage {
+        let model = event.model.get_or_insert_with(|| model.to_string());
+        event.cost_usd = crate::usage::cost_usd(model, &usage);
+        event.usage = Some(usage);
+    }
+}
 
-            let vault_path = vault.to_path_buf();
-            let thread_path = thread_path.to_path_buf();
-            let running = Arc::clone(&config.deep_think_running);
-            let event_sink = config.event_sink.clone();
-
-            std::thread::spawn(move || {
-                let result = deep_think_background(
-                    &vault_path,
-                    &thread_path,
-                    &prompt,
-                    &reason,
-                );
-                match result {
-                    Ok(monologue) => {
-                        if let Some(ref sink) = event_sink {
-                            let _ = sink.send(AgentEvent::DeepThinkComplete { monologue });
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!("deep_think background error: {e}");
-                    }
-                }
-                running.store(false, Ordering::SeqCst);
-            });
+pub fn with_datetime(ts: DateTime<Utc>, content: &str) -> String {
+    let local = ts.with_timezone(&Local).format("%Y-%m-%dT%H:%M:%S");
+    if content.is_empty() {
+        format!("[{local}]")
+    } else {
+        format!("[{local}] {content}")
+    }
+}
 
-            Ok(json!({ "status": "queued" }))
-        }
-        _ => Err(anyhow!("unknown tool: {name}")),
+fn tool_calls_payload(response: &ChatResponse) -> Result<Vec<Value>> {
+    let mut payload = Vec::new();
+    for call in &response.tool_calls {
+        let args = serde_json::to_string(&call.arguments)?;
+        payload.push(json!({
+            "id": call.id,
+            "type": "function",
+            "function": {
+                "name": call.name,
+                "arguments": args
+            }
+        }));
     }
+    Ok(payload)
 }
 
-/// Run deep_think work in a background thread: read transcript, call slow model
+/// Run deep_think work as a background task: read transcript, call slow model
 /// with knowledge tool access, append InnerMonologue to thread.
-fn deep_think_background(
+pub async fn deep_think_background(
     vault_path: &Path,
     thread_path: &Path,
     prompt: &str,
     reason: &str,
+    tools: Arc<ToolRegistry>,
 ) -> Result<String> {
     // 1. Read recent thread events for context
     let lines = read_thread(thread_path, None, Some(50))?;
@@ -842,22 +651,8 @@ fn deep_think_background(
         deep_messages.push(json!({"role": "user", "content": "Reflect on the overall conversation. What patterns do you see? What should I consider next?"}));
     }
 
-    // 3. Create engine for deep think — uses DEEP_THINK_ENGINE if set, else main engine
-    let mut client = if let Ok(dt_engine) = std::env::var("DEEP_THINK_ENGINE") {
-        let kind = match dt_engine.as_str() {
-            "openai" => crate::engine::EngineKind::OpenAI,
-            "anthropic" => crate::engine::EngineKind::Anthropic,
-            "gemini" => crate::engine::EngineKind::Gemini,
-            other => return Err(anyhow!("invalid DEEP_THINK_ENGINE={other}")),
-        };
-        crate::engine::create_engine_of_kind(kind)?
-    } else {
-        crate::engine::create_engine()?
-    };
-    // Override model if OPENAI_DEEP_THINK_MODEL is set (backward compat)
-    if let Ok(deep_model) = std::env::var("OPENAI_DEEP_THINK_MODEL") {
-        client.set_model(deep_model);
-    }
+    // 3. Create engine for deep think — the "deep" role, else the main engine
+    let client = crate::engine::create_role_engine(crate::config::Role::Deep).await?;
 
     // 4. Run agent loop with tool filter for knowledge tools, max 3 turns
     let inner_config = AgentConfig {
@@ -865,17 +660,21 @@ fn deep_think_background(
         thread_path: thread_path.to_path_buf(),
         max_turns: 3,
         allow_commit: false,
+        tools,
         tool_filter: Some(vec![
             "knowledge_search".into(),
             "knowledge_read".into(),
         ]),
-        event_sink: None,
+        // Private reflection: a sink nobody reads keeps it off the user's stdout
+        event_sink: Some(tokio::sync::mpsc::unbounded_channel().0),
+        approver: None,
+        cancel: CancelToken::default(),
         deep_think_running: Arc::new(AtomicBool::new(false)),
         engine_name: None,
         model_name: None,
     };
 
-    let final_messages = run_agent_loop(&inner_config, deep_messages, client.as_ref())?;
+    let final_messages = run_agent_loop(&inner_config, deep_messages, client.as_ref()).await?;
 
     // 5. Extract final content from returned messages
     let monologue = final_messages
@@ -905,59 +704,3 @@ fn deep_think_background(
 
     Ok(monologue)
 }
-
-fn parse_event_type(value: &str) -> Result<EventType> {
-    match value {
-        "user_message" => Ok(EventType::UserMessage),
-        "assistant_message" => Ok(EventType::AssistantMessage),
-        "tool_call" => Ok(EventType::ToolCall),
-        "tool_result" => Ok(EventType::ToolResult),
-        "system_note" => Ok(EventType::SystemNote),
-        "attachment_added" => Ok(EventType::AttachmentAdded),
-        "inner_monologue" => Ok(EventType::InnerMonologue),
-        "title_generated" => Ok(EventType::TitleGenerated),
-        _ => Err(anyhow!("invalid event_type: {value}")),
-    }
-}
-
-fn parse_role(value: &str) -> Result<Role> {
-    match value {
-        "user" => Ok(Role::User),
-        "assistant" => Ok(Role::Assistant),
-        "tool" => Ok(Role::Tool),
-        "system" => Ok(Role::System),
-        _ => Err(anyhow!("invalid role: {value}")),
-    }
-}
-
-fn walk_markdown(root: &Path) -> Result<Vec<PathBuf>> {
-    let mut files = Vec::new();
-    if !root.exists() {
-        return Ok(files);
-    }
-    let mut stack = vec![root.to_path_buf()];
-    while let Some(dir) = stack.pop() {
-        for entry in fs::read_dir(&dir)? {
-            let entry = entry?;
-            let path = entry.path();
-            if path.is_dir() {
-                stack.push(path);
-            } else if path.extension().and_then(|s| s.to_str()) == Some("md") {
-                files.push(path);
-            }
-        }
-    }
-    Ok(files)
-}
-
-fn excerpt_at(content: &str, idx: usize, radius: usize) -> String {
-    let mut start = idx.saturating_sub(radius);
-    let mut end = usize::min(content.len(), idx + radius);
-    while start < content.len() && !content.is_char_boundary(start) {
-        start += 1;
-    }
-    while end > start && !content.is_char_boundary(end) {
-        end -= 1;
-    }
-    content[start..end].replace('\n', " ")
-}
@@ -1,9 +1,14 @@
-use anyhow::{Context, Result};
-use reqwest::blocking::Client;
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use reqwest::Client;
 use serde_json::{json, Value};
 use std::time::Duration;
 
-use crate::engine::{ChatResponse, Engine, ToolCall};
+use crate::engine::{ChatResponse, DeltaSink, Engine, GenerationParams, ToolCall};
+use crate::media::ContentPart;
+use crate::retry::{self, RetryHook, RetryPolicy};
+use crate::schema::JsonSchema;
+use crate::sse;
 
 pub struct AnthropicClient {
     api_key: String,
@@ -11,6 +16,8 @@ pub struct AnthropicClient {
     http: Client,
     model: String,
     max_tokens: usize,
+    temperature: Option<f64>,
+    retry: RetryPolicy,
 }
 
 impl AnthropicClient {
@@ -25,14 +32,30 @@ impl AnthropicClient {
             http,
             model,
             max_tokens,
+            temperature: None,
+            retry: RetryPolicy::default(),
         }
     }
-}
 
-impl Engine for AnthropicClient {
-    fn chat(&self, messages: &[Value], tools: &[Value]) -> Result<ChatResponse> {
-        let url = format!("{}/v1/messages", self.base_url.trim_end_matches('/'));
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
 
+    fn url(&self) -> String {
+        format!("{}/v1/messages", self.base_url.trim_end_matches('/'))
+    }
+
+    fn post(&self, body: &Value) -> reqwest::RequestBuilder {
+        self.http
+            .post(self.url())
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", "2023-06-01")
+            .header("content-type", "application/json")
+            .json(body)
+    }
+
+    fn request_body(&self, messages: &[Value], tools: &[Value]) -> Value {
         // Separate system messages from conversation messages.
         // OpenAI format puts system as role:"system" in messages array.
         // Anthropic wants a top-level "system" string parameter.
@@ -48,6 +71,13 @@ impl Engine for AnthropicClient {
                     system_parts.push(content.to_string());
                 }
                 "user" => {
+                    // Content parts (text + images) become Anthropic content blocks
+                    let content = match msg.get("content") {
+                        Some(Value::Array(parts)) => {
+                            Value::Array(parts.iter().filter_map(convert_content_part).collect())
+                        }
+                        _ => Value::String(content.to_string()),
+                    };
                     converted_messages.push(json!({
                         "role": "user",
                         "content": content,
@@ -128,20 +158,28 @@ impl Engine for AnthropicClient {
         if !anthropic_tools.is_empty() {
             body["tools"] = Value::Array(anthropic_tools);
         }
+        if let Some(temperature) = self.temperature {
+            body["temperature"] = json!(temperature);
+        }
+        body
+    }
 
+    /// Non-streaming Messages API call.
+    async fn complete(&self, body: &Value) -> Result<ChatResponse> {
         let resp: Value = self
-            .http
-            .post(url)
-            .header("x-api-key", &self.api_key)
-            .header("anthropic-version", "2023-06-01")
-            .header("content-type", "application/json")
-            .json(&body)
-            .send()
-            .context("send anthropic messages")?
-            .error_for_status()
-            .context("anthropic messages status")?
-            .json()
-            .context("parse anthropic messages response")?;
+            .retry
+            .run_async("anthropic messages", || {
+                let request = self.post(body);
+                async move {
+                    retry::send_async(request)
+                        .await
+                        .context("send anthropic messages")?
+                        .json()
+                        .await
+                        .context("parse anthropic messages response")
+                }
+            })
+            .await?;
 
         // Parse response: content is an array of blocks
         let content_blocks = resp
@@ -188,12 +226,94 @@ impl Engine for AnthropicClient {
         } else {
             Some(text_parts.join(""))
         };
+        let usage = resp.get("usage").map(crate::usage::from_anthropic);
 
         Ok(ChatResponse {
             content,
             tool_calls,
+            usage,
         })
     }
+}
+
+#[async_trait]
+impl Engine for AnthropicClient {
+    async fn chat(&self, messages: &[Value], tools: &[Value]) -> Result<ChatResponse> {
+        let body = self.request_body(messages, tools);
+        self.complete(&body).await
+    }
+
+    /// Anthropic has no JSON mode; force a call to a tool whose input schema
+    /// is the requested schema and return the tool input as the JSON.
+    async fn chat_json(&self, messages: &[Value], schema: &JsonSchema) -> Result<ChatResponse> {
+        let mut body = self.request_body(messages, &[]);
+        body["tools"] = json!([{
+            "name": schema.name,
+            "description": "Record the response in the required structure.",
+            "input_schema": schema.schema,
+        }]);
+        body["tool_choice"] = json!({ "type": "tool", "name": schema.name });
+        let response = self.complete(&body).await?;
+        let input = response
+            .tool_calls
+            .into_iter()
+            .find(|call| call.name == schema.name)
+            .map(|call| call.arguments)
+            .ok_or_else(|| anyhow!("anthropic did not call the {} tool", schema.name))?;
+        Ok(ChatResponse {
+            content: Some(input.to_string()),
+            tool_calls: Vec::new(),
+            usage: response.usage,
+        })
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[Value],
+        tools: &[Value],
+        on_delta: &mut DeltaSink<'_>,
+    ) -> Result<ChatResponse> {
+        let mut body = self.request_body(messages, tools);
+        body["stream"] = Value::Bool(true);
+
+        // An overloaded error can also arrive inside the stream; that is only
+        // retried while no text has reached the caller yet.
+        let label = "anthropic messages";
+        let mut retries = 0;
+        loop {
+            let result = async {
+                let resp = retry::send_async(self.post(&body))
+                    .await
+                    .context("send anthropic messages")?;
+                let mut state = StreamState::default();
+                sse::read_events(resp, |event| state.handle(&event, &mut *on_delta)).await?;
+                state.finish()
+            }
+            .await;
+            let err = match result {
+                Ok(response) => return Ok(response),
+                Err(err) => err,
+            };
+            let delay = self
+                .retry
+                .next_delay(label, &err, retries)
+                .ok_or_else(|| self.retry.give_up(label, err, retries))?;
+            retries += 1;
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    fn set_retry_hook(&mut self, hook: RetryHook) {
+        self.retry.on_retry = Some(hook);
+    }
+
+    /// `max_tokens` is required by the API, so only a set value replaces it.
+    fn set_params(&mut self, params: GenerationParams) {
+        self.temperature = params.temperature;
+        if let Some(max_tokens) = params.max_tokens {
+            self.max_tokens = max_tokens;
+        }
+    }
 
     fn set_model(&mut self, model: String) {
         self.model = model;
@@ -251,6 +371,21 @@ fn merge_consecutive_roles(messages: Vec<Value>) -> Vec<Value> {
     merged
 }
 
+/// Convert an OpenAI-style content part to an Anthropic content block.
+fn convert_content_part(part: &Value) -> Option<Value> {
+    match crate::media::parse_part(part)? {
+        ContentPart::Text(text) => Some(json!({ "type": "text", "text": text })),
+        ContentPart::InlineImage { mime, data } => Some(json!({
+            "type": "image",
+            "source": { "type": "base64", "media_type": mime, "data": data },
+        })),
+        ContentPart::ImageUrl(url) => Some(json!({
+            "type": "image",
+            "source": { "type": "url", "url": url },
+        })),
+    }
+}
+
 /// Convert a content value to an array of content blocks.
 fn to_content_blocks(content: Value) -> Vec<Value> {
     match content {
@@ -260,3 +395,156 @@ fn to_content_blocks(content: Value) -> Vec<Value> {
         other => vec![json!({"type": "text", "text": other.to_string()})],
     }
 }
+
+/// Accumulates Messages API stream events into a full response.
+/// Content blocks are keyed by `index`; `tool_use` input arrives as
+/// `input_json_delta` fragments that only form valid JSON once the block stops.
+#[derive(Default)]
+struct StreamState {
+    blocks: Vec<PartialBlock>,
+    streamed: bool,
+    usage: Option<crate::usage::Usage>,
+}
+
+enum PartialBlock {
+    Text(String),
+    ToolUse {
+        id: String,
+        name: String,
+        input_json: String,
+    },
+    Other,
+}
+
+impl StreamState {
+    fn handle(
+        &mut self,
+        event: &sse::SseEvent,
+        on_delta: &mut DeltaSink<'_>,
+    ) -> Result<()> {
+        if event.data.is_empty() {
+            return Ok(());
+        }
+        let data: Value =
+            serde_json::from_str(&event.data).context("parse anthropic stream event")?;
+        let event_type = event
+            .event
+            .as_deref()
+            .or_else(|| data.get("type").and_then(|v| v.as_str()))
+            .unwrap_or("");
+        match event_type {
+            // Input tokens arrive up front; output_tokens is cumulative in message_delta
+            "message_start" => {
+                if let Some(usage) = data.get("message").and_then(|m| m.get("usage")) {
+                    self.usage = Some(crate::usage::from_anthropic(usage));
+                }
+            }
+            "message_delta" => {
+                if let Some(usage) = data.get("usage") {
+                    let delta = crate::usage::from_anthropic(usage);
+                    let total = self.usage.get_or_insert_with(Default::default);
+                    total.output_tokens = delta.output_tokens;
+                    if delta.input_tokens > 0 {
+                        total.input_tokens = delta.input_tokens;
+                    }
+                    if delta.cache_read_tokens > 0 {
+                        total.cache_read_tokens = delta.cache_read_tokens;
+                    }
+                    if delta.cache_write_tokens > 0 {
+                        total.cache_write_tokens = delta.cache_write_tokens;
+                    }
+                }
+            }
+            "content_block_start" => {
+                let index = data.get("index").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+                let block = data.get("content_block").unwrap_or(&Value::Null);
+                let partial = match block.get("type").and_then(|v| v.as_str()) {
+                    Some("text") => {
+                        let text = block.get("text").and_then(|v| v.as_str()).unwrap_or("");
+                        if !text.is_empty() {
+                            self.streamed = true;
+                            on_delta(text);
+                        }
+                        PartialBlock::Text(text.to_string())
+                    }
+                    Some("tool_use") => PartialBlock::ToolUse {
+                        id: block.get("id").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+                        name: block
+                            .get("name")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or("")
+                            .to_string(),
+                        input_json: String::new(),
+                    },
+                    _ => PartialBlock::Other,
+                };
+                while self.blocks.len() <= index {
+                    self.blocks.push(PartialBlock::Other);
+                }
+                self.blocks[index] = partial;
+            }
+            "content_block_delta" => {
+                let index = data.get("index").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+                let delta = data.get("delta").unwrap_or(&Value::Null);
+                match (self.blocks.get_mut(index), delta.get("type").and_then(|v| v.as_str())) {
+                    (Some(PartialBlock::Text(text)), Some("text_delta")) => {
+                        if let Some(fragment) = delta.get("text").and_then(|v| v.as_str()) {
+                            text.push_str(fragment);
+                            self.streamed = true;
+                            on_delta(fragment);
+                        }
+                    }
+                    (Some(PartialBlock::ToolUse { input_json, .. }), Some("input_json_delta")) => {
+                        if let Some(fragment) = delta.get("partial_json").and_then(|v| v.as_str()) {
+                            input_json.push_str(fragment);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            "error" => {
+                let err = data.get("error").cloned().unwrap_or(Value::Null);
+                let overloaded =
+                    err.get("type").and_then(|v| v.as_str()) == Some("overloaded_error");
+                if overloaded && !self.streamed {
+                    return Err(anyhow!(retry::Transient {
+                        reason: "overloaded".to_string(),
+                        retry_after: None,
+                    }));
+                }
+                return Err(anyhow!("anthropic stream error: {err}"));
+            }
+            _ => {} // content_block_stop, message_stop, ping
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<ChatResponse> {
+        let mut text_parts: Vec<String> = Vec::new();
+        let mut tool_calls: Vec<ToolCall> = Vec::new();
+        for block in self.blocks {
+            match block {
+                PartialBlock::Text(text) => text_parts.push(text),
+                PartialBlock::ToolUse { id, name, input_json } => {
+                    let arguments = if input_json.trim().is_empty() {
+                        json!({})
+                    } else {
+                        serde_json::from_str(&input_json).context("parse tool_use input as JSON")?
+                    };
+                    tool_calls.push(ToolCall { id, name, arguments });
+                }
+                PartialBlock::Other => {}
+            }
+        }
+        let content = if text_parts.is_empty() {
+            None
+        } else {
+            Some(text_parts.join(""))
+        };
+        Ok(ChatResponse {
+            content,
+            tool_calls,
+            usage: self.usage,
+        })
+    }
+}
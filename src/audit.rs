@@ -1,10 +1,11 @@
+use anyhow::{anyhow, bail, Context, Result};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json;
 use sha2::{Digest, Sha256};
 use std::fs;
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use crate::knowledge::KnowledgePatch;
 
@@ -59,6 +60,93 @@ impl LedgerEntry {
     }
 }
 
+/// Every doc version `apply_patch` writes, and the one it replaces, stored by
+/// SHA-256 so ledger hashes can be turned back into content.
+const OBJECTS_DIR: &str = "audit/objects";
+
+/// Shortest hash prefix accepted in place of a full hash.
+const MIN_PREFIX: usize = 8;
+
+fn object_path(vault: &Path, hash: &str) -> PathBuf {
+    vault.join(OBJECTS_DIR).join(hash)
+}
+
+/// Store `content` under its hash and return the hash. Storing a version
+/// that is already present is a no-op.
+pub fn store_object(vault: &Path, content: &str) -> Result<String> {
+    let hash = hash_str(content);
+    let path = object_path(vault, &hash);
+    if !path.exists() {
+        fs::create_dir_all(vault.join(OBJECTS_DIR))?;
+        // Written aside and renamed so a crash never leaves a truncated object
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, content.as_bytes()).with_context(|| format!("write {}", tmp.display()))?;
+        fs::rename(&tmp, &path)?;
+    }
+    Ok(hash)
+}
+
+/// Content stored under `hash`, which may be a unique prefix of at least 8
+/// hex characters. Returns the full hash with the content.
+pub fn read_object(vault: &Path, hash: &str) -> Result<(String, String)> {
+    let hash = hash.to_ascii_lowercase();
+    if hash.len() < MIN_PREFIX || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        bail!("invalid version hash: {hash} (need at least {MIN_PREFIX} hex characters)");
+    }
+    let full = if hash.len() == 64 {
+        hash
+    } else {
+        let mut found = Vec::new();
+        if let Ok(entries) = fs::read_dir(vault.join(OBJECTS_DIR)) {
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if name.len() == 64 && name.starts_with(&hash) {
+                    found.push(name);
+                }
+            }
+        }
+        match found.len() {
+            0 => bail!("no stored version matches {hash}"),
+            1 => found.remove(0),
+            n => bail!("{hash} matches {n} stored versions; use more characters"),
+        }
+    };
+    let content = fs::read_to_string(object_path(vault, &full))
+        .map_err(|_| anyhow!("version {full} is not in {OBJECTS_DIR}"))?;
+    if hash_str(&content) != full {
+        bail!("stored version {full} is corrupt");
+    }
+    Ok((full, content))
+}
+
+/// All ledger entries, oldest first. Lines that don't parse are skipped.
+pub fn read_ledger(path: &Path) -> Result<Vec<LedgerEntry>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
+    Ok(content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Ledger entries that changed `doc`, oldest first. `doc` is a vault-relative
+/// path or a doc id; a path also picks up entries for the same doc id, so
+/// history follows a doc that was written under another path.
+pub fn doc_history(vault: &Path, doc: &str) -> Result<Vec<LedgerEntry>> {
+    let entries = read_ledger(&vault.join("audit/ledger.jsonl"))?;
+    let ids: Vec<&str> = entries
+        .iter()
+        .filter(|entry| entry.doc_path == doc && !entry.doc_id.is_empty())
+        .map(|entry| entry.doc_id.as_str())
+        .collect();
+    let matches = |entry: &LedgerEntry| {
+        entry.doc_path == doc || entry.doc_id == doc || ids.contains(&entry.doc_id.as_str())
+    };
+    Ok(entries.iter().filter(|entry| matches(entry)).cloned().collect())
+}
+
 pub fn append_ledger(path: &Path, entry: &LedgerEntry) -> anyhow::Result<()> {
     let line = serde_json::to_string(entry)?;
     let mut file = fs::OpenOptions::new()
@@ -70,8 +158,78 @@ pub fn append_ledger(path: &Path, entry: &LedgerEntry) -> anyhow::Result<()> {
     Ok(())
 }
 
-fn hash_str(value: &str) -> String {
+pub fn hash_str(value: &str) -> String {
     let mut hasher = Sha256::new();
     hasher.update(value.as_bytes());
     hex::encode(hasher.finalize())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn empty_vault(name: &str) -> PathBuf {
+        let vault = std::env::temp_dir().join(format!("j-audit-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&vault);
+        vault
+    }
+
+    #[test]
+    fn stored_object_reads_back_by_full_hash_or_prefix() {
+        let vault = empty_vault("roundtrip");
+        let hash = store_object(&vault, "hello\n").unwrap();
+        assert_eq!(hash, hash_str("hello\n"));
+        // Storing the same content again is a no-op
+        assert_eq!(store_object(&vault, "hello\n").unwrap(), hash);
+
+        assert_eq!(read_object(&vault, &hash).unwrap(), (hash.clone(), "hello\n".to_string()));
+        let (full, _) = read_object(&vault, &hash[..8].to_ascii_uppercase()).unwrap();
+        assert_eq!(full, hash);
+        let _ = fs::remove_dir_all(&vault);
+    }
+
+    #[test]
+    fn short_or_non_hex_prefixes_are_rejected() {
+        let vault = empty_vault("invalid");
+        let hash = store_object(&vault, "hello\n").unwrap();
+        let err = read_object(&vault, &hash[..7]).unwrap_err();
+        assert!(err.to_string().starts_with("invalid version hash"), "{err}");
+        let err = read_object(&vault, "../../etc/passwd").unwrap_err();
+        assert!(err.to_string().starts_with("invalid version hash"), "{err}");
+        let _ = fs::remove_dir_all(&vault);
+    }
+
+    #[test]
+    fn unknown_prefix_is_reported() {
+        let vault = empty_vault("unknown");
+        store_object(&vault, "hello\n").unwrap();
+        let other = hash_str("something else");
+        let err = read_object(&vault, &other[..10]).unwrap_err();
+        assert_eq!(err.to_string(), format!("no stored version matches {}", &other[..10]));
+        let err = read_object(&vault, &other).unwrap_err();
+        assert!(err.to_string().contains("is not in audit/objects"), "{err}");
+        let _ = fs::remove_dir_all(&vault);
+    }
+
+    #[test]
+    fn ambiguous_prefix_asks_for_more() {
+        let vault = empty_vault("ambiguous");
+        let dir = vault.join(OBJECTS_DIR);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(format!("abcdef01{}", "0".repeat(56))), "a").unwrap();
+        fs::write(dir.join(format!("abcdef01{}", "1".repeat(56))), "b").unwrap();
+        let err = read_object(&vault, "abcdef01").unwrap_err();
+        assert_eq!(err.to_string(), "abcdef01 matches 2 stored versions; use more characters");
+        let _ = fs::remove_dir_all(&vault);
+    }
+
+    #[test]
+    fn corrupt_object_is_refused() {
+        let vault = empty_vault("corrupt");
+        let hash = store_object(&vault, "hello\n").unwrap();
+        fs::write(object_path(&vault, &hash), "tampered\n").unwrap();
+        let err = read_object(&vault, &hash[..12]).unwrap_err();
+        assert_eq!(err.to_string(), format!("stored version {hash} is corrupt"));
+        let _ = fs::remove_dir_all(&vault);
+    }
+}
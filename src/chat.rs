@@ -14,11 +14,13 @@ use std::sync::Arc;
 use crate::audit::LedgerEntry;
 use crate::knowledge::read_doc;
 
-use crate::agent::{run_agent_loop, tool_schemas, with_datetime, AgentConfig};
+use crate::agent::{run_agent_loop, with_datetime, AgentConfig, Approver, CancelToken, Cancelled};
+use crate::context::{load_history, truncate_lines, ContextBudget};
 use crate::engine::Engine;
 use crate::thread_store::{
-    append_event, build_event, create_thread, read_thread, EventType, Role, ThreadEvent, ThreadMeta,
+    append_event, build_event, create_thread, EventType, Role, ThreadMeta,
 };
+use crate::tools::ToolRegistry;
 use crate::vault::{init_vault, resolve_vault};
 
 pub struct ChatOptions {
@@ -27,7 +29,7 @@ pub struct ChatOptions {
     pub model: Option<String>,
     pub engine: Option<String>,
     pub allow_commit: bool,
-    pub history: usize,
+    pub history: Option<usize>,
     pub direct: bool,
 }
 
@@ -43,26 +45,22 @@ pub async fn run_chat(options: ChatOptions) -> Result<()> {
         eprintln!("(daemon not running, using direct mode)");
     }
 
-    run_chat_direct(options)
+    run_chat_direct(options).await
 }
 
-fn run_chat_direct(options: ChatOptions) -> Result<()> {
+async fn run_chat_direct(options: ChatOptions) -> Result<()> {
     let vault = resolve_vault(options.vault);
     if !vault.exists() {
         init_vault(&vault)?;
     }
 
-    let mut engine = if let Some(ref engine_name) = options.engine {
-        let kind = crate::engine::EngineKind::from_str_opt(engine_name)
-            .ok_or_else(|| anyhow!("unknown engine: {engine_name}. Valid: openai, anthropic, gemini"))?;
-        crate::engine::create_engine_of_kind(kind)?
-    } else {
-        crate::engine::create_engine()?
-    };
-    // CLI --model flag overrides engine default
-    if let Some(ref model_override) = options.model {
-        engine.set_model(model_override.clone());
-    }
+    // CLI --engine/--model override the env and vault config
+    crate::config::init(&vault, crate::config::CliOverrides {
+        role: Some(crate::config::Role::Chat),
+        engine: options.engine.clone(),
+        model: options.model.clone(),
+    })?;
+    let mut engine = crate::engine::create_role_engine(crate::config::Role::Chat).await?;
     let model = engine.model().to_string();
 
     let thread_path = match options.thread {
@@ -81,14 +79,11 @@ fn run_chat_direct(options: ChatOptions) -> Result<()> {
         }))?,
     };
 
-    let system_prompt = load_system_prompt(&vault)?;
-    let mut messages = Vec::new();
-    messages.push(json!({"role":"system","content": system_prompt}));
-    if options.history > 0 {
-        load_history(&thread_path, options.history, &mut messages)?;
-    }
-
-    let tools = tool_schemas();
+    let registry = Arc::new(ToolRegistry::load(&vault).await);
+    let tools = registry.schemas(None);
+    let budget = ContextBudget::for_model(&model, &tools);
+    let mut messages =
+        build_context(&vault, &thread_path, engine.as_ref(), &budget, options.history).await?;
 
     println!("J REPL ready. Thread: {}", thread_path.display());
     println!("Model: {model}. Type /help for commands.");
@@ -128,19 +123,63 @@ fn run_chat_direct(options: ChatOptions) -> Result<()> {
         let user_content = with_datetime(user_event.ts, input);
         append_event(&thread_path, user_event)?;
         messages.push(json!({"role":"user","content": user_content}));
+        // Past the history share build_context fits to: rebuild from the
+        // thread, folding older turns into a summary
+        let budget = ContextBudget::for_model(engine.model(), &tools);
+        let system_prompt = messages
+            .first()
+            .and_then(|m| m.get("content"))
+            .and_then(|c| c.as_str())
+            .unwrap_or_default();
+        let history = messages.get(1..).unwrap_or_default();
+        if budget.messages_tokens(history) > budget.history_for(system_prompt) {
+            messages = build_context(&vault, &thread_path, engine.as_ref(), &budget, options.history).await?;
+        }
 
         let config = AgentConfig {
             vault_path: vault.clone(),
             thread_path: thread_path.clone(),
             max_turns: 20,
             allow_commit: options.allow_commit,
+            tools: registry.clone(),
             tool_filter: None,
             event_sink: None,
+            approver: Some(Approver::Terminal),
+            cancel: CancelToken::default(),
             deep_think_running: deep_think_flag.clone(),
             engine_name: None,
             model_name: Some(model.clone()),
         };
-        messages = run_agent_loop(&config, messages, engine.as_ref())?;
+        // Ctrl-C stops the run; at the prompt rustyline handles it instead
+        let cancel = config.cancel.clone();
+        let on_interrupt = tokio::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                cancel.cancel();
+            }
+        });
+        let result = run_agent_loop(&config, messages, engine.as_ref()).await;
+        on_interrupt.abort();
+        messages = match result {
+            Ok(messages) => messages,
+            Err(e) if e.is::<Cancelled>() => {
+                eprintln!("[cancelled]");
+                let note = build_event(
+                    None,
+                    EventType::SystemNote,
+                    Role::System,
+                    Some(Value::String("run.cancelled".to_string())),
+                    None,
+                    None,
+                    None,
+                    None,
+                );
+                append_event(&thread_path, note)?;
+                // The in-memory messages may end mid tool call; start again from the thread
+                let budget = ContextBudget::for_model(engine.model(), &tools);
+                build_context(&vault, &thread_path, engine.as_ref(), &budget, options.history).await?
+            }
+            Err(e) => return Err(e),
+        };
     }
 
     Ok(())
@@ -182,6 +221,8 @@ async fn run_chat_daemon_async(options: ChatOptions) -> Result<()> {
     // Spawn a background task to print incoming events
     let (stop_tx, mut stop_rx) = tokio::sync::oneshot::channel::<()>();
     let event_task = tokio::spawn(async move {
+        // Whether delta text has been printed since the last final/tool line
+        let mut streamed = false;
         loop {
             tokio::select! {
                 msg = futures_util::StreamExt::next(&mut read) => {
@@ -195,14 +236,23 @@ async fn run_chat_daemon_async(options: ChatOptions) -> Result<()> {
                                             print!("{text}");
                                             use std::io::Write;
                                             let _ = std::io::stdout().flush();
+                                            streamed = true;
                                         }
                                     }
                                     "final" => {
-                                        if let Some(content) = val.get("payload").and_then(|p| p.get("content")).and_then(|c| c.as_str()) {
+                                        if streamed {
+                                            // Content already arrived as deltas
+                                            println!();
+                                            streamed = false;
+                                        } else if let Some(content) = val.get("payload").and_then(|p| p.get("content")).and_then(|c| c.as_str()) {
                                             println!("{content}");
                                         }
                                     }
                                     "tool_call_start" => {
+                                        if streamed {
+                                            println!();
+                                            streamed = false;
+                                        }
                                         if let Some(payload) = val.get("payload") {
                                             let name = payload.get("tool_name").and_then(|n| n.as_str()).unwrap_or("?");
                                             // Show a summary of key arguments (e.g. query for search, doc_path for read)
@@ -227,6 +277,47 @@ async fn run_chat_daemon_async(options: ChatOptions) -> Result<()> {
                                             eprintln!("[{name}]");
                                         }
                                     }
+                                    "retry" => {
+                                        if let Some(payload) = val.get("payload") {
+                                            let reason = payload.get("reason").and_then(|v| v.as_str()).unwrap_or("transient error");
+                                            let attempt = payload.get("attempt").and_then(|v| v.as_u64()).unwrap_or(0);
+                                            let max = payload.get("max_retries").and_then(|v| v.as_u64()).unwrap_or(0);
+                                            let delay_ms = payload.get("delay_ms").and_then(|v| v.as_u64()).unwrap_or(0);
+                                            eprintln!("[{reason}, retry {attempt}/{max} in {:.1}s]", delay_ms as f64 / 1000.0);
+                                        }
+                                    }
+                                    "engine_fallback" => {
+                                        if let Some(payload) = val.get("payload") {
+                                            let field = |name: &str| payload.get(name).and_then(|v| v.as_str()).unwrap_or("?").to_string();
+                                            eprintln!("[{} failed: {}; falling back to {} ({})]", field("from"), field("reason"), field("to"), field("model"));
+                                        }
+                                    }
+                                    "approval_required" => {
+                                        if streamed {
+                                            println!();
+                                            streamed = false;
+                                        }
+                                        if let Some(payload) = val.get("payload") {
+                                            let id = payload.get("approval_id").and_then(|v| v.as_str()).unwrap_or("?");
+                                            let name = payload.get("tool_name").and_then(|v| v.as_str()).unwrap_or("?");
+                                            let args = payload.get("arguments").cloned().unwrap_or(Value::Null);
+                                            eprintln!("[approval required: {name} {args}]");
+                                            eprintln!("  /approve {id}  or  /deny {id}");
+                                        }
+                                    }
+                                    "queue" => {
+                                        let depth = val.pointer("/payload/depth").and_then(|d| d.as_u64()).unwrap_or(0);
+                                        if depth > 0 {
+                                            eprintln!("[queued: {depth} waiting for the current reply]");
+                                        }
+                                    }
+                                    "cancelled" => {
+                                        if streamed {
+                                            println!();
+                                            streamed = false;
+                                        }
+                                        eprintln!("[cancelled]");
+                                    }
                                     "error" => {
                                         if let Some(msg) = val.get("payload").and_then(|p| p.get("message")).and_then(|m| m.as_str()) {
                                             eprintln!("Error: {msg}");
@@ -265,7 +356,19 @@ async fn run_chat_daemon_async(options: ChatOptions) -> Result<()> {
     loop {
         let line = match rl.readline("j> ") {
             Ok(line) => line,
-            Err(rustyline::error::ReadlineError::Interrupted) => continue,
+            Err(rustyline::error::ReadlineError::Interrupted) => {
+                // Stop the session's run, if one is going
+                let frame = json!({
+                    "type": "req",
+                    "id": ulid::Ulid::new().to_string(),
+                    "method": "session.cancel",
+                    "params": {"session_key": session_key},
+                });
+                write
+                    .send(Message::Text(serde_json::to_string(&frame)?.into()))
+                    .await?;
+                continue;
+            }
             Err(rustyline::error::ReadlineError::Eof) => break,
             Err(err) => return Err(err.into()),
         };
@@ -284,6 +387,10 @@ async fn run_chat_daemon_async(options: ChatOptions) -> Result<()> {
             println!("  /exit, /quit    Exit the REPL");
             println!("  /sessions       List all sessions");
             println!("  /session <key>  Switch to a different session");
+            println!("  /approve <id>   Allow a tool call waiting for approval");
+            println!("  /deny <id>      Refuse a tool call waiting for approval");
+            println!("  /interrupt <m>  Cancel the running reply and send <m>");
+            println!("  Ctrl-C          Cancel the running reply");
             continue;
         }
         if input == "/sessions" {
@@ -298,6 +405,22 @@ async fn run_chat_daemon_async(options: ChatOptions) -> Result<()> {
                 .await?;
             continue;
         }
+        let approval = input
+            .strip_prefix("/approve ")
+            .map(|id| ("session.approve", id))
+            .or_else(|| input.strip_prefix("/deny ").map(|id| ("session.deny", id)));
+        if let Some((method, approval_id)) = approval {
+            let frame = json!({
+                "type": "req",
+                "id": ulid::Ulid::new().to_string(),
+                "method": method,
+                "params": {"session_key": session_key, "approval_id": approval_id.trim()},
+            });
+            write
+                .send(Message::Text(serde_json::to_string(&frame)?.into()))
+                .await?;
+            continue;
+        }
         if let Some(new_key) = input.strip_prefix("/session ") {
             let new_key = new_key.trim();
             let frame = json!({
@@ -314,14 +437,19 @@ async fn run_chat_daemon_async(options: ChatOptions) -> Result<()> {
             continue;
         }
 
-        // Send user message
+        // Send user message; while a reply is running it queues unless /interrupt
+        let (mode, content) = match input.strip_prefix("/interrupt ") {
+            Some(rest) => ("interrupt", rest.trim()),
+            None => ("queue", input),
+        };
         let frame = json!({
             "type": "req",
             "id": ulid::Ulid::new().to_string(),
             "method": "session.send",
             "params": {
                 "session_key": session_key,
-                "content": input,
+                "content": content,
+                "mode": mode,
             },
         });
         write
@@ -382,6 +510,35 @@ fn handle_command(
 }
 
 pub fn load_system_prompt(vault: &Path) -> Result<String> {
+    assemble_system_prompt(vault, None)
+}
+
+/// System prompt with the TOC and digest cut to their share of `budget`.
+pub fn load_system_prompt_within(vault: &Path, budget: &ContextBudget) -> Result<String> {
+    assemble_system_prompt(vault, Some(budget))
+}
+
+/// System prompt plus as much thread history as fits `budget`.
+/// `history` caps the events considered; `Some(0)` starts without history.
+pub async fn build_context(
+    vault: &Path,
+    thread_path: &Path,
+    engine: &dyn Engine,
+    budget: &ContextBudget,
+    history: Option<usize>,
+) -> Result<Vec<Value>> {
+    let system_prompt = load_system_prompt_within(vault, budget)?;
+    let history_tokens = budget.history_for(&system_prompt);
+    let mut messages = vec![json!({"role": "system", "content": system_prompt})];
+    if history != Some(0) {
+        messages.extend(
+            load_history(vault, thread_path, engine, history_tokens, history, budget).await?,
+        );
+    }
+    Ok(messages)
+}
+
+fn assemble_system_prompt(vault: &Path, budget: Option<&ContextBudget>) -> Result<String> {
     let path = vault.join("prompts/j.system.md");
     let base = if path.exists() {
         fs::read_to_string(&path)
@@ -390,8 +547,12 @@ pub fn load_system_prompt(vault: &Path) -> Result<String> {
         "You are J, a memory-first assistant.".to_string()
     };
 
-    let toc = build_vault_toc(vault).unwrap_or_default();
-    let digest = build_mutation_digest(vault).unwrap_or_default();
+    let mut toc = build_vault_toc(vault).unwrap_or_default();
+    let mut digest = build_mutation_digest(vault).unwrap_or_default();
+    if let Some(budget) = budget {
+        toc = truncate_lines(&toc, budget.toc, budget);
+        digest = truncate_lines(&digest, budget.digest, budget);
+    }
 
     if toc.is_empty() && digest.is_empty() {
         Ok(base)
@@ -501,32 +662,3 @@ fn build_mutation_digest(vault: &Path) -> Result<String> {
     Ok(out)
 }
 
-fn load_history(thread_path: &Path, history: usize, messages: &mut Vec<Value>) -> Result<()> {
-    let lines = read_thread(thread_path, None, None)?;
-    let start = lines.len().saturating_sub(history);
-    for line in lines.into_iter().skip(start) {
-        if let Ok(event) = serde_json::from_str::<ThreadEvent>(&line) {
-            if let Some(value) = event.content {
-                let content = match value {
-                    Value::String(text) => text,
-                    other => other.to_string(),
-                };
-                match event.event_type {
-                    EventType::UserMessage => {
-                        let content = with_datetime(event.ts, &content);
-                        messages.push(json!({"role":"user","content": content}));
-                    }
-                    EventType::AssistantMessage => {
-                        messages.push(json!({"role":"assistant","content": content}));
-                    }
-                    EventType::InnerMonologue => {
-                        messages.push(json!({"role":"system","content": format!("[inner thoughts — not spoken aloud]\n{content}")}));
-                    }
-                    _ => {}
-                }
-            }
-        }
-    }
-    Ok(())
-}
-
@@ -0,0 +1,887 @@
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use crate::engine::{EngineKind, GenerationParams};
+use crate::tools::PermissionClass;
+use crate::usage::Price;
+
+const RUNTIME_FILE: &str = "config/j.runtime.yml";
+const MEMORY_POLICY_FILE: &str = "config/memory.policy.yml";
+const PRICES_FILE: &str = "config/prices.yml";
+
+/// Per-request timeout for MCP servers that don't set `timeout_secs`.
+const DEFAULT_MCP_TIMEOUT_SECS: u64 = 60;
+
+/// How often the daemon checks the config files for changes.
+const RELOAD_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Where a setting's effective value came from. Layers, highest first:
+/// CLI flag, environment variable, vault config file, built-in default.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Default,
+    File(PathBuf),
+    Env(&'static str),
+    Cli(&'static str),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Default => f.write_str("default"),
+            Source::File(path) => write!(f, "{}", path.display()),
+            Source::Env(var) => write!(f, "env {var}"),
+            Source::Cli(flag) => f.write_str(flag),
+        }
+    }
+}
+
+/// A resolved value and the layer it came from.
+#[derive(Debug, Clone)]
+pub struct Setting<T> {
+    pub value: T,
+    pub source: Source,
+}
+
+impl<T> Setting<T> {
+    fn new(value: T, source: Source) -> Self {
+        Self { value, source }
+    }
+}
+
+/// Flags that override everything else for the role a command runs as,
+/// e.g. `j chat --engine` for `Role::Chat`.
+#[derive(Debug, Clone, Default)]
+pub struct CliOverrides {
+    pub role: Option<Role>,
+    pub engine: Option<String>,
+    pub model: Option<String>,
+}
+
+/// A job the agent sends to an LLM. Each role can be routed to its own engine,
+/// model and sampling parameters under `roles.<name>`, so cheap jobs go to
+/// small models and reflection to large ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    /// Conversation turns (`j chat`, gateway sessions).
+    Chat,
+    /// Background reflection (the `deep_think` tool).
+    Deep,
+    /// Session titles.
+    Title,
+    /// Document ingestion.
+    Ingest,
+    /// Knowledge doc summaries and rolling conversation summaries.
+    Summarize,
+}
+
+impl Role {
+    pub const ALL: [Role; 5] = [Role::Chat, Role::Deep, Role::Title, Role::Ingest, Role::Summarize];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::Chat => "chat",
+            Role::Deep => "deep",
+            Role::Title => "title",
+            Role::Ingest => "ingest",
+            Role::Summarize => "summarize",
+        }
+    }
+}
+
+/// Engine, model and parameters for one role. Unset values fall back to the
+/// default engine and that engine's configured model.
+#[derive(Debug, Clone)]
+pub struct RoleSettings {
+    pub role: Role,
+    pub engine: Setting<Option<EngineKind>>,
+    pub model: Setting<Option<String>>,
+    pub temperature: Setting<Option<f64>>,
+    pub max_tokens: Setting<Option<usize>>,
+}
+
+impl RoleSettings {
+    /// Whether anything routes this role away from the default engine's setup.
+    pub fn is_configured(&self) -> bool {
+        [&self.engine.source, &self.model.source, &self.temperature.source, &self.max_tokens.source]
+            .iter()
+            .any(|source| **source != Source::Default)
+    }
+
+    pub fn params(&self) -> GenerationParams {
+        GenerationParams {
+            temperature: self.temperature.value,
+            max_tokens: self.max_tokens.value,
+        }
+    }
+}
+
+/// Model and endpoint for one provider. API keys stay in the environment.
+#[derive(Debug, Clone)]
+pub struct ProviderSettings {
+    pub kind: EngineKind,
+    pub model: Setting<String>,
+    pub base_url: Setting<String>,
+}
+
+/// Memory governance policy from `config/memory.policy.yml`.
+#[derive(Debug, Clone)]
+pub struct MemoryPolicy {
+    pub auto_apply_confidence: Setting<f64>,
+    pub auto_apply_risk_levels: Setting<Vec<String>>,
+    pub review_risk_levels: Setting<Vec<String>>,
+}
+
+/// An MCP server from `mcp.servers`, mounted as tools at startup.
+#[derive(Debug, Clone)]
+pub struct McpServerSettings {
+    pub name: String,
+    pub transport: McpTransport,
+    pub timeout: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub enum McpTransport {
+    /// Child process speaking JSON-RPC over stdin/stdout. It inherits J's
+    /// environment plus `env`.
+    Stdio {
+        command: String,
+        args: Vec<String>,
+        env: BTreeMap<String, String>,
+    },
+    /// Streamable HTTP endpoint; replies may come back as JSON or SSE.
+    Http {
+        url: String,
+        /// Env var holding a bearer token, so the secret stays out of the file.
+        bearer_token_env: Option<String>,
+    },
+}
+
+impl fmt::Display for McpServerSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.transport {
+            McpTransport::Stdio { command, .. } => write!(f, "{}={command}", self.name),
+            McpTransport::Http { url, .. } => write!(f, "{}={url}", self.name),
+        }
+    }
+}
+
+/// What happens when the agent calls a tool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolPolicy {
+    Allow,
+    Deny,
+    /// Pause the run until someone approves or denies the call.
+    Ask,
+}
+
+impl ToolPolicy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ToolPolicy::Allow => "allow",
+            ToolPolicy::Deny => "deny",
+            ToolPolicy::Ask => "ask",
+        }
+    }
+}
+
+/// `permissions` in the runtime file: a policy per permission class, with
+/// per-tool overrides.
+#[derive(Debug, Clone)]
+pub struct Permissions {
+    pub read: Setting<ToolPolicy>,
+    pub write: Setting<ToolPolicy>,
+    pub execute: Setting<ToolPolicy>,
+    pub tools: Setting<BTreeMap<String, ToolPolicy>>,
+}
+
+impl Permissions {
+    pub fn policy(&self, tool: &str, class: PermissionClass) -> ToolPolicy {
+        if let Some(policy) = self.tools.value.get(tool) {
+            return *policy;
+        }
+        match class {
+            PermissionClass::Read => self.read.value,
+            PermissionClass::Write => self.write.value,
+            PermissionClass::Execute => self.execute.value,
+        }
+    }
+}
+
+/// Effective runtime configuration for a vault.
+#[derive(Debug, Clone)]
+pub struct RuntimeConfig {
+    pub vault: PathBuf,
+    pub engine: Setting<EngineKind>,
+    /// Engines to try after the primary, in order; never contains the primary.
+    pub fallback: Setting<Vec<EngineKind>>,
+    /// One entry per network provider (not Replay, which reads its fixture from env).
+    pub providers: Vec<ProviderSettings>,
+    pub anthropic_max_tokens: Setting<usize>,
+    /// One entry per `Role`, in `Role::ALL` order.
+    pub roles: Vec<RoleSettings>,
+    pub gateway_port: Setting<u16>,
+    /// None: looked up from the model name.
+    pub context_window: Setting<Option<usize>>,
+    pub history_tokens: Setting<usize>,
+    /// None: whichever provider has an API key.
+    pub embedding_provider: Setting<Option<String>>,
+    pub log_level: Setting<String>,
+    pub mcp_servers: Setting<Vec<McpServerSettings>>,
+    pub permissions: Permissions,
+    pub memory: MemoryPolicy,
+    /// Per-model prices from `config/prices.yml`, on top of the built-in table.
+    pub prices: Setting<BTreeMap<String, Price>>,
+    overrides: CliOverrides,
+}
+
+impl RuntimeConfig {
+    pub fn provider(&self, kind: EngineKind) -> Option<&ProviderSettings> {
+        self.providers.iter().find(|p| p.kind == kind)
+    }
+
+    pub fn role(&self, role: Role) -> &RoleSettings {
+        self.roles
+            .iter()
+            .find(|r| r.role == role)
+            .expect("every role is resolved")
+    }
+
+    /// The engine a role runs on.
+    pub fn role_engine(&self, role: Role) -> EngineKind {
+        self.role(role).engine.value.unwrap_or(self.engine.value)
+    }
+
+    /// The model a role runs, without contacting the provider.
+    pub fn role_model(&self, role: Role) -> String {
+        let kind = self.role_engine(role);
+        match (&self.role(role).model.value, self.provider(kind)) {
+            (Some(model), _) => model.clone(),
+            (None, Some(provider)) => provider.model.value.clone(),
+            (None, None) => kind.defaults().0.to_string(),
+        }
+    }
+
+    /// Every setting as (key, value, source), keyed by its path in the config file.
+    pub fn entries(&self) -> Vec<(String, String, &Source)> {
+        fn opt<T: ToString>(value: &Option<T>) -> String {
+            value.as_ref().map(T::to_string).unwrap_or_else(|| "-".into())
+        }
+        fn kinds(kinds: &[EngineKind]) -> String {
+            if kinds.is_empty() {
+                return "-".into();
+            }
+            kinds.iter().map(|k| k.as_str()).collect::<Vec<_>>().join(",")
+        }
+        fn tool_policies(tools: &BTreeMap<String, ToolPolicy>) -> String {
+            if tools.is_empty() {
+                return "-".into();
+            }
+            tools.iter().map(|(name, policy)| format!("{name}={}", policy.as_str())).collect::<Vec<_>>().join(",")
+        }
+        fn prices(prices: &BTreeMap<String, Price>) -> String {
+            if prices.is_empty() {
+                return "-".into();
+            }
+            prices.iter().map(|(model, p)| format!("{model}={}/{}", p.input, p.output)).collect::<Vec<_>>().join(",")
+        }
+        fn servers(servers: &[McpServerSettings]) -> String {
+            if servers.is_empty() {
+                return "-".into();
+            }
+            servers.iter().map(|s| s.to_string()).collect::<Vec<_>>().join(",")
+        }
+
+        let mut out = vec![
+            ("providers.default".to_string(), self.engine.value.as_str().to_string(), &self.engine.source),
+            ("providers.fallback".to_string(), kinds(&self.fallback.value), &self.fallback.source),
+        ];
+        for provider in &self.providers {
+            let name = provider.kind.as_str();
+            out.push((format!("providers.{name}.model"), provider.model.value.clone(), &provider.model.source));
+            out.push((format!("providers.{name}.base_url"), provider.base_url.value.clone(), &provider.base_url.source));
+        }
+        out.extend([
+            ("providers.anthropic.max_tokens".to_string(), self.anthropic_max_tokens.value.to_string(), &self.anthropic_max_tokens.source),
+            ("gateway.port".to_string(), self.gateway_port.value.to_string(), &self.gateway_port.source),
+            ("context.window".to_string(), opt(&self.context_window.value), &self.context_window.source),
+            ("context.history_tokens".to_string(), self.history_tokens.value.to_string(), &self.history_tokens.source),
+            ("embeddings.provider".to_string(), opt(&self.embedding_provider.value), &self.embedding_provider.source),
+            ("logging.level".to_string(), self.log_level.value.clone(), &self.log_level.source),
+            ("mcp.servers".to_string(), servers(&self.mcp_servers.value), &self.mcp_servers.source),
+            ("permissions.read".to_string(), self.permissions.read.value.as_str().to_string(), &self.permissions.read.source),
+            ("permissions.write".to_string(), self.permissions.write.value.as_str().to_string(), &self.permissions.write.source),
+            ("permissions.execute".to_string(), self.permissions.execute.value.as_str().to_string(), &self.permissions.execute.source),
+            ("permissions.tools".to_string(), tool_policies(&self.permissions.tools.value), &self.permissions.tools.source),
+            ("memory.auto_apply.confidence_threshold".to_string(), self.memory.auto_apply_confidence.value.to_string(), &self.memory.auto_apply_confidence.source),
+            ("memory.auto_apply.risk_levels".to_string(), self.memory.auto_apply_risk_levels.value.join(","), &self.memory.auto_apply_risk_levels.source),
+            ("memory.queue_for_review.risk_levels".to_string(), self.memory.review_risk_levels.value.join(","), &self.memory.review_risk_levels.source),
+            ("prices".to_string(), prices(&self.prices.value), &self.prices.source),
+        ]);
+        for role in &self.roles {
+            let name = role.role.as_str();
+            out.extend([
+                (format!("roles.{name}.engine"), opt(&role.engine.value.map(|k| k.as_str())), &role.engine.source),
+                (format!("roles.{name}.model"), opt(&role.model.value), &role.model.source),
+                (format!("roles.{name}.temperature"), opt(&role.temperature.value), &role.temperature.source),
+                (format!("roles.{name}.max_tokens"), opt(&role.max_tokens.value), &role.max_tokens.source),
+            ]);
+        }
+        out
+    }
+
+    pub fn to_json(&self) -> Value {
+        Value::Array(
+            self.entries()
+                .into_iter()
+                .map(|(key, value, source)| json!({"key": key, "value": value, "source": source.to_string()}))
+                .collect(),
+        )
+    }
+}
+
+// ── File layer ─────────────────────────────────────────────────────────
+
+/// `config/j.runtime.yml`. Every field is optional; unknown keys are an error
+/// so a typo doesn't silently fall through to the default.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RuntimeFile {
+    providers: ProvidersFile,
+    roles: RolesFile,
+    gateway: GatewayFile,
+    context: ContextFile,
+    embeddings: EmbeddingsFile,
+    logging: LoggingFile,
+    mcp: McpFile,
+    permissions: PermissionsFile,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ProvidersFile {
+    default: Option<String>,
+    fallback: Option<Vec<String>>,
+    openai: ProviderFile,
+    anthropic: ProviderFile,
+    gemini: ProviderFile,
+    local: ProviderFile,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ProviderFile {
+    model: Option<String>,
+    base_url: Option<String>,
+    /// Anthropic only.
+    max_tokens: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RolesFile {
+    chat: RoleFile,
+    deep: RoleFile,
+    title: RoleFile,
+    ingest: RoleFile,
+    summarize: RoleFile,
+}
+
+impl RolesFile {
+    fn get(&self, role: Role) -> &RoleFile {
+        match role {
+            Role::Chat => &self.chat,
+            Role::Deep => &self.deep,
+            Role::Title => &self.title,
+            Role::Ingest => &self.ingest,
+            Role::Summarize => &self.summarize,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RoleFile {
+    engine: Option<String>,
+    model: Option<String>,
+    temperature: Option<f64>,
+    max_tokens: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct GatewayFile {
+    port: Option<u16>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ContextFile {
+    window: Option<usize>,
+    history_tokens: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct EmbeddingsFile {
+    provider: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct LoggingFile {
+    level: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct PermissionsFile {
+    read: Option<ToolPolicy>,
+    write: Option<ToolPolicy>,
+    execute: Option<ToolPolicy>,
+    tools: BTreeMap<String, ToolPolicy>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct McpFile {
+    servers: BTreeMap<String, McpServerFile>,
+}
+
+/// Either `command` (stdio) or `url` (HTTP).
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct McpServerFile {
+    command: Option<String>,
+    args: Vec<String>,
+    env: BTreeMap<String, String>,
+    url: Option<String>,
+    bearer_token_env: Option<String>,
+    timeout_secs: Option<u64>,
+}
+
+/// `config/memory.policy.yml`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct MemoryPolicyFile {
+    auto_apply: AutoApplyFile,
+    queue_for_review: ReviewFile,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct AutoApplyFile {
+    confidence_threshold: Option<f64>,
+    risk_levels: Option<Vec<String>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ReviewFile {
+    risk_levels: Option<Vec<String>>,
+}
+
+fn read_yaml<T: Default + serde::de::DeserializeOwned>(path: &Path) -> Result<T> {
+    match fs::read_to_string(path) {
+        Ok(content) if content.trim().is_empty() => Ok(T::default()),
+        Ok(content) => serde_yaml::from_str(&content).with_context(|| format!("parse {}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(T::default()),
+        Err(e) => Err(e).with_context(|| format!("read {}", path.display())),
+    }
+}
+
+// ── Resolution ─────────────────────────────────────────────────────────
+
+/// First env var in `vars` that is set, parsed. A set but invalid value is an
+/// error rather than being skipped, so a typo doesn't go unnoticed.
+fn env_layer<T>(
+    vars: &[&'static str],
+    expected: &str,
+    parse: impl Fn(&str) -> Option<T>,
+) -> Result<Option<Setting<T>>> {
+    for &var in vars {
+        if let Ok(raw) = env::var(var) {
+            let value = parse(raw.trim())
+                .ok_or_else(|| anyhow!("invalid {var}={raw:?}: expected {expected}"))?;
+            return Ok(Some(Setting::new(value, Source::Env(var))));
+        }
+    }
+    Ok(None)
+}
+
+fn parse_kind(name: &str) -> Option<EngineKind> {
+    EngineKind::from_str_opt(name)
+}
+
+fn parse_kinds(list: &str) -> Option<Vec<EngineKind>> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|n| !n.is_empty())
+        .map(parse_kind)
+        .collect()
+}
+
+fn engines_expected() -> String {
+    format!("one of {}", EngineKind::valid_names())
+}
+
+/// The vault's config files, parsed. Missing files count as empty.
+struct Files {
+    runtime_path: PathBuf,
+    runtime: RuntimeFile,
+    memory_path: PathBuf,
+    memory: MemoryPolicyFile,
+    prices_path: PathBuf,
+    prices: BTreeMap<String, Price>,
+}
+
+impl Files {
+    fn read(vault: &Path) -> Result<Self> {
+        let runtime_path = vault.join(RUNTIME_FILE);
+        let memory_path = vault.join(MEMORY_POLICY_FILE);
+        let prices_path = vault.join(PRICES_FILE);
+        Ok(Self {
+            runtime: read_yaml(&runtime_path)?,
+            memory: read_yaml(&memory_path)?,
+            prices: read_yaml(&prices_path)?,
+            runtime_path,
+            memory_path,
+            prices_path,
+        })
+    }
+
+    fn empty(vault: &Path) -> Self {
+        Self {
+            runtime_path: vault.join(RUNTIME_FILE),
+            runtime: RuntimeFile::default(),
+            memory_path: vault.join(MEMORY_POLICY_FILE),
+            memory: MemoryPolicyFile::default(),
+            prices_path: vault.join(PRICES_FILE),
+            prices: BTreeMap::new(),
+        }
+    }
+}
+
+/// Load the effective config for `vault`: `overrides`, then the environment
+/// (including `.env`), then the vault's config files, then defaults.
+pub fn load(vault: &Path, overrides: CliOverrides) -> Result<RuntimeConfig> {
+    resolve(vault, Files::read(vault)?, overrides)
+}
+
+fn resolve(vault: &Path, files: Files, overrides: CliOverrides) -> Result<RuntimeConfig> {
+    dotenvy::dotenv().ok();
+    let Files { runtime_path, runtime: file, memory_path, memory, prices_path, prices } = files;
+    let from_file = || Source::File(runtime_path.clone());
+    let from_memory_file = || Source::File(memory_path.clone());
+    let file_kind = |key: &str, name: &str| {
+        parse_kind(name).ok_or_else(|| {
+            anyhow!("invalid {key}: {name:?} in {}: expected {}", runtime_path.display(), engines_expected())
+        })
+    };
+
+    let engine = match env_layer(&["LLM_ENGINE"], &engines_expected(), parse_kind)? {
+        Some(setting) => setting,
+        None => match file.providers.default.as_deref() {
+            Some(name) => Setting::new(file_kind("providers.default", name)?, from_file()),
+            None => Setting::new(EngineKind::OpenAI, Source::Default),
+        },
+    };
+
+    let mut fallback = match env_layer(&["LLM_FALLBACK"], &format!("a comma-separated list of {}", EngineKind::valid_names()), parse_kinds)? {
+        Some(setting) => setting,
+        None => match &file.providers.fallback {
+            Some(names) => Setting::new(
+                names
+                    .iter()
+                    .map(|name| file_kind("providers.fallback", name))
+                    .collect::<Result<_>>()?,
+                from_file(),
+            ),
+            None => Setting::new(Vec::new(), Source::Default),
+        },
+    };
+    let mut seen = vec![engine.value];
+    fallback.value.retain(|kind| {
+        let keep = !seen.contains(kind);
+        seen.push(*kind);
+        keep
+    });
+
+    let mut providers = Vec::new();
+    for (kind, section) in [
+        (EngineKind::OpenAI, &file.providers.openai),
+        (EngineKind::Anthropic, &file.providers.anthropic),
+        (EngineKind::Gemini, &file.providers.gemini),
+        (EngineKind::Local, &file.providers.local),
+    ] {
+        let (default_model, default_url) = kind.defaults();
+        let (model_var, url_var) = provider_vars(kind);
+        let model = env_layer(&["LLM_MODEL", model_var], "a model name", |v| Some(v.to_string()))?
+            .or_else(|| section.model.clone().map(|m| Setting::new(m, from_file())))
+            .unwrap_or_else(|| Setting::new(default_model.to_string(), Source::Default));
+        let base_url = env_layer(&["LLM_BASE_URL", url_var], "a URL", |v| Some(v.to_string()))?
+            .or_else(|| section.base_url.clone().map(|u| Setting::new(u, from_file())))
+            .unwrap_or_else(|| Setting::new(default_url.to_string(), Source::Default));
+        providers.push(ProviderSettings { kind, model, base_url });
+    }
+
+    let anthropic_max_tokens = env_layer(&["ANTHROPIC_MAX_TOKENS"], "a number", |v| v.parse().ok())?
+        .or_else(|| file.providers.anthropic.max_tokens.map(|n| Setting::new(n, from_file())))
+        .unwrap_or_else(|| Setting::new(8192, Source::Default));
+
+    let mut roles = Vec::new();
+    for role in Role::ALL {
+        let section = file.roles.get(role);
+        let name = role.as_str();
+        // Deep think kept its own env vars from before roles existed
+        let (engine_vars, model_vars): (&[&'static str], &[&'static str]) = match role {
+            Role::Deep => (&["DEEP_THINK_ENGINE"], &["OPENAI_DEEP_THINK_MODEL"]),
+            _ => (&[], &[]),
+        };
+        let cli = overrides.role == Some(role);
+        let engine = match overrides.engine.as_deref().filter(|_| cli) {
+            Some(name) => Setting::new(
+                Some(parse_kind(name).ok_or_else(|| anyhow!("invalid --engine {name:?}: expected {}", engines_expected()))?),
+                Source::Cli("--engine"),
+            ),
+            None => match env_layer(engine_vars, &engines_expected(), parse_kind)? {
+                Some(setting) => Setting::new(Some(setting.value), setting.source),
+                None => match section.engine.as_deref() {
+                    Some(kind) => Setting::new(Some(file_kind(&format!("roles.{name}.engine"), kind)?), from_file()),
+                    None => Setting::new(None, Source::Default),
+                },
+            },
+        };
+        let model = match overrides.model.clone().filter(|_| cli) {
+            Some(model) => Setting::new(Some(model), Source::Cli("--model")),
+            // A model configured for another engine doesn't carry over
+            None if cli && overrides.engine.is_some() => Setting::new(None, Source::Cli("--engine")),
+            None => env_layer(model_vars, "a model name", |v| Some(Some(v.to_string())))?
+                .or_else(|| section.model.clone().map(|m| Setting::new(Some(m), from_file())))
+                .unwrap_or_else(|| Setting::new(None, Source::Default)),
+        };
+        let temperature = section
+            .temperature
+            .map(|t| Setting::new(Some(t), from_file()))
+            .unwrap_or_else(|| Setting::new(None, Source::Default));
+        let max_tokens = section
+            .max_tokens
+            .map(|n| Setting::new(Some(n), from_file()))
+            .unwrap_or_else(|| Setting::new(None, Source::Default));
+        roles.push(RoleSettings { role, engine, model, temperature, max_tokens });
+    }
+
+    let gateway_port = env_layer(&["J_GATEWAY_PORT"], "a port number", |v| v.parse().ok())?
+        .or_else(|| file.gateway.port.map(|p| Setting::new(p, from_file())))
+        .unwrap_or_else(|| Setting::new(crate::gateway::DEFAULT_PORT, Source::Default));
+
+    let context_window = env_layer(&["LLM_CONTEXT_WINDOW"], "a number of tokens", |v| v.parse().ok().map(Some))?
+        .or_else(|| file.context.window.map(|n| Setting::new(Some(n), from_file())))
+        .unwrap_or_else(|| Setting::new(None, Source::Default));
+    let history_tokens = env_layer(&["LLM_HISTORY_TOKENS"], "a number of tokens", |v| v.parse().ok())?
+        .or_else(|| file.context.history_tokens.map(|n| Setting::new(n, from_file())))
+        .unwrap_or_else(|| Setting::new(crate::context::DEFAULT_HISTORY_TOKENS, Source::Default));
+
+    let embedding_provider = env_layer(&["EMBEDDING_PROVIDER"], "a provider name", |v| Some(Some(v.to_string())))?
+        .or_else(|| file.embeddings.provider.clone().map(|p| Setting::new(Some(p), from_file())))
+        .unwrap_or_else(|| Setting::new(None, Source::Default));
+
+    let log_level = env_layer(&["RUST_LOG"], "a log filter", |v| Some(v.to_string()))?
+        .or_else(|| file.logging.level.clone().map(|l| Setting::new(l, from_file())))
+        .unwrap_or_else(|| Setting::new("info".to_string(), Source::Default));
+
+    let mut servers = Vec::new();
+    for (name, section) in &file.mcp.servers {
+        let transport = match (&section.command, &section.url) {
+            (Some(command), None) => McpTransport::Stdio {
+                command: command.clone(),
+                args: section.args.clone(),
+                env: section.env.clone(),
+            },
+            (None, Some(url)) => McpTransport::Http {
+                url: url.clone(),
+                bearer_token_env: section.bearer_token_env.clone(),
+            },
+            _ => return Err(anyhow!(
+                "invalid mcp.servers.{name} in {}: set exactly one of command or url",
+                runtime_path.display()
+            )),
+        };
+        servers.push(McpServerSettings {
+            name: name.clone(),
+            transport,
+            timeout: Duration::from_secs(section.timeout_secs.unwrap_or(DEFAULT_MCP_TIMEOUT_SECS)),
+        });
+    }
+    let mcp_servers = if file.mcp.servers.is_empty() {
+        Setting::new(servers, Source::Default)
+    } else {
+        Setting::new(servers, from_file())
+    };
+
+    // Tools ran unchecked before policies existed, so everything defaults to allow
+    let class_policy = |policy: Option<ToolPolicy>| {
+        policy
+            .map(|p| Setting::new(p, from_file()))
+            .unwrap_or_else(|| Setting::new(ToolPolicy::Allow, Source::Default))
+    };
+    let permissions = Permissions {
+        read: class_policy(file.permissions.read),
+        write: class_policy(file.permissions.write),
+        execute: class_policy(file.permissions.execute),
+        tools: if file.permissions.tools.is_empty() {
+            Setting::new(BTreeMap::new(), Source::Default)
+        } else {
+            Setting::new(file.permissions.tools.clone(), from_file())
+        },
+    };
+
+    let memory = MemoryPolicy {
+        auto_apply_confidence: memory
+            .auto_apply
+            .confidence_threshold
+            .map(|n| Setting::new(n, from_memory_file()))
+            .unwrap_or_else(|| Setting::new(0.8, Source::Default)),
+        auto_apply_risk_levels: memory
+            .auto_apply
+            .risk_levels
+            .map(|l| Setting::new(l, from_memory_file()))
+            .unwrap_or_else(|| Setting::new(vec!["low".into()], Source::Default)),
+        review_risk_levels: memory
+            .queue_for_review
+            .risk_levels
+            .map(|l| Setting::new(l, from_memory_file()))
+            .unwrap_or_else(|| Setting::new(vec!["medium".into(), "high".into()], Source::Default)),
+    };
+
+    Ok(RuntimeConfig {
+        vault: vault.to_path_buf(),
+        engine,
+        fallback,
+        providers,
+        anthropic_max_tokens,
+        roles,
+        gateway_port,
+        context_window,
+        history_tokens,
+        embedding_provider,
+        log_level,
+        mcp_servers,
+        permissions,
+        memory,
+        prices: if prices.is_empty() {
+            Setting::new(prices, Source::Default)
+        } else {
+            Setting::new(prices, Source::File(prices_path))
+        },
+        overrides,
+    })
+}
+
+/// Provider-specific env vars for model and base URL, e.g. OPENAI_MODEL.
+fn provider_vars(kind: EngineKind) -> (&'static str, &'static str) {
+    match kind {
+        EngineKind::OpenAI => ("OPENAI_MODEL", "OPENAI_BASE_URL"),
+        EngineKind::Anthropic => ("ANTHROPIC_MODEL", "ANTHROPIC_BASE_URL"),
+        EngineKind::Gemini => ("GEMINI_MODEL", "GEMINI_BASE_URL"),
+        EngineKind::Local => ("LOCAL_MODEL", "LOCAL_BASE_URL"),
+        EngineKind::Replay => ("REPLAY_MODEL", "REPLAY_FIXTURE"),
+    }
+}
+
+// ── Process-wide config ────────────────────────────────────────────────
+
+static CURRENT: RwLock<Option<Arc<RuntimeConfig>>> = RwLock::new(None);
+
+/// Load the config for `vault` and make it current for this process.
+/// Commands that take `--vault` call this before building engines.
+pub fn init(vault: &Path, overrides: CliOverrides) -> Result<Arc<RuntimeConfig>> {
+    let config = Arc::new(load(vault, overrides)?);
+    *CURRENT.write().unwrap() = Some(config.clone());
+    Ok(config)
+}
+
+/// The current config. If nothing called `init`, loads it for the vault in
+/// J_VAULT (or the default vault). Unreadable config files are skipped with a
+/// warning; an invalid environment variable is fatal, as it would be in `init`.
+pub fn current() -> Arc<RuntimeConfig> {
+    if let Some(config) = CURRENT.read().unwrap().as_ref() {
+        return config.clone();
+    }
+    let vault = crate::vault::resolve_vault(env::var("J_VAULT").ok().map(PathBuf::from));
+    let files = Files::read(&vault).unwrap_or_else(|e| {
+        eprintln!("Warning: {e:#}; ignoring config files");
+        Files::empty(&vault)
+    });
+    match resolve(&vault, files, CliOverrides::default()) {
+        Ok(config) => {
+            let config = Arc::new(config);
+            *CURRENT.write().unwrap() = Some(config.clone());
+            config
+        }
+        Err(e) => {
+            eprintln!("Error: {e:#}");
+            std::process::exit(1)
+        }
+    }
+}
+
+/// Reload from the same vault and overrides. On error the current config
+/// stays in place. Returns the keys whose value changed as (key, old, new).
+pub fn reload() -> Result<Vec<(String, String, String)>> {
+    let previous = current();
+    let next = load(&previous.vault, previous.overrides.clone())?;
+    let before: Vec<(String, String, &Source)> = previous.entries();
+    let changes = next
+        .entries()
+        .into_iter()
+        .zip(before)
+        .filter(|((_, new, _), (_, old, _))| new != old)
+        .map(|((key, new, _), (_, old, _))| (key, old, new))
+        .collect();
+    *CURRENT.write().unwrap() = Some(Arc::new(next));
+    Ok(changes)
+}
+
+fn modified(vault: &Path) -> [Option<SystemTime>; 3] {
+    [RUNTIME_FILE, MEMORY_POLICY_FILE, PRICES_FILE]
+        .map(|file| fs::metadata(vault.join(file)).and_then(|m| m.modified()).ok())
+}
+
+/// Poll the config files and reload when any of them changes. Runs until the task
+/// is dropped. The port, log level and MCP servers are only read at startup,
+/// so changes to those are reported but need a restart.
+pub async fn watch() {
+    let vault = current().vault.clone();
+    let mut last = modified(&vault);
+    loop {
+        tokio::time::sleep(RELOAD_INTERVAL).await;
+        let now = modified(&vault);
+        if now == last {
+            continue;
+        }
+        last = now;
+        match reload() {
+            Ok(changes) if changes.is_empty() => {}
+            Ok(changes) => {
+                for (key, old, new) in changes {
+                    if matches!(key.as_str(), "gateway.port" | "logging.level" | "mcp.servers") {
+                        tracing::warn!("config {key} changed ({old} -> {new}); restart the daemon to apply");
+                    } else {
+                        tracing::info!("config {key}: {old} -> {new}");
+                    }
+                }
+            }
+            Err(e) => tracing::warn!("config reload failed, keeping previous config: {e:#}"),
+        }
+    }
+}
@@ -0,0 +1,468 @@
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::path::Path;
+
+use crate::engine::Engine;
+use crate::thread_store::{append_event, build_event, read_thread, EventType, Role, ThreadEvent};
+
+/// Context windows by model prefix (longest match wins), in tokens.
+const CONTEXT_WINDOWS: &[(&str, usize)] = &[
+    ("gpt-5", 400_000),
+    ("gpt-4.1", 1_047_576),
+    ("gpt-4o", 128_000),
+    ("o4-mini", 200_000),
+    ("o3", 200_000),
+    ("claude", 200_000),
+    ("gemini-2.5", 1_048_576),
+    ("gemini-2.0", 1_048_576),
+];
+
+/// Window assumed for unknown models, e.g. whatever a local server runs.
+/// Ollama's default context is small, so this errs low.
+const DEFAULT_WINDOW: usize = 8_192;
+
+/// Default ceiling on history tokens, so a large window doesn't mean every
+/// turn resends the whole session. Override with LLM_HISTORY_TOKENS or
+/// `context.history_tokens`.
+pub const DEFAULT_HISTORY_TOKENS: usize = 24_000;
+
+/// Rough per-image cost; providers charge by resolution, this is a typical size.
+const IMAGE_TOKENS: usize = 1_000;
+
+/// How the prompt budget is split for one model. Token counts are estimates
+/// from character length, which is close enough to keep well inside the window
+/// without shipping a tokenizer per provider.
+#[derive(Debug, Clone)]
+pub struct ContextBudget {
+    pub window: usize,
+    /// Held back for the model's reply.
+    pub output_reserve: usize,
+    /// Tool schemas are sent on every call.
+    pub tools: usize,
+    /// Caps for the vault table of contents and the recent-changes digest.
+    pub toc: usize,
+    pub digest: usize,
+    /// Cap on conversation history, before subtracting the system prompt.
+    pub history: usize,
+    chars_per_token: f64,
+}
+
+impl ContextBudget {
+    /// Budget for `model`. The configured context window (LLM_CONTEXT_WINDOW,
+    /// `context.window`) overrides the lookup, which is useful for local
+    /// servers started with a larger num_ctx.
+    pub fn for_model(model: &str, tools: &[Value]) -> Self {
+        let config = crate::config::current();
+        let window = config
+            .context_window
+            .value
+            .unwrap_or_else(|| context_window(model));
+        // Claude's tokenizer splits English a little finer than OpenAI's and Gemini's
+        let chars_per_token = if model.starts_with("claude") { 3.5 } else { 4.0 };
+        let history_cap = config.history_tokens.value;
+        let mut budget = Self {
+            window,
+            output_reserve: (window / 8).min(16_000),
+            tools: 0,
+            toc: 0,
+            digest: 0,
+            history: 0,
+            chars_per_token,
+        };
+        budget.tools = budget.tokens(&Value::Array(tools.to_vec()).to_string());
+        let input = budget.input();
+        budget.toc = input / 5;
+        budget.digest = input / 20;
+        budget.history = history_cap.min(input);
+        budget
+    }
+
+    /// Tokens available for the prompt: system prompt plus history.
+    pub fn input(&self) -> usize {
+        self.window.saturating_sub(self.output_reserve + self.tools)
+    }
+
+    pub fn tokens(&self, text: &str) -> usize {
+        (text.chars().count() as f64 / self.chars_per_token).ceil() as usize
+    }
+
+    /// Estimated tokens for one chat message, including content parts.
+    pub fn message_tokens(&self, message: &Value) -> usize {
+        // Role and message framing
+        let overhead = 4;
+        let content = match message.get("content") {
+            Some(Value::String(text)) => self.tokens(text),
+            Some(Value::Array(parts)) => parts
+                .iter()
+                .map(|part| match part.get("type").and_then(|v| v.as_str()) {
+                    Some("image_url") => IMAGE_TOKENS,
+                    _ => self.tokens(part.get("text").and_then(|v| v.as_str()).unwrap_or("")),
+                })
+                .sum(),
+            _ => 0,
+        };
+        let tool_calls = message
+            .get("tool_calls")
+            .map(|calls| self.tokens(&calls.to_string()))
+            .unwrap_or(0);
+        overhead + content + tool_calls
+    }
+
+    pub fn messages_tokens(&self, messages: &[Value]) -> usize {
+        messages.iter().map(|m| self.message_tokens(m)).sum()
+    }
+
+    /// History tokens left once the system prompt is in place.
+    pub fn history_for(&self, system_prompt: &str) -> usize {
+        self.history
+            .min(self.input().saturating_sub(self.tokens(system_prompt)))
+    }
+}
+
+/// Context window for a model, matched by longest prefix.
+pub fn context_window(model: &str) -> usize {
+    CONTEXT_WINDOWS
+        .iter()
+        .filter(|(prefix, _)| model.starts_with(prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|&(_, window)| window)
+        .unwrap_or(DEFAULT_WINDOW)
+}
+
+/// Cut `text` to whole lines within `tokens`, noting how much was left out.
+pub fn truncate_lines(text: &str, tokens: usize, budget: &ContextBudget) -> String {
+    if budget.tokens(text) <= tokens {
+        return text.to_string();
+    }
+    let lines: Vec<&str> = text.lines().collect();
+    let mut out = String::new();
+    let mut used = 0;
+    for (idx, line) in lines.iter().enumerate() {
+        let cost = budget.tokens(line) + 1;
+        if used + cost > tokens {
+            out.push_str(&format!("- … {} more lines omitted to fit the context window\n", lines.len() - idx));
+            break;
+        }
+        used += cost;
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
+
+/// Content of a `context_summary` event: a rolling summary of the thread up to
+/// and including `through` (an event id). Later loads start from the newest one.
+#[derive(Debug, Deserialize)]
+struct SummaryContent {
+    summary: String,
+    through: String,
+}
+
+/// Thread history as chat messages, fitted to `history_tokens`.
+///
+/// Starts from the newest `context_summary` event, if any. When the events
+/// after it still don't fit, the oldest ones are folded into a new summary
+/// (built on the previous one) which is appended to the thread, so the next
+/// load starts from there instead of silently losing those turns.
+/// `max_events` caps how many recent events are considered at all.
+pub async fn load_history(
+    vault: &Path,
+    thread_path: &Path,
+    client: &dyn Engine,
+    history_tokens: usize,
+    max_events: Option<usize>,
+    budget: &ContextBudget,
+) -> Result<Vec<Value>> {
+    let lines = read_thread(thread_path, None, None)?;
+    let mut events: Vec<ThreadEvent> = lines
+        .iter()
+        .filter_map(|line| serde_json::from_str::<ThreadEvent>(line).ok())
+        .collect();
+    if let Some(max) = max_events {
+        events.drain(..events.len().saturating_sub(max));
+    }
+
+    // Resume from the newest summary that is still within range
+    let mut summary: Option<String> = None;
+    if let Some(pos) = events
+        .iter()
+        .rposition(|e| matches!(e.event_type, EventType::ContextSummary))
+    {
+        let content: Option<SummaryContent> = events[pos]
+            .content
+            .clone()
+            .and_then(|c| serde_json::from_value(c).ok());
+        if let Some(content) = content {
+            summary = Some(content.summary);
+            let through = events.iter().position(|e| e.event_id == content.through);
+            events.drain(..=through.unwrap_or(pos));
+        }
+    }
+
+    // (event id, message) for every event that contributes to context
+    let entries: Vec<(String, Value)> = events
+        .iter()
+        .filter_map(|event| Some((event.event_id.clone(), event_message(vault, event)?)))
+        .collect();
+
+    let summary_tokens = summary
+        .as_deref()
+        .map(|s| budget.tokens(s) + 16)
+        .unwrap_or(0);
+    let available = history_tokens.saturating_sub(summary_tokens);
+
+    // Keep the newest messages that fit; always keep the latest one
+    let mut used = 0;
+    let mut split = entries.len();
+    while split > 0 {
+        let cost = budget.message_tokens(&entries[split - 1].1);
+        if used + cost > available && split < entries.len() {
+            break;
+        }
+        used += cost;
+        split -= 1;
+    }
+    // Start the kept part on a user turn rather than mid-exchange
+    while split > 0
+        && split + 1 < entries.len()
+        && entries[split].1.get("role").and_then(|r| r.as_str()) != Some("user")
+    {
+        split += 1;
+    }
+
+    if split > 0 {
+        let folded: Vec<Value> = entries[..split].iter().map(|(_, m)| m.clone()).collect();
+        let through = entries[split - 1].0.clone();
+        // Leave room for the summary itself within the history share
+        let max_words = (history_tokens / 8).clamp(100, 600);
+        // The "summarize" role, when configured, else the conversation's own engine
+        let role_engine = if crate::config::current().role(crate::config::Role::Summarize).is_configured() {
+            match crate::engine::create_role_engine(crate::config::Role::Summarize).await {
+                Ok(engine) => Some(engine),
+                Err(e) => {
+                    eprintln!("Warning: summarize role unavailable, using the chat engine: {e:#}");
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        let summariser = role_engine.as_deref().unwrap_or(client);
+        match summarise(summariser, summary.as_deref(), &folded, max_words).await {
+            Ok((text, usage)) => {
+                let mut event = build_event(
+                    None,
+                    EventType::ContextSummary,
+                    Role::System,
+                    Some(json!({ "summary": text, "through": through })),
+                    None,
+                    None,
+                    None,
+                    Some("context_window".to_string()),
+                );
+                crate::agent::attach_usage(&mut event, usage, summariser.model());
+                append_event(thread_path, event)?;
+                summary = Some(text);
+            }
+            Err(e) => {
+                eprintln!("Warning: could not summarise earlier conversation: {e:#}");
+                let note = format!("[{} earlier messages omitted to fit the context window]", folded.len());
+                summary = Some(match summary {
+                    Some(previous) => format!("{previous}\n\n{note}"),
+                    None => note,
+                });
+            }
+        }
+    }
+
+    let mut messages = Vec::new();
+    if let Some(summary) = summary {
+        messages.push(json!({
+            "role": "system",
+            "content": format!("[summary of the earlier conversation]\n{summary}"),
+        }));
+    }
+    messages.extend(entries.into_iter().skip(split).map(|(_, m)| m));
+    Ok(messages)
+}
+
+/// The chat message an event contributes to context, if any.
+fn event_message(vault: &Path, event: &ThreadEvent) -> Option<Value> {
+    let value = event.content.as_ref()?;
+    if matches!(event.event_type, EventType::AttachmentAdded) {
+        return Some(crate::media::attachment_message(vault, value));
+    }
+    let content = match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    };
+    match event.event_type {
+        EventType::UserMessage => Some(json!({
+            "role": "user",
+            "content": crate::agent::with_datetime(event.ts, &content),
+        })),
+        EventType::AssistantMessage => Some(json!({"role": "assistant", "content": content})),
+        EventType::InnerMonologue => Some(json!({
+            "role": "system",
+            "content": format!("[inner thoughts — not spoken aloud]\n{content}"),
+        })),
+        _ => None,
+    }
+}
+
+async fn summarise(
+    client: &dyn Engine,
+    previous: Option<&str>,
+    messages: &[Value],
+    max_words: usize,
+) -> Result<(String, Option<crate::usage::Usage>)> {
+    #[derive(Deserialize)]
+    struct Summary {
+        summary: String,
+    }
+
+    let mut transcript = String::new();
+    if let Some(previous) = previous {
+        transcript.push_str(&format!("Summary so far:\n{previous}\n\nContinued conversation:\n"));
+    }
+    for message in messages {
+        let role = message.get("role").and_then(|r| r.as_str()).unwrap_or("?");
+        let text = match message.get("content") {
+            Some(Value::String(text)) => text.clone(),
+            Some(Value::Array(parts)) => parts
+                .iter()
+                .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+                .collect::<Vec<_>>()
+                .join(" "),
+            _ => continue,
+        };
+        transcript.push_str(&format!("{role}: {text}\n"));
+    }
+    let prompt = vec![
+        json!({"role": "system", "content": format!(
+            "Summarise this conversation so it can stand in for the full transcript. Keep facts, decisions, open questions and anything the user asked to remember. At most {max_words} words."
+        )}),
+        json!({"role": "user", "content": transcript}),
+    ];
+    let schema = crate::schema::JsonSchema::new(
+        "conversation_summary",
+        json!({
+            "type": "object",
+            "properties": { "summary": { "type": "string", "minLength": 1 } },
+            "required": ["summary"],
+            "additionalProperties": false,
+        }),
+    );
+    let result = client.chat_structured(&prompt, &schema).await?;
+    Ok((result.parse::<Summary>()?.summary, result.usage))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::ReplayEngine;
+    use crate::thread_store::create_thread;
+    use std::path::PathBuf;
+
+    /// A fresh vault with one thread holding `turns` user/assistant pairs.
+    fn thread_with_turns(name: &str, turns: usize, words: usize) -> (PathBuf, PathBuf) {
+        let vault = std::env::temp_dir().join(format!("j-context-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&vault);
+        let thread = create_thread(&vault, None, None, None).unwrap();
+        for turn in 0..turns {
+            for (kind, role) in [(EventType::UserMessage, Role::User), (EventType::AssistantMessage, Role::Assistant)] {
+                let text = format!("turn {turn} {}", "word ".repeat(words));
+                let event = build_event(None, kind, role, Some(Value::String(text)), None, None, None, None);
+                append_event(&thread, event).unwrap();
+            }
+        }
+        (vault, thread)
+    }
+
+    /// A replay engine serving `responses` as assistant contents.
+    fn replay(vault: &Path, responses: &[&str]) -> ReplayEngine {
+        let fixture = vault.join("fixture.jsonl");
+        let lines: Vec<String> = responses
+            .iter()
+            .map(|content| json!({"response": {"content": content}}).to_string())
+            .collect();
+        std::fs::write(&fixture, lines.join("\n")).unwrap();
+        ReplayEngine::load(&fixture, "replay".into()).unwrap()
+    }
+
+    fn events_of(thread: &Path, kind: &str) -> Vec<Value> {
+        read_thread(thread, None, None)
+            .unwrap()
+            .iter()
+            .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+            .filter(|event| event["type"] == kind)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn history_that_fits_is_returned_whole() {
+        let (vault, thread) = thread_with_turns("fits", 3, 5);
+        let budget = ContextBudget::for_model("replay", &[]);
+        // No responses: a summarise call would fail the test via the omission note
+        let engine = replay(&vault, &[]);
+        let messages = load_history(&vault, &thread, &engine, 10_000, None, &budget).await.unwrap();
+        assert_eq!(messages.len(), 6);
+        assert_eq!(messages[0]["role"], "user");
+        assert!(events_of(&thread, "context_summary").is_empty());
+        std::fs::remove_dir_all(&vault).unwrap();
+    }
+
+    #[tokio::test]
+    async fn overflow_folds_oldest_turns_into_a_summary() {
+        let (vault, thread) = thread_with_turns("folds", 6, 40);
+        let budget = ContextBudget::for_model("replay", &[]);
+        let engine = replay(&vault, &[r#"{"summary":"Six turns about words."}"#]);
+        let messages = load_history(&vault, &thread, &engine, 150, None, &budget).await.unwrap();
+
+        assert_eq!(messages[0]["role"], "system");
+        assert!(messages[0]["content"].as_str().unwrap().contains("Six turns about words."));
+        // Kept part starts on a user turn and ends with the newest message
+        assert_eq!(messages[1]["role"], "user");
+        assert!(messages.last().unwrap()["content"].as_str().unwrap().starts_with("turn 5"));
+        assert!(messages.len() < 13);
+
+        let summaries = events_of(&thread, "context_summary");
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0]["content"]["summary"], "Six turns about words.");
+        std::fs::remove_dir_all(&vault).unwrap();
+    }
+
+    #[tokio::test]
+    async fn later_loads_resume_from_the_stored_summary() {
+        let (vault, thread) = thread_with_turns("resume", 6, 40);
+        let budget = ContextBudget::for_model("replay", &[]);
+        let engine = replay(&vault, &[r#"{"summary":"Earlier: words."}"#]);
+        let first = load_history(&vault, &thread, &engine, 150, None, &budget).await.unwrap();
+
+        // The fixture is spent, so a second fold would fall back to the omission note
+        let second = load_history(&vault, &thread, &engine, 150, None, &budget).await.unwrap();
+        assert_eq!(first, second);
+        assert_eq!(events_of(&thread, "context_summary").len(), 1);
+        std::fs::remove_dir_all(&vault).unwrap();
+    }
+
+    #[tokio::test]
+    async fn failed_summary_notes_the_omitted_messages() {
+        let (vault, thread) = thread_with_turns("fails", 6, 40);
+        let budget = ContextBudget::for_model("replay", &[]);
+        let engine = replay(&vault, &[]);
+        let messages = load_history(&vault, &thread, &engine, 150, None, &budget).await.unwrap();
+        let note = messages[0]["content"].as_str().unwrap();
+        assert!(note.contains("earlier messages omitted"), "{note}");
+        assert!(events_of(&thread, "context_summary").is_empty());
+        std::fs::remove_dir_all(&vault).unwrap();
+    }
+
+    #[test]
+    fn window_lookup_uses_longest_prefix() {
+        assert_eq!(context_window("gpt-4o-mini"), 128_000);
+        assert_eq!(context_window("claude-sonnet-4-20250514"), 200_000);
+        assert_eq!(context_window("llama3.2"), DEFAULT_WINDOW);
+    }
+}
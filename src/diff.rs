@@ -0,0 +1,181 @@
+//! Line-based unified diffs between stored doc versions.
+
+/// Lines compared by the LCS table before falling back to replacing the
+/// whole differing region; docs are far smaller than this in practice.
+const MAX_TABLE: usize = 4_000_000;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Op {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Unified diff of `old` against `new` with `context` unchanged lines around
+/// each change. Empty when the two are equal.
+pub fn unified_diff(old: &str, new: &str, old_label: &str, new_label: &str, context: usize) -> String {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+    let ops = diff_lines(&a, &b);
+    if ops.iter().all(|(op, _)| *op == Op::Equal) {
+        return String::new();
+    }
+
+    // Merge the context windows of nearby changes into hunks over `ops`
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for (i, (op, _)) in ops.iter().enumerate() {
+        if *op == Op::Equal {
+            continue;
+        }
+        let lo = i.saturating_sub(context);
+        let hi = (i + context + 1).min(ops.len());
+        match ranges.last_mut() {
+            Some(last) if lo <= last.1 => last.1 = hi,
+            _ => ranges.push((lo, hi)),
+        }
+    }
+
+    // Line numbers in each file before every op
+    let mut positions = Vec::with_capacity(ops.len());
+    let (mut old_line, mut new_line) = (0, 0);
+    for (op, _) in &ops {
+        positions.push((old_line, new_line));
+        match op {
+            Op::Equal => {
+                old_line += 1;
+                new_line += 1;
+            }
+            Op::Delete => old_line += 1,
+            Op::Insert => new_line += 1,
+        }
+    }
+
+    let mut out = format!("--- {old_label}\n+++ {new_label}\n");
+    for (lo, hi) in ranges {
+        let hunk = &ops[lo..hi];
+        let old_len = hunk.iter().filter(|(op, _)| *op != Op::Insert).count();
+        let new_len = hunk.iter().filter(|(op, _)| *op != Op::Delete).count();
+        let (old_start, new_start) = positions[lo];
+        // An empty side is numbered by the line it follows, as in GNU diff
+        let start = |line: usize, len: usize| if len == 0 { line } else { line + 1 };
+        out.push_str(&format!(
+            "@@ -{},{old_len} +{},{new_len} @@\n",
+            start(old_start, old_len),
+            start(new_start, new_len)
+        ));
+        for (op, line) in hunk {
+            let sign = match op {
+                Op::Equal => ' ',
+                Op::Delete => '-',
+                Op::Insert => '+',
+            };
+            out.push(sign);
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+fn diff_lines<'a>(a: &[&'a str], b: &[&'a str]) -> Vec<(Op, &'a str)> {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let a_mid = &a[prefix..a.len() - suffix];
+    let b_mid = &b[prefix..b.len() - suffix];
+
+    let mut ops: Vec<(Op, &str)> = a[..prefix].iter().map(|line| (Op::Equal, *line)).collect();
+    if a_mid.len().saturating_mul(b_mid.len()) > MAX_TABLE {
+        ops.extend(a_mid.iter().map(|line| (Op::Delete, *line)));
+        ops.extend(b_mid.iter().map(|line| (Op::Insert, *line)));
+    } else {
+        ops.extend(lcs_ops(a_mid, b_mid));
+    }
+    ops.extend(a[a.len() - suffix..].iter().map(|line| (Op::Equal, *line)));
+    ops
+}
+
+fn lcs_ops<'a>(a: &[&'a str], b: &[&'a str]) -> Vec<(Op, &'a str)> {
+    let (n, m) = (a.len(), b.len());
+    // table[i][j] = LCS length of a[i..] and b[j..]
+    let mut table = vec![0u32; (n + 1) * (m + 1)];
+    let idx = |i: usize, j: usize| i * (m + 1) + j;
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            table[idx(i, j)] = if a[i] == b[j] {
+                table[idx(i + 1, j + 1)] + 1
+            } else {
+                table[idx(i + 1, j)].max(table[idx(i, j + 1)])
+            };
+        }
+    }
+
+    let mut ops = Vec::with_capacity(n + m);
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if a[i] == b[j] {
+            ops.push((Op::Equal, a[i]));
+            i += 1;
+            j += 1;
+        } else if table[idx(i + 1, j)] >= table[idx(i, j + 1)] {
+            ops.push((Op::Delete, a[i]));
+            i += 1;
+        } else {
+            ops.push((Op::Insert, b[j]));
+            j += 1;
+        }
+    }
+    ops.extend(a[i..].iter().map(|line| (Op::Delete, *line)));
+    ops.extend(b[j..].iter().map(|line| (Op::Insert, *line)));
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equal_texts_have_no_diff() {
+        assert_eq!(unified_diff("a\nb\n", "a\nb\n", "old", "new", 3), "");
+    }
+
+    #[test]
+    fn change_is_shown_with_context() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n";
+        let new = "1\n2\n3\nfour\n5\n6\n7\n";
+        assert_eq!(
+            unified_diff(old, new, "a.md@old", "a.md@new", 1),
+            "--- a.md@old\n+++ a.md@new\n@@ -3,3 +3,3 @@\n 3\n-4\n+four\n 5\n"
+        );
+    }
+
+    #[test]
+    fn distant_changes_get_separate_hunks() {
+        let old = "a\nb\nc\nd\ne\nf\ng\n";
+        let new = "A\nb\nc\nd\ne\nf\nG\n";
+        assert_eq!(
+            unified_diff(old, new, "old", "new", 1),
+            "--- old\n+++ new\n@@ -1,2 +1,2 @@\n-a\n+A\n b\n@@ -6,2 +6,2 @@\n f\n-g\n+G\n"
+        );
+        // With more context the windows overlap and merge
+        assert_eq!(unified_diff(old, new, "old", "new", 3).matches("@@ -").count(), 1);
+    }
+
+    #[test]
+    fn new_file_numbers_the_empty_side_from_zero() {
+        assert_eq!(
+            unified_diff("", "x\ny\n", "/dev/null", "new", 3),
+            "--- /dev/null\n+++ new\n@@ -0,0 +1,2 @@\n+x\n+y\n"
+        );
+    }
+
+    #[test]
+    fn insertion_keeps_surrounding_lines_equal() {
+        let diff = unified_diff("a\nc\n", "a\nb\nc\n", "old", "new", 3);
+        assert_eq!(diff, "--- old\n+++ new\n@@ -1,2 +1,3 @@\n a\n+b\n c\n");
+    }
+}
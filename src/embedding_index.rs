@@ -6,6 +6,7 @@ use std::io::{BufRead, BufReader, Write};
 use std::path::{Path, PathBuf};
 use ulid::Ulid;
 
+use crate::agent::CancelToken;
 use crate::embeddings::EmbeddingClient;
 use crate::knowledge::read_doc;
 
@@ -35,13 +36,17 @@ pub struct IndexStats {
     pub model: String,
 }
 
-pub fn build_knowledge_index(vault: &Path, client: &EmbeddingClient) -> Result<IndexStats> {
+/// Embed every knowledge doc into a fresh index. Stops between chunks when
+/// `cancel` fires, leaving the previous index in place.
+pub fn build_knowledge_index(vault: &Path, client: &EmbeddingClient, cancel: &CancelToken) -> Result<IndexStats> {
     let knowledge_root = vault.join("knowledge");
     let index_dir = vault.join("index");
     fs::create_dir_all(&index_dir)?;
     let index_path = index_dir.join("knowledge_embeddings.jsonl");
-    let mut file = fs::File::create(&index_path)
-        .with_context(|| format!("create index {}", index_path.display()))?;
+    // Built aside and renamed, so searches never see a half-written index
+    let tmp_path = index_path.with_extension("jsonl.tmp");
+    let mut file = fs::File::create(&tmp_path)
+        .with_context(|| format!("create index {}", tmp_path.display()))?;
 
     let mut doc_count = 0;
     let mut chunk_count = 0;
@@ -60,6 +65,11 @@ pub fn build_knowledge_index(vault: &Path, client: &EmbeddingClient) -> Result<I
         doc_count += 1;
         let rel_path = path.strip_prefix(vault).unwrap_or(&path).to_string_lossy().to_string();
         for chunk in chunks {
+            if cancel.is_cancelled() {
+                drop(file);
+                let _ = fs::remove_file(&tmp_path);
+                return Err(anyhow!("knowledge_index: cancelled"));
+            }
             let embedding = client.embed_text(&chunk)?;
             let record = EmbeddingRecord {
                 doc_path: rel_path.clone(),
@@ -75,6 +85,10 @@ pub fn build_knowledge_index(vault: &Path, client: &EmbeddingClient) -> Result<I
         }
     }
 
+    drop(file);
+    fs::rename(&tmp_path, &index_path)
+        .with_context(|| format!("replace index {}", index_path.display()))?;
+
     Ok(IndexStats {
         doc_count,
         chunk_count,
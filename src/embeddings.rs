@@ -3,6 +3,8 @@ use reqwest::blocking::Client;
 use serde_json::{json, Value};
 use std::env;
 
+use crate::retry::{self, RetryPolicy};
+
 #[derive(Debug, Clone, Copy)]
 pub enum EmbeddingProvider {
     OpenAI,
@@ -15,14 +17,15 @@ pub struct EmbeddingClient {
     base_url: String,
     model: String,
     http: Client,
+    retry: RetryPolicy,
 }
 
 impl EmbeddingClient {
     pub fn from_env() -> Result<Self> {
-        let provider = match env::var("EMBEDDING_PROVIDER").ok().as_deref() {
+        let provider = match crate::config::current().embedding_provider.value.as_deref() {
             Some("gemini") => EmbeddingProvider::Gemini,
             Some("openai") => EmbeddingProvider::OpenAI,
-            Some(other) => return Err(anyhow!("unsupported EMBEDDING_PROVIDER: {other}")),
+            Some(other) => return Err(anyhow!("unsupported embedding provider: {other}")),
             None => {
                 if env::var("OPENAI_API_KEY").is_ok() {
                     EmbeddingProvider::OpenAI
@@ -47,6 +50,7 @@ impl EmbeddingClient {
                     base_url,
                     model,
                     http: Client::new(),
+                    retry: RetryPolicy::from_env("EMBEDDING"),
                 })
             }
             EmbeddingProvider::Gemini => {
@@ -61,6 +65,7 @@ impl EmbeddingClient {
                     base_url,
                     model,
                     http: Client::new(),
+                    retry: RetryPolicy::from_env("EMBEDDING"),
                 })
             }
         }
@@ -88,17 +93,13 @@ impl EmbeddingClient {
             "input": text,
             "encoding_format": "float"
         });
-        let resp: Value = self
-            .http
-            .post(url)
-            .bearer_auth(&self.api_key)
-            .json(&body)
-            .send()
-            .context("send embeddings request")?
-            .error_for_status()
-            .context("embeddings status")?
-            .json()
-            .context("parse embeddings response")?;
+        let resp: Value = self.retry.run("embeddings", || {
+            let request = self.http.post(&url).bearer_auth(&self.api_key).json(&body);
+            retry::send(request)
+                .context("send embeddings request")?
+                .json()
+                .context("parse embeddings response")
+        })?;
         parse_openai_embedding(&resp)
     }
 
@@ -115,17 +116,17 @@ impl EmbeddingClient {
                 ]
             }
         });
-        let resp: Value = self
-            .http
-            .post(url)
-            .header("x-goog-api-key", &self.api_key)
-            .json(&body)
-            .send()
-            .context("send gemini embeddings request")?
-            .error_for_status()
-            .context("gemini embeddings status")?
-            .json()
-            .context("parse gemini embeddings response")?;
+        let resp: Value = self.retry.run("gemini embeddings", || {
+            let request = self
+                .http
+                .post(&url)
+                .header("x-goog-api-key", &self.api_key)
+                .json(&body);
+            retry::send(request)
+                .context("send gemini embeddings request")?
+                .json()
+                .context("parse gemini embeddings response")
+        })?;
         parse_gemini_embedding(&resp)
     }
 }
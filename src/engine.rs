@@ -1,20 +1,46 @@
 use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::env;
+use std::path::PathBuf;
+
+use crate::schema::JsonSchema;
 
 /// A tool call returned by the LLM.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolCall {
     pub id: String,
     pub name: String,
+    #[serde(default)]
     pub arguments: Value,
 }
 
 /// Response from a chat completion call.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatResponse {
+    #[serde(default)]
     pub content: Option<String>,
+    #[serde(default)]
     pub tool_calls: Vec<ToolCall>,
+    /// Token counts, when the provider reports them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<crate::usage::Usage>,
+}
+
+/// Result of `Engine::chat_structured`: JSON that passed schema validation.
+#[derive(Debug, Clone)]
+pub struct Structured {
+    pub value: Value,
+    /// Tokens used across all attempts.
+    pub usage: Option<crate::usage::Usage>,
+}
+
+impl Structured {
+    /// Deserialize the validated JSON into a typed result.
+    pub fn parse<T: serde::de::DeserializeOwned>(&self) -> Result<T> {
+        serde_json::from_value(self.value.clone()).context("decode structured response")
+    }
 }
 
 /// Which wire protocol to use.
@@ -24,28 +50,56 @@ pub enum EngineKind {
     OpenAI,
     Anthropic,
     Gemini,
+    /// Local OpenAI-compatible server (Ollama, llama.cpp); API key optional.
+    Local,
+    /// Scripted responses from a JSONL fixture (REPLAY_FIXTURE); no network.
+    Replay,
 }
 
 impl EngineKind {
     /// All known engine kinds.
-    pub const ALL: [EngineKind; 3] = [EngineKind::OpenAI, EngineKind::Anthropic, EngineKind::Gemini];
+    pub const ALL: [EngineKind; 5] = [
+        EngineKind::OpenAI,
+        EngineKind::Anthropic,
+        EngineKind::Gemini,
+        EngineKind::Local,
+        EngineKind::Replay,
+    ];
 
     /// Check if this engine has an API key configured in the environment.
-    pub fn is_available(self) -> bool {
+    pub async fn is_available(self) -> bool {
         let key_var = match self {
             EngineKind::OpenAI => "OPENAI_API_KEY",
             EngineKind::Anthropic => "ANTHROPIC_API_KEY",
             EngineKind::Gemini => "GEMINI_API_KEY",
+            // Local servers are keyless; available when something is listening
+            EngineKind::Local => {
+                let config = crate::config::current();
+                let base_url = match config.provider(EngineKind::Local) {
+                    Some(local) => local.base_url.value.clone(),
+                    None => EngineKind::Local.defaults().1.to_string(),
+                };
+                return crate::local::is_reachable(&base_url).await;
+            }
+            // Replay needs a fixture, not a key
+            EngineKind::Replay => return env::var("REPLAY_FIXTURE").is_ok(),
         };
         env::var("LLM_API_KEY").is_ok() || env::var(key_var).is_ok()
     }
 
+    /// Comma-separated list of valid engine names, for error messages.
+    pub fn valid_names() -> String {
+        Self::ALL.map(|k| k.as_str()).join(", ")
+    }
+
     /// Return the string name for this engine kind.
     pub fn as_str(self) -> &'static str {
         match self {
             EngineKind::OpenAI => "openai",
             EngineKind::Anthropic => "anthropic",
             EngineKind::Gemini => "gemini",
+            EngineKind::Local => "local",
+            EngineKind::Replay => "replay",
         }
     }
 
@@ -55,6 +109,8 @@ impl EngineKind {
             "openai" => Some(EngineKind::OpenAI),
             "anthropic" => Some(EngineKind::Anthropic),
             "gemini" => Some(EngineKind::Gemini),
+            "local" | "ollama" => Some(EngineKind::Local),
+            "replay" => Some(EngineKind::Replay),
             _ => None,
         }
     }
@@ -65,113 +121,239 @@ impl EngineKind {
             EngineKind::OpenAI => ("gpt-5-mini-2025-08-07", "https://api.openai.com"),
             EngineKind::Anthropic => ("claude-sonnet-4-20250514", "https://api.anthropic.com"),
             EngineKind::Gemini => ("gemini-2.0-flash", "https://generativelanguage.googleapis.com"),
+            EngineKind::Local => ("llama3.2", "http://localhost:11434"),
+            EngineKind::Replay => ("replay", ""),
         }
     }
 }
 
+/// Sampling parameters set per role; None leaves the provider's default.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GenerationParams {
+    pub temperature: Option<f64>,
+    /// Cap on reply tokens.
+    pub max_tokens: Option<usize>,
+}
+
+/// Receives assistant text fragments as they stream in.
+pub type DeltaSink<'a> = dyn FnMut(&str) + Send + 'a;
+
 /// Chat completion engine — one implementation per wire protocol.
+#[async_trait]
 pub trait Engine: Send + Sync {
-    fn chat(&self, messages: &[Value], tools: &[Value]) -> Result<ChatResponse>;
+    async fn chat(&self, messages: &[Value], tools: &[Value]) -> Result<ChatResponse>;
+    /// Like `chat`, but calls `on_delta` with each fragment of assistant text as
+    /// the provider produces it. The returned response holds the full content.
+    async fn chat_stream(
+        &self,
+        messages: &[Value],
+        tools: &[Value],
+        on_delta: &mut DeltaSink<'_>,
+    ) -> Result<ChatResponse> {
+        let response = self.chat(messages, tools).await?;
+        if let Some(ref content) = response.content {
+            on_delta(content);
+        }
+        Ok(response)
+    }
+    /// One structured-output call: asks the provider for JSON matching `schema`
+    /// and returns the raw JSON text as `content`. Providers override this with
+    /// their native mechanism; the default only asks for JSON in the prompt.
+    async fn chat_json(&self, messages: &[Value], schema: &JsonSchema) -> Result<ChatResponse> {
+        let mut messages = messages.to_vec();
+        messages.push(serde_json::json!({
+            "role": "system",
+            "content": format!(
+                "Respond with only a JSON value matching this JSON Schema, with no other text:\n{}",
+                schema.schema
+            ),
+        }));
+        self.chat(&messages, &[]).await
+    }
+    /// `chat_json`, then parse and validate the result. A response that isn't
+    /// valid JSON or violates the schema is sent back once with the errors.
+    async fn chat_structured(&self, messages: &[Value], schema: &JsonSchema) -> Result<Structured> {
+        let mut messages = messages.to_vec();
+        let mut usage: Option<crate::usage::Usage> = None;
+        for attempt in 0..2 {
+            let response = self.chat_json(&messages, schema).await?;
+            if let Some(u) = response.usage {
+                let total = usage.get_or_insert_with(Default::default);
+                total.input_tokens += u.input_tokens;
+                total.output_tokens += u.output_tokens;
+                total.cache_read_tokens += u.cache_read_tokens;
+                total.cache_write_tokens += u.cache_write_tokens;
+            }
+            let raw = response.content.unwrap_or_default();
+            let problem = match serde_json::from_str::<Value>(strip_code_fence(&raw)) {
+                Ok(value) => {
+                    let violations = crate::schema::validate(&schema.schema, &value);
+                    if violations.is_empty() {
+                        return Ok(Structured { value, usage });
+                    }
+                    crate::schema::describe(&violations)
+                }
+                Err(e) => format!("not valid JSON ({e})"),
+            };
+            if attempt == 1 {
+                return Err(anyhow!("{} response does not match schema: {problem}", schema.name));
+            }
+            messages.push(serde_json::json!({"role": "assistant", "content": raw}));
+            messages.push(serde_json::json!({
+                "role": "user",
+                "content": format!(
+                    "That response does not match the required schema: {problem}. Respond again with corrected JSON only."
+                ),
+            }));
+        }
+        unreachable!("loop returns on the second attempt")
+    }
+    /// Route retry notices somewhere other than stderr (e.g. gateway clients).
+    fn set_retry_hook(&mut self, _hook: crate::retry::RetryHook) {}
+    /// Route engine-switch notices from a fallback chain (no-op elsewhere).
+    fn set_fallback_hook(&mut self, _hook: crate::fallback::FallbackHook) {}
+    /// Engine kind currently serving calls, for engines that can switch
+    /// providers mid-run. None means "whatever the caller built".
+    fn active_engine(&self) -> Option<EngineKind> {
+        None
+    }
+    /// Apply sampling parameters to every later call (ignored by Replay).
+    fn set_params(&mut self, _params: GenerationParams) {}
     fn set_model(&mut self, model: String);
     fn model(&self) -> &str;
 }
 
-/// Resolve which engine kind to use from environment.
-pub fn resolve_engine_kind() -> Result<EngineKind> {
-    match env::var("LLM_ENGINE").ok().as_deref() {
-        Some("openai") => Ok(EngineKind::OpenAI),
-        Some("anthropic") => Ok(EngineKind::Anthropic),
-        Some("gemini") => Ok(EngineKind::Gemini),
-        Some(other) => Err(anyhow!(
-            "invalid LLM_ENGINE={other:?}. Valid options: openai, anthropic, gemini"
-        )),
-        None => Ok(EngineKind::OpenAI), // backward compatible default
+/// Models sometimes wrap JSON in a markdown fence despite instructions.
+fn strip_code_fence(text: &str) -> &str {
+    let text = text.trim();
+    match text.strip_prefix("```") {
+        Some(rest) => {
+            let rest = rest.strip_prefix("json").unwrap_or(rest);
+            rest.strip_suffix("```").unwrap_or(rest).trim()
+        }
+        None => text,
     }
 }
 
 /// Resolve API key, base URL, and model for the given engine kind.
-/// Generic LLM_* env vars override provider-specific ones.
+/// Model and base URL come from the runtime config, where generic LLM_* env
+/// vars override provider-specific ones; API keys are read from env only.
+/// For `Local`, `api_key` may be empty (no auth header is sent).
+/// For `Replay`, `base_url` holds the fixture path and `api_key` is empty.
 pub struct EngineConfig {
     pub api_key: String,
     pub base_url: String,
     pub model: String,
+    /// Whether the model was configured rather than a built-in default.
+    pub explicit_model: bool,
 }
 
 impl EngineConfig {
-    pub fn from_env(kind: EngineKind) -> Result<Self> {
-        let (provider_key_var, provider_url_var, provider_model_var, default_url, default_model) =
-            match kind {
-                EngineKind::OpenAI => (
-                    "OPENAI_API_KEY",
-                    "OPENAI_BASE_URL",
-                    "OPENAI_MODEL",
-                    "https://api.openai.com",
-                    "gpt-5-mini-2025-08-07",
-                ),
-                EngineKind::Anthropic => (
-                    "ANTHROPIC_API_KEY",
-                    "ANTHROPIC_BASE_URL",
-                    "ANTHROPIC_MODEL",
-                    "https://api.anthropic.com",
-                    "claude-sonnet-4-20250514",
-                ),
-                EngineKind::Gemini => (
-                    "GEMINI_API_KEY",
-                    "GEMINI_BASE_URL",
-                    "GEMINI_MODEL",
-                    "https://generativelanguage.googleapis.com",
-                    "gemini-2.0-flash",
-                ),
-            };
-
-        let api_key = env::var("LLM_API_KEY")
-            .or_else(|_| env::var(provider_key_var))
-            .with_context(|| {
-                format!("set LLM_API_KEY or {provider_key_var} for engine {kind:?}")
-            })?;
-
-        let base_url = env::var("LLM_BASE_URL")
-            .or_else(|_| env::var(provider_url_var))
-            .unwrap_or_else(|_| default_url.to_string());
-
-        let model = env::var("LLM_MODEL")
-            .or_else(|_| env::var(provider_model_var))
-            .unwrap_or_else(|_| default_model.to_string());
-
+    pub fn load(kind: EngineKind) -> Result<Self> {
+        if kind == EngineKind::Replay {
+            let fixture = env::var("REPLAY_FIXTURE")
+                .context("set REPLAY_FIXTURE to a fixture JSONL for engine Replay")?;
+            let model = env::var("LLM_MODEL").ok();
+            return Ok(Self {
+                api_key: String::new(),
+                base_url: fixture,
+                explicit_model: model.is_some(),
+                model: model.unwrap_or_else(|| "replay".to_string()),
+            });
+        }
+        let config = crate::config::current();
+        let provider = config
+            .provider(kind)
+            .ok_or_else(|| anyhow!("no provider settings for engine {kind:?}"))?;
+        let key_var = match kind {
+            EngineKind::OpenAI => "OPENAI_API_KEY",
+            EngineKind::Anthropic => "ANTHROPIC_API_KEY",
+            EngineKind::Gemini => "GEMINI_API_KEY",
+            EngineKind::Local => "LOCAL_API_KEY",
+            EngineKind::Replay => unreachable!("handled above"),
+        };
+        // LLM_API_KEY is meant for hosted providers; never send it to a local endpoint
+        let api_key = match kind {
+            EngineKind::Local => env::var(key_var).unwrap_or_default(),
+            _ => match env::var("LLM_API_KEY").or_else(|_| env::var(key_var)) {
+                Ok(key) => key,
+                Err(_) => anyhow::bail!("set LLM_API_KEY or {key_var} for engine {kind:?}"),
+            },
+        };
         Ok(Self {
             api_key,
-            base_url,
-            model,
+            base_url: provider.base_url.value.clone(),
+            model: provider.model.value.clone(),
+            explicit_model: provider.model.source != crate::config::Source::Default,
         })
     }
 }
 
-/// Build an engine from environment variables.
-pub fn create_engine() -> Result<Box<dyn Engine>> {
-    let kind = resolve_engine_kind()?;
-    create_engine_of_kind(kind)
+/// Build the engine for a role (`roles.<name>` in the runtime config): its
+/// engine, model and parameters, each falling back to the default engine's.
+/// Resolved on every call, so config reloads apply to the next job.
+pub async fn create_role_engine(role: crate::config::Role) -> Result<Box<dyn Engine>> {
+    let config = crate::config::current();
+    let settings = config.role(role);
+    let mut engine = create_engine_of_kind(config.role_engine(role)).await?;
+    if let Some(ref model) = settings.model.value {
+        engine.set_model(model.clone());
+    }
+    engine.set_params(settings.params());
+    Ok(engine)
+}
+
+/// Build an engine of a specific kind from the runtime config.
+/// If a fallback list is configured, the engine is wrapped in a fallback chain
+/// over the listed kinds that are available. If LLM_RECORD is set, every exchange is
+/// appended to that file as a replay fixture.
+pub async fn create_engine_of_kind(kind: EngineKind) -> Result<Box<dyn Engine>> {
+    let engine = build_engine_of_kind(kind).await?;
+    let engine = match crate::fallback::fallback_kinds(kind) {
+        fallbacks if fallbacks.is_empty() => engine,
+        fallbacks => {
+            let mut chain = vec![(kind, engine)];
+            for fallback in fallbacks {
+                if !fallback.is_available().await {
+                    continue;
+                }
+                match build_engine_of_kind(fallback).await {
+                    Ok(engine) => chain.push((fallback, engine)),
+                    Err(e) => eprintln!("Warning: fallback engine {} unavailable: {e:#}", fallback.as_str()),
+                }
+            }
+            Box::new(crate::fallback::FallbackEngine::new(chain))
+        }
+    };
+    match env::var("LLM_RECORD") {
+        Ok(path) if !path.is_empty() => Ok(Box::new(crate::replay::RecordingEngine::new(
+            engine,
+            PathBuf::from(path),
+        ))),
+        _ => Ok(engine),
+    }
 }
 
-/// Build an engine of a specific kind from environment variables.
-pub fn create_engine_of_kind(kind: EngineKind) -> Result<Box<dyn Engine>> {
-    let config = EngineConfig::from_env(kind)?;
+async fn build_engine_of_kind(kind: EngineKind) -> Result<Box<dyn Engine>> {
+    let config = EngineConfig::load(kind)?;
+    // e.g. ANTHROPIC_MAX_RETRIES, LOCAL_RETRY_BASE_MS
+    let retry = crate::retry::RetryPolicy::from_env(&kind.as_str().to_uppercase());
     match kind {
         EngineKind::OpenAI => {
             let client =
-                crate::openai::OpenAIClient::new(config.api_key, config.base_url, config.model);
+                crate::openai::OpenAIClient::new(config.api_key, config.base_url, config.model)
+                    .with_retry(retry);
             Ok(Box::new(client))
         }
         EngineKind::Anthropic => {
-            let max_tokens: usize = env::var("ANTHROPIC_MAX_TOKENS")
-                .ok()
-                .and_then(|v| v.parse().ok())
-                .unwrap_or(8192);
+            let max_tokens = crate::config::current().anthropic_max_tokens.value;
             let client = crate::anthropic::AnthropicClient::new(
                 config.api_key,
                 config.base_url,
                 config.model,
                 max_tokens,
-            );
+            )
+            .with_retry(retry);
             Ok(Box::new(client))
         }
         EngineKind::Gemini => {
@@ -179,9 +361,30 @@ pub fn create_engine_of_kind(kind: EngineKind) -> Result<Box<dyn Engine>> {
                 config.api_key,
                 config.base_url,
                 config.model,
-            );
+            )
+            .with_retry(retry);
             Ok(Box::new(client))
         }
+        EngineKind::Local => {
+            // Without an explicit model, use whatever the server has loaded first
+            let model = if config.explicit_model {
+                config.model
+            } else {
+                crate::local::discover_models(&config.base_url, &config.api_key)
+                    .await
+                    .ok()
+                    .and_then(|models| models.into_iter().next())
+                    .unwrap_or(config.model)
+            };
+            let client = crate::openai::OpenAIClient::new(config.api_key, config.base_url, model)
+                .with_retry(retry);
+            Ok(Box::new(client))
+        }
+        EngineKind::Replay => {
+            let engine =
+                crate::replay::ReplayEngine::load(&PathBuf::from(config.base_url), config.model)?;
+            Ok(Box::new(engine))
+        }
     }
 }
 
@@ -194,15 +397,12 @@ pub fn create_engine_with(
     model: String,
 ) -> Result<Box<dyn Engine>> {
     match kind {
-        EngineKind::OpenAI => {
+        EngineKind::OpenAI | EngineKind::Local => {
             let client = crate::openai::OpenAIClient::new(api_key, base_url, model);
             Ok(Box::new(client))
         }
         EngineKind::Anthropic => {
-            let max_tokens: usize = env::var("ANTHROPIC_MAX_TOKENS")
-                .ok()
-                .and_then(|v| v.parse().ok())
-                .unwrap_or(8192);
+            let max_tokens = crate::config::current().anthropic_max_tokens.value;
             let client =
                 crate::anthropic::AnthropicClient::new(api_key, base_url, model, max_tokens);
             Ok(Box::new(client))
@@ -211,5 +411,9 @@ pub fn create_engine_with(
             let client = crate::gemini_chat::GeminiChatClient::new(api_key, base_url, model);
             Ok(Box::new(client))
         }
+        EngineKind::Replay => {
+            let engine = crate::replay::ReplayEngine::load(&PathBuf::from(base_url), model)?;
+            Ok(Box::new(engine))
+        }
     }
 }
@@ -0,0 +1,169 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::Value;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::engine::{ChatResponse, DeltaSink, Engine, EngineKind, GenerationParams};
+use crate::schema::JsonSchema;
+
+/// Called when a fallback chain moves on to its next engine.
+pub type FallbackHook = Arc<dyn Fn(&FallbackNotice) + Send + Sync>;
+
+/// Details of an engine switch, passed to the `FallbackHook`.
+#[derive(Debug, Clone)]
+pub struct FallbackNotice {
+    pub from: EngineKind,
+    pub to: EngineKind,
+    /// Model of the engine now serving calls.
+    pub model: String,
+    /// The error that made `from` give up.
+    pub reason: String,
+}
+
+/// Engines to try after `primary`, from the configured fallback list
+/// (LLM_FALLBACK or `providers.fallback`, e.g. "openai,local").
+pub fn fallback_kinds(primary: EngineKind) -> Vec<EngineKind> {
+    crate::config::current()
+        .fallback
+        .value
+        .iter()
+        .copied()
+        .filter(|&kind| kind != primary)
+        .collect()
+}
+
+/// Tries each engine in order until one answers. Messages are kept in the
+/// canonical OpenAI format, so each engine translates the in-flight
+/// conversation (tool calls included) into its own wire format.
+///
+/// Each engine has already spent its own retry budget by the time it fails.
+/// Once the chain has moved on it stays there for the life of this instance,
+/// so the rest of a run doesn't wait out the broken provider on every turn.
+/// A stream that already emitted text is not retried elsewhere, since the
+/// client would see the answer twice.
+pub struct FallbackEngine {
+    engines: Vec<(EngineKind, Box<dyn Engine>)>,
+    active: AtomicUsize,
+    on_fallback: Option<FallbackHook>,
+}
+
+impl FallbackEngine {
+    /// `engines` must be non-empty; the first is the primary.
+    pub fn new(engines: Vec<(EngineKind, Box<dyn Engine>)>) -> Self {
+        assert!(!engines.is_empty(), "fallback chain needs at least one engine");
+        Self {
+            engines,
+            active: AtomicUsize::new(0),
+            on_fallback: None,
+        }
+    }
+
+    fn switch(&self, from: usize, err: &anyhow::Error) {
+        let to = from + 1;
+        self.active.store(to, Ordering::SeqCst);
+        let notice = FallbackNotice {
+            from: self.engines[from].0,
+            to: self.engines[to].0,
+            model: self.engines[to].1.model().to_string(),
+            reason: format!("{err:#}"),
+        };
+        match self.on_fallback {
+            Some(ref hook) => hook(&notice),
+            None => eprintln!(
+                "[{} failed: {}; falling back to {} ({})]",
+                notice.from.as_str(),
+                notice.reason,
+                notice.to.as_str(),
+                notice.model
+            ),
+        }
+    }
+}
+
+#[async_trait]
+impl Engine for FallbackEngine {
+    async fn chat(&self, messages: &[Value], tools: &[Value]) -> Result<ChatResponse> {
+        let mut idx = self.active.load(Ordering::SeqCst);
+        loop {
+            match self.engines[idx].1.chat(messages, tools).await {
+                Ok(response) => return Ok(response),
+                Err(err) if idx + 1 < self.engines.len() => {
+                    self.switch(idx, &err);
+                    idx += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    async fn chat_json(&self, messages: &[Value], schema: &JsonSchema) -> Result<ChatResponse> {
+        let mut idx = self.active.load(Ordering::SeqCst);
+        loop {
+            match self.engines[idx].1.chat_json(messages, schema).await {
+                Ok(response) => return Ok(response),
+                Err(err) if idx + 1 < self.engines.len() => {
+                    self.switch(idx, &err);
+                    idx += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[Value],
+        tools: &[Value],
+        on_delta: &mut DeltaSink<'_>,
+    ) -> Result<ChatResponse> {
+        let mut idx = self.active.load(Ordering::SeqCst);
+        loop {
+            let mut streamed = false;
+            let result = self.engines[idx]
+                .1
+                .chat_stream(messages, tools, &mut |text| {
+                    streamed = true;
+                    on_delta(text);
+                })
+                .await;
+            match result {
+                Ok(response) => return Ok(response),
+                Err(err) if !streamed && idx + 1 < self.engines.len() => {
+                    self.switch(idx, &err);
+                    idx += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    fn set_retry_hook(&mut self, hook: crate::retry::RetryHook) {
+        for (_, engine) in &mut self.engines {
+            engine.set_retry_hook(hook.clone());
+        }
+    }
+
+    fn set_params(&mut self, params: GenerationParams) {
+        for (_, engine) in &mut self.engines {
+            engine.set_params(params);
+        }
+    }
+
+    fn set_fallback_hook(&mut self, hook: FallbackHook) {
+        self.on_fallback = Some(hook);
+    }
+
+    fn active_engine(&self) -> Option<EngineKind> {
+        Some(self.engines[self.active.load(Ordering::SeqCst)].0)
+    }
+
+    /// Model overrides are provider-specific, so only the primary takes them.
+    fn set_model(&mut self, model: String) {
+        self.engines[0].1.set_model(model);
+    }
+
+    fn model(&self) -> &str {
+        self.engines[self.active.load(Ordering::SeqCst)].1.model()
+    }
+}
@@ -16,7 +16,7 @@ use tokio::signal;
 use tracing::info;
 
 /// Default gateway port.
-const DEFAULT_PORT: u16 = 9123;
+pub const DEFAULT_PORT: u16 = 9123;
 
 /// Returns the gateway data directory, creating it if needed.
 pub fn gateway_dir() -> Result<PathBuf> {
@@ -30,12 +30,9 @@ fn dirs_gateway() -> PathBuf {
     PathBuf::from(home).join(".j").join("gateway")
 }
 
-/// Resolve the port from env or default.
+/// The gateway port from the runtime config (J_GATEWAY_PORT, gateway.port).
 pub fn resolve_port() -> u16 {
-    std::env::var("J_GATEWAY_PORT")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(DEFAULT_PORT)
+    crate::config::current().gateway_port.value
 }
 
 // ── PID Guard ──────────────────────────────────────────────────────────
@@ -132,11 +129,12 @@ pub async fn run_daemon() -> Result<()> {
     info!(port, "starting j gateway daemon");
     info!("token written to {}", dir.join("token").display());
 
-    // Resolve vault path from env or default
-    let vault_path = crate::vault::resolve_vault(
-        std::env::var("J_VAULT").ok().map(std::path::PathBuf::from),
-    );
-    let sessions = session::SessionManager::new(vault_path)?;
+    // The vault the config was loaded from (J_VAULT or default)
+    let vault_path = crate::config::current().vault.clone();
+    tokio::spawn(crate::config::watch());
+    let tools = crate::tools::ToolRegistry::load(&vault_path).await;
+    info!(tools = tools.iter().count(), "loaded tool registry");
+    let sessions = session::SessionManager::new(vault_path, tools)?;
     let state = ws::AppState::new(token.clone(), sessions);
 
     // Backfill titles for existing sessions that have messages but no title
@@ -218,10 +216,51 @@ pub async fn handle_history(session_key: &str, limit: usize) -> Result<()> {
     Ok(())
 }
 
-pub async fn handle_send(session_key: &str, message: &str, wait: Option<Option<u64>>) -> Result<()> {
+pub async fn handle_usage(session_key: &str) -> Result<()> {
+    let payload = oneshot_request(
+        "session.usage",
+        serde_json::json!({"session_key": session_key}),
+    )
+    .await?;
+    println!("{}", serde_json::to_string(&payload)?);
+    Ok(())
+}
+
+pub async fn handle_cancel(session_key: &str) -> Result<()> {
+    let payload = oneshot_request(
+        "session.cancel",
+        serde_json::json!({"session_key": session_key}),
+    )
+    .await?;
+    println!("{}", serde_json::to_string(&payload)?);
+    Ok(())
+}
+
+pub async fn handle_send(
+    session_key: &str,
+    message: &str,
+    images: &[PathBuf],
+    wait: Option<Option<u64>>,
+    interrupt: bool,
+) -> Result<()> {
+    use base64::Engine as _;
     use futures_util::StreamExt;
     use tokio_tungstenite::tungstenite::Message;
 
+    // Images are uploaded inline; the daemon stores them under media/attachments/
+    let mut attachments = Vec::new();
+    for path in images {
+        let bytes = std::fs::read(path).with_context(|| format!("read {}", path.display()))?;
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("image.png");
+        attachments.push(serde_json::json!({
+            "name": name,
+            "data": base64::engine::general_purpose::STANDARD.encode(&bytes),
+        }));
+    }
+
     let (mut write, mut read) = cli_client::connect().await.map_err(|e| {
         eprintln!(
             "{}",
@@ -244,7 +283,12 @@ pub async fn handle_send(session_key: &str, message: &str, wait: Option<Option<u
         &mut write,
         &mut read,
         "session.send",
-        serde_json::json!({"session_key": session_key, "content": message}),
+        serde_json::json!({
+            "session_key": session_key,
+            "content": message,
+            "attachments": attachments,
+            "mode": if interrupt { "interrupt" } else { "queue" },
+        }),
     )
     .await?;
 
@@ -258,6 +302,11 @@ pub async fn handle_send(session_key: &str, message: &str, wait: Option<Option<u
         Some(Some(s)) => s,
     };
 
+    // A queued message is answered by the turn that starts when the queue
+    // drains; until then, final/error/cancelled belong to the earlier run
+    let mut waiting_in_queue =
+        send_payload.get("status").and_then(|v| v.as_str()) == Some("queued");
+
     // Block and stream events until final/error or timeout
     let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(timeout_secs);
 
@@ -325,8 +374,12 @@ pub async fn handle_send(session_key: &str, message: &str, wait: Option<Option<u
         println!("{}", serde_json::to_string(&val)?);
 
         match event_name {
+            "queue" if val.pointer("/payload/depth").and_then(|d| d.as_u64()) == Some(0) => {
+                waiting_in_queue = false;
+            }
+            _ if waiting_in_queue => {}
             "final" => return Ok(()),
-            "error" => std::process::exit(1),
+            "error" | "cancelled" => std::process::exit(1),
             _ => {}
         }
     }
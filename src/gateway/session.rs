@@ -1,7 +1,7 @@
 use anyhow::{anyhow, Context, Result};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::AtomicBool;
@@ -10,9 +10,10 @@ use tokio::sync::{mpsc, Mutex, RwLock, Semaphore};
 use tracing::{info, warn};
 
 use crate::thread_store::{
-    append_event, build_event, create_thread, read_thread, EventType, Role,
-    ThreadEvent, ThreadMeta,
+    append_event, build_event, create_thread, read_thread, EventType, Role, ThreadMeta,
 };
+use crate::agent::{Approver, CancelToken, Cancelled, PendingApprovals};
+use crate::tools::ToolRegistry;
 
 /// Persistent mapping of session_key -> session metadata.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -31,6 +32,15 @@ pub struct SessionEntry {
     pub model: Option<String>,
 }
 
+/// An image sent along with a user message.
+#[derive(Debug, Clone)]
+pub enum Attachment {
+    /// Existing vault media, e.g. "media/food/pizza.png".
+    Media(String),
+    /// Uploaded file with base64-encoded bytes; stored under media/attachments/.
+    Upload { name: String, data: String },
+}
+
 /// Runtime state for a single session.
 struct SessionState {
     entry: RwLock<SessionEntry>,
@@ -44,6 +54,68 @@ struct SessionState {
     title_running: Arc<AtomicBool>,
     /// Per-session engine override (runtime only, not persisted to config).
     engine_override: RwLock<Option<crate::engine::EngineKind>>,
+    /// Tool calls of the current run waiting for session.approve/deny.
+    approvals: Arc<PendingApprovals>,
+    /// Messages that arrived during a run, and the run's cancel token.
+    inbox: Mutex<Inbox>,
+}
+
+/// How `send` treats a message that arrives while a run is in progress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendMode {
+    /// Wait for the run to finish and fold into the next turn.
+    Queue,
+    /// Cancel the run and start the next turn now.
+    Interrupt,
+}
+
+/// What `send` did with a message.
+pub enum SendOutcome {
+    /// A run started for it.
+    Started,
+    /// It waits in the inbox behind the current run; `depth` counts it.
+    Queued { depth: usize },
+}
+
+/// A user message waiting in a session's inbox, attachments already stored.
+struct QueuedMessage {
+    content: String,
+    attachments: Vec<Value>,
+}
+
+/// Queued messages and the current run's token under one lock, so an
+/// interrupt always cancels the run before the one that takes its message.
+#[derive(Default)]
+struct Inbox {
+    /// Oldest first.
+    queue: VecDeque<QueuedMessage>,
+    /// Cancels the current run; None while idle.
+    run_cancel: Option<CancelToken>,
+}
+
+impl Inbox {
+    /// Queue `message`, first cancelling the current run if `mode` says so.
+    /// Returns the queue depth, this message included.
+    fn push(&mut self, message: QueuedMessage, mode: SendMode) -> usize {
+        if mode == SendMode::Interrupt
+            && let Some(cancel) = &self.run_cancel
+        {
+            cancel.cancel();
+        }
+        self.queue.push_back(message);
+        self.queue.len()
+    }
+
+    /// Everything queued, taken for a new run along with that run's token.
+    /// None when nothing is waiting.
+    fn start_run(&mut self) -> Option<(Vec<QueuedMessage>, CancelToken)> {
+        if self.queue.is_empty() {
+            return None;
+        }
+        let cancel = CancelToken::default();
+        self.run_cancel = Some(cancel.clone());
+        Some((self.queue.drain(..).collect(), cancel))
+    }
 }
 
 /// Manages all sessions, backed by sessions.json in the vault.
@@ -51,11 +123,12 @@ pub struct SessionManager {
     vault_path: PathBuf,
     sessions: RwLock<HashMap<String, Arc<SessionState>>>,
     index_path: PathBuf,
+    tools: Arc<ToolRegistry>,
 }
 
 impl SessionManager {
-    /// Load or create the session manager for a vault.
-    pub fn new(vault_path: PathBuf) -> Result<Self> {
+    /// Load or create the session manager for a vault, running agents with `tools`.
+    pub fn new(vault_path: PathBuf, tools: ToolRegistry) -> Result<Self> {
         let index_path = vault_path.join("gateway").join("sessions.json");
         if let Some(parent) = index_path.parent() {
             fs::create_dir_all(parent)?;
@@ -100,6 +173,8 @@ impl SessionManager {
                         deep_think_running: Arc::new(AtomicBool::new(false)),
                         title_running: Arc::new(AtomicBool::new(false)),
                         engine_override: RwLock::new(None),
+                        approvals: Arc::new(PendingApprovals::default()),
+                        inbox: Mutex::new(Inbox::default()),
                     }),
                 );
             }
@@ -111,6 +186,7 @@ impl SessionManager {
             vault_path,
             sessions: RwLock::new(sessions),
             index_path,
+            tools: Arc::new(tools),
         })
     }
 
@@ -134,6 +210,10 @@ impl SessionManager {
         &self.vault_path
     }
 
+    pub fn tools(&self) -> &ToolRegistry {
+        &self.tools
+    }
+
     /// Open (create-if-missing) a session. Returns session metadata and subscribes the client.
     pub async fn open(
         &self,
@@ -149,15 +229,13 @@ impl SessionManager {
             }
         }
 
-        // Create new session — resolve engine + model for the thread header
-        let engine_kind = crate::engine::resolve_engine_kind().unwrap_or(crate::engine::EngineKind::OpenAI);
-        let config = crate::engine::EngineConfig::from_env(engine_kind);
-        let (model, base_url) = match &config {
-            Ok(c) => (c.model.clone(), c.base_url.clone()),
-            Err(_) => {
-                let (dm, du) = engine_kind.defaults();
-                (dm.to_string(), du.to_string())
-            }
+        // Create new session — resolve the "chat" role's engine + model for the thread header
+        let runtime = crate::config::current();
+        let engine_kind = runtime.role_engine(crate::config::Role::Chat);
+        let model = runtime.role_model(crate::config::Role::Chat);
+        let base_url = match runtime.provider(engine_kind) {
+            Some(provider) => provider.base_url.value.clone(),
+            None => engine_kind.defaults().1.to_string(),
         };
         let thread_path = create_thread(
             &self.vault_path,
@@ -196,6 +274,8 @@ impl SessionManager {
             deep_think_running: Arc::new(AtomicBool::new(false)),
             title_running: Arc::new(AtomicBool::new(false)),
             engine_override: RwLock::new(None),
+            approvals: Arc::new(PendingApprovals::default()),
+            inbox: Mutex::new(Inbox::default()),
         });
 
         {
@@ -220,7 +300,7 @@ impl SessionManager {
 
     /// Set the engine override for a session (runtime only).
     pub async fn set_engine(&self, session_key: &str, kind: crate::engine::EngineKind) -> Result<(String, String)> {
-        if !kind.is_available() {
+        if !kind.is_available().await {
             return Err(anyhow!("engine {:?} has no API key configured", kind));
         }
         let sessions = self.sessions.read().await;
@@ -229,7 +309,7 @@ impl SessionManager {
             .ok_or_else(|| anyhow!("session not found: {session_key}"))?;
         *state.engine_override.write().await = Some(kind);
         // Update the cached entry for display purposes
-        let config = crate::engine::EngineConfig::from_env(kind)?;
+        let config = crate::engine::EngineConfig::load(kind)?;
         {
             let mut entry = state.entry.write().await;
             entry.engine = Some(kind.as_str().to_string());
@@ -248,7 +328,7 @@ impl SessionManager {
         if let Some(kind) = *state.engine_override.read().await {
             return Ok(kind);
         }
-        crate::engine::resolve_engine_kind()
+        Ok(crate::config::current().role_engine(crate::config::Role::Chat))
     }
 
     /// Get the engine override for a session (used by run_agent).
@@ -261,6 +341,34 @@ impl SessionManager {
         None
     }
 
+    /// Stop the session's current run. It ends at the next point it checks
+    /// for cancellation, with a run.cancelled note on the thread.
+    pub async fn cancel_run(&self, session_key: &str) -> Result<()> {
+        let sessions = self.sessions.read().await;
+        let state = sessions
+            .get(session_key)
+            .ok_or_else(|| anyhow!("session not found: {session_key}"))?;
+        match state.inbox.lock().await.run_cancel.as_ref() {
+            Some(cancel) => {
+                cancel.cancel();
+                Ok(())
+            }
+            None => Err(anyhow!("no run in progress")),
+        }
+    }
+
+    /// Approve or deny a tool call the session's run is waiting on.
+    pub async fn resolve_approval(&self, session_key: &str, approval_id: &str, approved: bool) -> Result<()> {
+        let sessions = self.sessions.read().await;
+        let state = sessions
+            .get(session_key)
+            .ok_or_else(|| anyhow!("session not found: {session_key}"))?;
+        if !state.approvals.resolve(approval_id, approved) {
+            return Err(anyhow!("no pending approval: {approval_id}"));
+        }
+        Ok(())
+    }
+
     /// Fetch last N events from a session's thread transcript.
     pub async fn history(&self, session_key: &str, limit: usize) -> Result<Vec<String>> {
         let sessions = self.sessions.read().await;
@@ -274,13 +382,32 @@ impl SessionManager {
         Ok(all.into_iter().skip(start).collect())
     }
 
+    /// Token usage and cost for a session's thread, in total and per agent run.
+    pub async fn usage(&self, session_key: &str) -> Result<crate::usage::ThreadUsage> {
+        let sessions = self.sessions.read().await;
+        let state = sessions
+            .get(session_key)
+            .ok_or_else(|| anyhow!("session not found: {session_key}"))?;
+        let thread_path = PathBuf::from(&state.entry.read().await.thread_path);
+        crate::usage::thread_usage(&thread_path)
+    }
+
     /// Send a user message and trigger an agent run.
+    /// Attachments are recorded as `attachment_added` events ahead of the
+    /// message, so the agent sees the images first when the thread is replayed.
     /// Returns immediately after enqueuing; the agent run happens in background.
+    ///
+    /// While a run is in progress the message waits in the session's inbox
+    /// and is written to the thread when the next turn starts, together with
+    /// anything else queued by then. `SendMode::Interrupt` also cancels the
+    /// current run so that turn starts right away.
     pub async fn send(
         self: &Arc<Self>,
         session_key: &str,
         content: &str,
-    ) -> Result<()> {
+        attachments: &[Attachment],
+        mode: SendMode,
+    ) -> Result<SendOutcome> {
         let state = {
             let sessions = self.sessions.read().await;
             sessions
@@ -290,20 +417,25 @@ impl SessionManager {
         };
 
         let entry_snapshot = state.entry.read().await.clone();
-        let thread_path = PathBuf::from(&entry_snapshot.thread_path);
 
-        // Append user message to thread
-        let user_event = build_event(
-            None,
-            EventType::UserMessage,
-            Role::User,
-            Some(Value::String(content.to_string())),
-            None,
-            None,
-            None,
-            None,
-        );
-        append_event(&thread_path, user_event)?;
+        // Validate and store every attachment before queueing anything
+        let mut attached: Vec<Value> = Vec::new();
+        for attachment in attachments {
+            let (path, name) = match attachment {
+                Attachment::Media(path) => {
+                    let full = crate::media::resolve_media_path(&self.vault_path, path)?;
+                    if crate::media::image_mime(&full).is_none() {
+                        return Err(anyhow!("not a supported image: {path}"));
+                    }
+                    let name = path.rsplit('/').next().unwrap_or(path).to_string();
+                    (path.clone(), name)
+                }
+                Attachment::Upload { name, data } => {
+                    (crate::media::save_attachment(&self.vault_path, name, data)?, name.clone())
+                }
+            };
+            attached.push(json!({ "path": path, "name": name }));
+        }
 
         // Track first user line for preview + trigger title generation
         {
@@ -322,37 +454,65 @@ impl SessionManager {
             "type": "event",
             "event": "user_message",
             "session_id": entry_snapshot.session_key,
-            "payload": { "content": content }
+            "payload": { "content": content, "attachments": attached }
         });
         broadcast(&state.subscribers, &user_msg).await;
 
-        // Try to acquire the run semaphore (non-blocking, owned)
+        // The permit is taken under the inbox lock, and the runner only gives
+        // it back under the same lock, so a queued message is never stranded
+        let mut inbox = state.inbox.lock().await;
+        let depth = inbox.push(QueuedMessage { content: content.to_string(), attachments: attached }, mode);
         let permit = match Arc::clone(&state.run_semaphore).try_acquire_owned() {
             Ok(permit) => permit,
             Err(_) => {
-                return Err(anyhow!("session is busy (agent run in progress)"));
+                drop(inbox);
+                broadcast(&state.subscribers, &queue_event(session_key, depth)).await;
+                return Ok(SendOutcome::Queued { depth });
             }
         };
+        drop(inbox);
 
-        // Spawn agent run in background
+        // Spawn agent runs in background until the inbox is empty
         let manager = Arc::clone(self);
         let session_key = session_key.to_string();
         let state = Arc::clone(&state);
         tokio::spawn(async move {
-            let _permit = permit; // held until this block completes
-            if let Err(e) = manager.run_agent(&session_key, &state).await {
-                warn!(session_key, error = %e, "agent run failed");
-                let err_event = json!({
-                    "type": "event",
-                    "event": "error",
-                    "session_id": session_key,
-                    "payload": { "message": e.to_string() }
-                });
-                broadcast(&state.subscribers, &err_event).await;
+            let mut first = true;
+            loop {
+                let (batch, cancel) = {
+                    let mut inbox = state.inbox.lock().await;
+                    match inbox.start_run() {
+                        Some(run) => run,
+                        None => {
+                            drop(permit);
+                            break;
+                        }
+                    }
+                };
+                if !first {
+                    broadcast(&state.subscribers, &queue_event(&session_key, 0)).await;
+                }
+                first = false;
+
+                let outcome = match record_messages(&state, &batch).await {
+                    Ok(()) => manager.run_agent(&session_key, &state, cancel).await,
+                    Err(e) => Err(e),
+                };
+                state.inbox.lock().await.run_cancel = None;
+                if let Err(e) = outcome {
+                    warn!(session_key, error = %e, "agent run failed");
+                    let err_event = json!({
+                        "type": "event",
+                        "event": "error",
+                        "session_id": session_key,
+                        "payload": { "message": e.to_string() }
+                    });
+                    broadcast(&state.subscribers, &err_event).await;
+                }
             }
         });
 
-        Ok(())
+        Ok(SendOutcome::Started)
     }
 
     /// Subscribe to a session's events (without sending a message).
@@ -370,12 +530,12 @@ impl SessionManager {
         Ok(rx)
     }
 
-    /// Run the agent loop for a session (blocking work wrapped in spawn_blocking).
-    async fn run_agent(&self, session_key: &str, state: &SessionState) -> Result<()> {
+    /// Run the agent loop for a session and forward its events to subscribers.
+    async fn run_agent(&self, session_key: &str, state: &SessionState, cancel: CancelToken) -> Result<()> {
         let entry_snap = state.entry.read().await.clone();
         let thread_path = PathBuf::from(&entry_snap.thread_path);
         let vault_path = self.vault_path.clone();
-        let session_key_owned = session_key.to_string();
+        let tools = Arc::clone(&self.tools);
         let subscribers = state.subscribers.lock().await.clone();
 
         // Write run.started marker
@@ -391,69 +551,99 @@ impl SessionManager {
         );
         append_event(&thread_path, started)?;
 
-        // Create a sync channel for the agent loop to emit events
-        let (event_tx, event_rx) = std::sync::mpsc::channel::<crate::agent::AgentEvent>();
+        // Channel for the agent loop to emit events
+        let (event_tx, mut event_rx) = mpsc::unbounded_channel::<crate::agent::AgentEvent>();
 
-        // Spawn a task that bridges sync events to async broadcast
+        // Spawn a task that forwards agent events to subscribers
         let subs_clone = subscribers.clone();
         let sk = session_key.to_string();
         let bridge_task = tokio::spawn(async move {
-            // Wrap the blocking recv in spawn_blocking to avoid blocking the async runtime
-            loop {
-                let rx_ref = &event_rx;
-                // We can't move event_rx into spawn_blocking, so poll with try_recv + sleep
-                match rx_ref.try_recv() {
-                    Ok(event) => {
-                        use crate::agent::AgentEvent;
-                        let ws_event = match event {
-                            AgentEvent::ToolCallStart { tool_name, arguments } => json!({
-                                "type": "event",
-                                "event": "tool_call_start",
-                                "session_id": sk,
-                                "payload": { "tool_name": tool_name, "arguments": arguments }
-                            }),
-                            AgentEvent::ToolCallResult { tool_name, result } => json!({
-                                "type": "event",
-                                "event": "tool_call_result",
-                                "session_id": sk,
-                                "payload": { "tool_name": tool_name, "result": result }
-                            }),
-                            AgentEvent::FinalContent { content } => json!({
-                                "type": "event",
-                                "event": "final",
-                                "session_id": sk,
-                                "payload": { "content": content }
-                            }),
-                            AgentEvent::DeepThinkComplete { monologue } => json!({
-                                "type": "event",
-                                "event": "deep_think_complete",
-                                "session_id": sk,
-                                "payload": { "length": monologue.len() }
-                            }),
-                        };
-                        broadcast_to(&subs_clone, &ws_event);
-                    }
-                    Err(std::sync::mpsc::TryRecvError::Empty) => {
-                        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
-                    }
-                    Err(std::sync::mpsc::TryRecvError::Disconnected) => break,
-                }
+            while let Some(event) = event_rx.recv().await {
+                use crate::agent::AgentEvent;
+                let ws_event = match event {
+                    AgentEvent::Delta { text } => json!({
+                        "type": "event",
+                        "event": "delta",
+                        "session_id": sk,
+                        "payload": { "text": text }
+                    }),
+                    AgentEvent::ToolCallStart { tool_name, arguments } => json!({
+                        "type": "event",
+                        "event": "tool_call_start",
+                        "session_id": sk,
+                        "payload": { "tool_name": tool_name, "arguments": arguments }
+                    }),
+                    AgentEvent::ToolCallResult { tool_name, result } => json!({
+                        "type": "event",
+                        "event": "tool_call_result",
+                        "session_id": sk,
+                        "payload": { "tool_name": tool_name, "result": result }
+                    }),
+                    AgentEvent::FinalContent { content } => json!({
+                        "type": "event",
+                        "event": "final",
+                        "session_id": sk,
+                        "payload": { "content": content }
+                    }),
+                    AgentEvent::Retry { label, attempt, max_retries, delay_ms, reason } => json!({
+                        "type": "event",
+                        "event": "retry",
+                        "session_id": sk,
+                        "payload": {
+                            "label": label,
+                            "attempt": attempt,
+                            "max_retries": max_retries,
+                            "delay_ms": delay_ms,
+                            "reason": reason,
+                        }
+                    }),
+                    AgentEvent::EngineFallback { from, to, model, reason } => json!({
+                        "type": "event",
+                        "event": "engine_fallback",
+                        "session_id": sk,
+                        "payload": {
+                            "from": from,
+                            "to": to,
+                            "model": model,
+                            "reason": reason,
+                        }
+                    }),
+                    AgentEvent::ApprovalRequired { approval_id, tool_name, arguments } => json!({
+                        "type": "event",
+                        "event": "approval_required",
+                        "session_id": sk,
+                        "payload": {
+                            "approval_id": approval_id,
+                            "tool_name": tool_name,
+                            "arguments": arguments,
+                        }
+                    }),
+                    AgentEvent::DeepThinkComplete { monologue } => json!({
+                        "type": "event",
+                        "event": "deep_think_complete",
+                        "session_id": sk,
+                        "payload": { "length": monologue.len() }
+                    }),
+                };
+                broadcast_to(&subs_clone, &ws_event);
             }
         });
 
-        // Run the sync agent loop in a blocking thread
-        let deep_think_flag_clone = Arc::clone(&state.deep_think_running);
-        let engine_override = (*state.engine_override.read().await).clone();
-        let result = tokio::task::spawn_blocking(move || {
-            run_agent_blocking(&vault_path, &thread_path, &session_key_owned, event_tx, deep_think_flag_clone, engine_override)
-        })
-        .await
-        .context("agent task panicked")?;
+        let result = run_session_agent(&vault_path, &thread_path, state, event_tx, tools, cancel).await;
 
         // Wait for bridge to drain remaining events
         let _ = bridge_task.await;
 
-        if let Err(ref e) = result {
+        let cancelled = matches!(result, Err(ref e) if e.is::<Cancelled>());
+        if cancelled {
+            let cancelled_event = json!({
+                "type": "event",
+                "event": "cancelled",
+                "session_id": session_key,
+                "payload": {}
+            });
+            broadcast_to(&subscribers, &cancelled_event);
+        } else if let Err(ref e) = result {
             let err_event = json!({
                 "type": "event",
                 "event": "error",
@@ -463,12 +653,13 @@ impl SessionManager {
             broadcast_to(&subscribers, &err_event);
         }
 
-        // Write run.completed marker
+        // Write run.completed (or run.cancelled) marker
+        let marker = if cancelled { "run.cancelled" } else { "run.completed" };
         let completed = build_event(
             None,
             EventType::SystemNote,
             Role::System,
-            Some(Value::String("run.completed".to_string())),
+            Some(Value::String(marker.to_string())),
             None,
             None,
             None,
@@ -476,6 +667,9 @@ impl SessionManager {
         );
         append_event(&PathBuf::from(&entry_snap.thread_path), completed)?;
 
+        if cancelled {
+            return Ok(());
+        }
         result.map(|_| ())
     }
 
@@ -507,11 +701,11 @@ impl SessionManager {
         let session_key = entry_snap.session_key.clone();
         let state = Arc::clone(state);
 
-        tokio::task::spawn_blocking(move || {
-            match generate_title_blocking(&content) {
-                Ok(title) => {
+        tokio::spawn(async move {
+            match generate_title(&content).await {
+                Ok((title, usage, model)) => {
                     // Append TitleGenerated event to thread JSONL
-                    let event = build_event(
+                    let mut event = build_event(
                         None,
                         EventType::TitleGenerated,
                         Role::System,
@@ -521,26 +715,24 @@ impl SessionManager {
                         None,
                         Some("auto_title".to_string()),
                     );
+                    crate::agent::attach_usage(&mut event, usage, &model);
                     if let Err(e) = append_event(&thread_path, event) {
                         warn!(error = %e, "failed to append title event");
                     }
 
-                    // Update cached entry + broadcast via tokio runtime
-                    let rt = tokio::runtime::Handle::current();
-                    rt.block_on(async {
-                        {
-                            let mut entry = state.entry.write().await;
-                            entry.title = Some(title.clone());
-                        }
+                    // Update cached entry + broadcast
+                    {
+                        let mut entry = state.entry.write().await;
+                        entry.title = Some(title.clone());
+                    }
 
-                        let title_event = json!({
-                            "type": "event",
-                            "event": "title_generated",
-                            "session_id": session_key,
-                            "payload": { "title": title }
-                        });
-                        broadcast(&state.subscribers, &title_event).await;
+                    let title_event = json!({
+                        "type": "event",
+                        "event": "title_generated",
+                        "session_id": session_key,
+                        "payload": { "title": title }
                     });
+                    broadcast(&state.subscribers, &title_event).await;
 
                     info!(session_key, "title generated");
                 }
@@ -553,23 +745,40 @@ impl SessionManager {
     }
 }
 
-/// Generate a title by calling a cheap LLM model.
-fn generate_title_blocking(first_message: &str) -> Result<String> {
+/// Generate a title with the "title" role's engine (ideally a cheap model).
+/// Returns the title plus the call's token usage and model for cost accounting.
+async fn generate_title(
+    first_message: &str,
+) -> Result<(String, Option<crate::usage::Usage>, String)> {
     dotenvy::dotenv().ok();
-    let client = crate::engine::create_engine()?;
+    #[derive(Deserialize)]
+    struct Title {
+        title: String,
+    }
+
+    let client = crate::engine::create_role_engine(crate::config::Role::Title).await?;
     let messages = vec![
-        json!({"role": "system", "content": "Generate a concise title (max 8 words) for this conversation. Return only the title, nothing else."}),
+        json!({"role": "system", "content": "Generate a concise title (max 8 words) for this conversation."}),
         json!({"role": "user", "content": first_message}),
     ];
-    let resp = client.chat(&messages, &[])?;
-    let title = resp.content.unwrap_or_default().trim().to_string();
+    let schema = crate::schema::JsonSchema::new(
+        "session_title",
+        json!({
+            "type": "object",
+            "properties": { "title": { "type": "string", "minLength": 1 } },
+            "required": ["title"],
+            "additionalProperties": false,
+        }),
+    );
+    let resp = client.chat_structured(&messages, &schema).await?;
+    let title = resp.parse::<Title>()?.title.trim().to_string();
     // Truncate to 100 chars
     let title = if title.len() > 100 {
         title.chars().take(100).collect()
     } else {
         title
     };
-    Ok(title)
+    Ok((title, resp.usage, client.model().to_string()))
 }
 
 /// Truncate a string to max chars at a word boundary.
@@ -599,58 +808,101 @@ fn broadcast_to(subscribers: &[mpsc::UnboundedSender<Value>], event: &Value) {
     }
 }
 
-/// Run the sync agent loop. Called from spawn_blocking.
-fn run_agent_blocking(
+/// `queue` event reporting how many messages wait for the next turn.
+fn queue_event(session_key: &str, depth: usize) -> Value {
+    json!({
+        "type": "event",
+        "event": "queue",
+        "session_id": session_key,
+        "payload": { "depth": depth }
+    })
+}
+
+/// Write dequeued messages to the thread, each one's attachments first.
+async fn record_messages(state: &SessionState, batch: &[QueuedMessage]) -> Result<()> {
+    let thread_path = PathBuf::from(&state.entry.read().await.thread_path);
+    for message in batch {
+        for value in &message.attachments {
+            let attachment_event = build_event(
+                None,
+                EventType::AttachmentAdded,
+                Role::User,
+                Some(value.clone()),
+                None,
+                None,
+                None,
+                None,
+            );
+            append_event(&thread_path, attachment_event)?;
+        }
+        let user_event = build_event(
+            None,
+            EventType::UserMessage,
+            Role::User,
+            Some(Value::String(message.content.clone())),
+            None,
+            None,
+            None,
+            None,
+        );
+        append_event(&thread_path, user_event)?;
+    }
+    Ok(())
+}
+
+/// Run the agent loop for one session turn.
+async fn run_session_agent(
     vault_path: &Path,
     thread_path: &Path,
-    _session_key: &str,
-    event_sink: std::sync::mpsc::Sender<crate::agent::AgentEvent>,
-    deep_think_running: Arc<AtomicBool>,
-    engine_override: Option<crate::engine::EngineKind>,
+    state: &SessionState,
+    event_sink: mpsc::UnboundedSender<crate::agent::AgentEvent>,
+    tools: Arc<ToolRegistry>,
+    cancel: CancelToken,
 ) -> Result<String> {
     use crate::agent::{run_agent_loop, AgentConfig};
-    use crate::chat::load_system_prompt;
+    use crate::chat::build_context;
+    use crate::context::ContextBudget;
 
     dotenvy::dotenv().ok();
 
-    let client = match engine_override {
-        Some(kind) => crate::engine::create_engine_of_kind(kind)?,
-        None => crate::engine::create_engine()?,
-    };
+    let engine_override = *state.engine_override.read().await;
 
-    let system_prompt = load_system_prompt(vault_path)?;
-    let mut messages = vec![json!({"role": "system", "content": system_prompt})];
-
-    // Load recent history for context
-    let history = read_thread(thread_path, None, None)?;
-    let start = history.len().saturating_sub(50);
-    for line in history.into_iter().skip(start) {
-        if let Ok(event) = serde_json::from_str::<ThreadEvent>(&line) {
-            if let Some(value) = event.content {
-                let content = match value {
-                    Value::String(text) => text,
-                    other => other.to_string(),
-                };
-                match event.event_type {
-                    EventType::UserMessage => {
-                        let content = crate::agent::with_datetime(event.ts, &content);
-                        messages.push(json!({"role": "user", "content": content}));
-                    }
-                    EventType::AssistantMessage => {
-                        messages.push(json!({"role": "assistant", "content": content}));
-                    }
-                    EventType::InnerMonologue => {
-                        messages.push(json!({"role": "system", "content": format!("[inner thoughts — not spoken aloud]\n{content}")}));
-                    }
-                    _ => {}
-                }
-            }
+    // A session's engine.set choice replaces the "chat" role's engine and model
+    let mut client = match engine_override {
+        Some(kind) => {
+            let mut client = crate::engine::create_engine_of_kind(kind).await?;
+            client.set_params(crate::config::current().role(crate::config::Role::Chat).params());
+            client
         }
-    }
+        None => crate::engine::create_role_engine(crate::config::Role::Chat).await?,
+    };
+    let retry_sink = event_sink.clone();
+    client.set_retry_hook(Arc::new(move |notice: &crate::retry::RetryNotice| {
+        let _ = retry_sink.send(crate::agent::AgentEvent::Retry {
+            label: notice.label.clone(),
+            attempt: notice.attempt,
+            max_retries: notice.max_retries,
+            delay_ms: notice.delay.as_millis() as u64,
+            reason: notice.reason.clone(),
+        });
+    }));
+    let fallback_sink = event_sink.clone();
+    client.set_fallback_hook(Arc::new(move |notice: &crate::fallback::FallbackNotice| {
+        let _ = fallback_sink.send(crate::agent::AgentEvent::EngineFallback {
+            from: notice.from.as_str().to_string(),
+            to: notice.to.as_str().to_string(),
+            model: notice.model.clone(),
+            reason: notice.reason.clone(),
+        });
+    }));
+
+    // System prompt and history fitted to the model's context window
+    let budget = ContextBudget::for_model(client.model(), &tools.schemas(None));
+    let messages = build_context(vault_path, thread_path, client.as_ref(), &budget, None).await?;
 
     let engine_name = engine_override
         .map(|k| k.as_str().to_string())
-        .or_else(|| crate::engine::resolve_engine_kind().ok().map(|k| k.as_str().to_string()));
+        .or_else(|| Some(crate::config::current().role_engine(crate::config::Role::Chat).as_str().to_string()));
     let model_name = Some(client.model().to_string());
 
     let config = AgentConfig {
@@ -658,14 +910,17 @@ fn run_agent_blocking(
         thread_path: thread_path.to_path_buf(),
         max_turns: 20,
         allow_commit: false,
+        tools,
         tool_filter: None,
         event_sink: Some(event_sink),
-        deep_think_running,
+        approver: Some(Approver::Gateway(Arc::clone(&state.approvals))),
+        cancel,
+        deep_think_running: Arc::clone(&state.deep_think_running),
         engine_name,
         model_name,
     };
 
-    let final_messages = run_agent_loop(&config, messages, client.as_ref())?;
+    let final_messages = run_agent_loop(&config, messages, client.as_ref()).await?;
 
     // Extract the last assistant message as the final content
     let final_content = final_messages
@@ -682,3 +937,62 @@ fn run_agent_blocking(
 
     Ok(final_content)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(content: &str) -> QueuedMessage {
+        QueuedMessage { content: content.to_string(), attachments: Vec::new() }
+    }
+
+    fn contents(batch: &[QueuedMessage]) -> Vec<&str> {
+        batch.iter().map(|m| m.content.as_str()).collect()
+    }
+
+    #[test]
+    fn interrupt_cancels_the_run_before_its_own() {
+        let mut inbox = Inbox::default();
+        inbox.push(message("first"), SendMode::Queue);
+        let (batch, first_run) = inbox.start_run().unwrap();
+        assert_eq!(contents(&batch), ["first"]);
+
+        assert_eq!(inbox.push(message("stop, do this"), SendMode::Interrupt), 1);
+        assert!(first_run.is_cancelled());
+
+        // The runner then finishes the cancelled run and starts the next
+        inbox.run_cancel = None;
+        let (batch, own_run) = inbox.start_run().unwrap();
+        assert_eq!(contents(&batch), ["stop, do this"]);
+        assert!(!own_run.is_cancelled(), "the interrupting message's own run must complete");
+        assert!(inbox.start_run().is_none());
+    }
+
+    #[test]
+    fn interrupt_lands_on_the_run_in_progress() {
+        let mut inbox = Inbox::default();
+        inbox.push(message("first"), SendMode::Queue);
+        let (_, first_run) = inbox.start_run().unwrap();
+        inbox.push(message("second"), SendMode::Queue);
+        inbox.run_cancel = None;
+        let (batch, second_run) = inbox.start_run().unwrap();
+        assert_eq!(contents(&batch), ["second"]);
+
+        // Arrives while "second" runs: that run is the one to stop
+        inbox.push(message("third"), SendMode::Interrupt);
+        assert!(second_run.is_cancelled());
+        assert!(!first_run.is_cancelled());
+    }
+
+    #[test]
+    fn queue_mode_leaves_the_run_alone() {
+        let mut inbox = Inbox::default();
+        inbox.push(message("first"), SendMode::Queue);
+        let (_, run) = inbox.start_run().unwrap();
+        assert_eq!(inbox.push(message("second"), SendMode::Queue), 1);
+        assert_eq!(inbox.push(message("third"), SendMode::Queue), 2);
+        assert!(!run.is_cancelled());
+        let (batch, _) = inbox.start_run().unwrap();
+        assert_eq!(contents(&batch), ["second", "third"]);
+    }
+}
@@ -15,7 +15,7 @@ use tokio::sync::mpsc;
 use tracing::{debug, info, warn};
 
 use super::protocol::{self, InboundFrame};
-use super::session::SessionManager;
+use super::session::{Attachment, SendMode, SendOutcome, SessionManager};
 
 #[derive(Clone)]
 pub struct AppState {
@@ -296,6 +296,21 @@ async fn dispatch_method(
             }
         }
 
+        "session.usage" => {
+            let session_key = params
+                .get("session_key")
+                .and_then(|v| v.as_str())
+                .unwrap_or("main");
+
+            match state.sessions.usage(session_key).await {
+                Ok(usage) => protocol::Response::ok(
+                    id,
+                    json!({ "session_key": session_key, "total": usage.total, "runs": usage.runs }),
+                ),
+                Err(e) => protocol::Response::err(id, "session.usage.failed", e.to_string()),
+            }
+        }
+
         "session.send" => {
             let session_key = params
                 .get("session_key")
@@ -306,47 +321,120 @@ async fn dispatch_method(
                 .and_then(|v| v.as_str())
                 .unwrap_or("");
 
-            if content.is_empty() {
+            // [{"path": "media/..."}] for vault media, [{"name", "data"}] for uploads
+            let mut attachments = Vec::new();
+            for item in params.get("attachments").and_then(|v| v.as_array()).into_iter().flatten() {
+                let path = item.get("path").and_then(|v| v.as_str());
+                let upload = item
+                    .get("name")
+                    .and_then(|v| v.as_str())
+                    .zip(item.get("data").and_then(|v| v.as_str()));
+                match (path, upload) {
+                    (Some(path), _) => attachments.push(Attachment::Media(path.to_string())),
+                    (None, Some((name, data))) => attachments.push(Attachment::Upload {
+                        name: name.to_string(),
+                        data: data.to_string(),
+                    }),
+                    (None, None) => {
+                        return protocol::Response::err(
+                            id,
+                            "invalid_params",
+                            "each attachment needs either path or name + data",
+                        );
+                    }
+                }
+            }
+
+            if content.is_empty() && attachments.is_empty() {
                 return protocol::Response::err(id, "invalid_params", "content is required");
             }
 
-            match state.sessions.send(session_key, content).await {
-                Ok(()) => protocol::Response::ok(id, json!({ "status": "accepted" })),
-                Err(e) => {
-                    let code = if e.to_string().contains("busy") {
-                        "session.busy"
-                    } else {
-                        "session.send.failed"
-                    };
-                    protocol::Response::err(id, code, e.to_string())
+            // "queue" (default) folds into the next turn; "interrupt" cancels the current run
+            let mode = match params.get("mode").and_then(|v| v.as_str()) {
+                None | Some("queue") => SendMode::Queue,
+                Some("interrupt") => SendMode::Interrupt,
+                Some(other) => {
+                    return protocol::Response::err(
+                        id,
+                        "invalid_params",
+                        format!("unknown mode: {other} (expected queue or interrupt)"),
+                    );
                 }
+            };
+
+            match state.sessions.send(session_key, content, &attachments, mode).await {
+                Ok(SendOutcome::Started) => protocol::Response::ok(id, json!({ "status": "accepted" })),
+                Ok(SendOutcome::Queued { depth }) => {
+                    protocol::Response::ok(id, json!({ "status": "queued", "depth": depth }))
+                }
+                Err(e) => protocol::Response::err(id, "session.send.failed", e.to_string()),
+            }
+        }
+
+        "session.cancel" => {
+            let session_key = params
+                .get("session_key")
+                .and_then(|v| v.as_str())
+                .unwrap_or("main");
+            match state.sessions.cancel_run(session_key).await {
+                Ok(()) => protocol::Response::ok(id, json!({ "status": "cancelling" })),
+                Err(e) => protocol::Response::err(id, "session.cancel.failed", e.to_string()),
+            }
+        }
+
+        "session.approve" | "session.deny" => {
+            let session_key = params
+                .get("session_key")
+                .and_then(|v| v.as_str())
+                .unwrap_or("main");
+            let Some(approval_id) = params.get("approval_id").and_then(|v| v.as_str()) else {
+                return protocol::Response::err(id, "invalid_params", "approval_id is required");
+            };
+            let approved = method == "session.approve";
+            match state.sessions.resolve_approval(session_key, approval_id, approved).await {
+                Ok(()) => protocol::Response::ok(
+                    id,
+                    json!({ "approval_id": approval_id, "approved": approved }),
+                ),
+                Err(e) => protocol::Response::err(id, "session.approval.failed", e.to_string()),
             }
         }
 
         "engine.list" => {
-            let current = crate::engine::resolve_engine_kind().unwrap_or(crate::engine::EngineKind::OpenAI);
-            let engines: Vec<Value> = crate::engine::EngineKind::ALL
-                .iter()
-                .map(|&kind| {
-                    let available = kind.is_available();
-                    let (default_model, default_url) = kind.defaults();
-                    let (model, base_url) = if available {
-                        match crate::engine::EngineConfig::from_env(kind) {
-                            Ok(c) => (c.model, c.base_url),
-                            Err(_) => (default_model.to_string(), default_url.to_string()),
-                        }
-                    } else {
-                        (default_model.to_string(), default_url.to_string())
-                    };
-                    json!({
-                        "engine": kind.as_str(),
-                        "available": available,
-                        "model": model,
-                        "base_url": base_url,
-                        "active": kind == current,
-                    })
-                })
-                .collect();
+            let current = crate::config::current().role_engine(crate::config::Role::Chat);
+            let mut engines: Vec<Value> = Vec::new();
+            for kind in crate::engine::EngineKind::ALL {
+                let available = kind.is_available().await;
+                let (default_model, default_url) = kind.defaults();
+                let (model, base_url) = if available {
+                    match crate::engine::EngineConfig::load(kind) {
+                        Ok(c) => (c.model, c.base_url),
+                        Err(_) => (default_model.to_string(), default_url.to_string()),
+                    }
+                } else {
+                    (default_model.to_string(), default_url.to_string())
+                };
+                engines.push(json!({
+                    "engine": kind.as_str(),
+                    "available": available,
+                    "model": model,
+                    "base_url": base_url,
+                    "active": kind == current,
+                }));
+            }
+            // List the models a reachable local server has pulled
+            for entry in engines.iter_mut() {
+                if entry["engine"] == "local" && entry["available"] == true {
+                    let base_url = entry["base_url"].as_str().unwrap_or_default().to_string();
+                    let api_key = crate::engine::EngineConfig::load(crate::engine::EngineKind::Local)
+                        .map(|c| c.api_key)
+                        .unwrap_or_default();
+                    let models = crate::local::discover_models(&base_url, &api_key)
+                        .await
+                        .unwrap_or_default();
+                    entry["models"] = json!(models);
+                }
+            }
             protocol::Response::ok(id, json!({ "engines": engines }))
         }
 
@@ -372,11 +460,32 @@ async fn dispatch_method(
                 None => protocol::Response::err(
                     id,
                     "invalid_params",
-                    format!("unknown engine: {engine_str}. Valid: openai, anthropic, gemini"),
+                    format!(
+                        "unknown engine: {engine_str}. Valid: {}",
+                        crate::engine::EngineKind::valid_names()
+                    ),
                 ),
             }
         }
 
+        "tools.list" => {
+            let tools: Vec<Value> = state
+                .sessions
+                .tools()
+                .iter()
+                .map(|tool| {
+                    json!({
+                        "name": tool.name(),
+                        "description": tool.description(),
+                        "permission": tool.permission().as_str(),
+                        "read_only": tool.read_only(),
+                        "source": tool.source(),
+                    })
+                })
+                .collect();
+            protocol::Response::ok(id, json!({ "tools": tools }))
+        }
+
         "system.prompt" => {
             let vault_path = state.sessions.vault_path();
             match crate::chat::load_system_prompt(vault_path) {
@@ -1,15 +1,23 @@
-use anyhow::{Context, Result};
-use reqwest::blocking::Client;
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use reqwest::Client;
 use serde_json::{json, Value};
+use std::collections::HashMap;
 use std::time::Duration;
 
-use crate::engine::{ChatResponse, Engine, ToolCall};
+use crate::engine::{ChatResponse, DeltaSink, Engine, GenerationParams, ToolCall};
+use crate::media::ContentPart;
+use crate::retry::{self, RetryHook, RetryPolicy};
+use crate::schema::JsonSchema;
+use crate::sse;
 
 pub struct GeminiChatClient {
     api_key: String,
     base_url: String,
     http: Client,
     model: String,
+    params: GenerationParams,
+    retry: RetryPolicy,
 }
 
 impl GeminiChatClient {
@@ -23,22 +31,32 @@ impl GeminiChatClient {
             base_url,
             http,
             model,
+            params: GenerationParams::default(),
+            retry: RetryPolicy::default(),
         }
     }
-}
 
-impl Engine for GeminiChatClient {
-    fn chat(&self, messages: &[Value], tools: &[Value]) -> Result<ChatResponse> {
-        let url = format!(
-            "{}/v1beta/models/{}:generateContent?key={}",
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    fn url(&self, method: &str) -> String {
+        format!(
+            "{}/v1beta/models/{}:{method}?key={}",
             self.base_url.trim_end_matches('/'),
             self.model,
             self.api_key,
-        );
+        )
+    }
 
+    fn request_body(&self, messages: &[Value], tools: &[Value]) -> Value {
         // Separate system messages from conversation
         let mut system_parts: Vec<String> = Vec::new();
         let mut contents: Vec<Value> = Vec::new();
+        // functionResponse is matched by function name, not call id; remember
+        // which name each call id belongs to (ids may come from another provider)
+        let mut call_names: HashMap<String, String> = HashMap::new();
 
         for msg in messages {
             let role = msg.get("role").and_then(|v| v.as_str()).unwrap_or("");
@@ -49,9 +67,15 @@ impl Engine for GeminiChatClient {
                     system_parts.push(content.to_string());
                 }
                 "user" => {
+                    let parts = match msg.get("content") {
+                        Some(Value::Array(parts)) => {
+                            parts.iter().filter_map(convert_content_part).collect()
+                        }
+                        _ => vec![json!({"text": content})],
+                    };
                     contents.push(json!({
                         "role": "user",
-                        "parts": [{"text": content}],
+                        "parts": parts,
                     }));
                 }
                 "assistant" => {
@@ -65,6 +89,9 @@ impl Engine for GeminiChatClient {
                             let func = tc.get("function").unwrap_or(&Value::Null);
                             let name =
                                 func.get("name").and_then(|v| v.as_str()).unwrap_or("");
+                            if let Some(id) = tc.get("id").and_then(|v| v.as_str()) {
+                                call_names.insert(id.to_string(), name.to_string());
+                            }
                             let args_str =
                                 func.get("arguments").and_then(|v| v.as_str()).unwrap_or("{}");
                             let args: Value =
@@ -93,6 +120,10 @@ impl Engine for GeminiChatClient {
                         .get("tool_call_id")
                         .and_then(|v| v.as_str())
                         .unwrap_or("unknown");
+                    let name = call_names
+                        .get(tool_call_id)
+                        .map(String::as_str)
+                        .unwrap_or(tool_call_id);
                     // Try to parse content as JSON for structured response
                     let response_value: Value =
                         serde_json::from_str(content).unwrap_or(json!({"result": content}));
@@ -100,7 +131,7 @@ impl Engine for GeminiChatClient {
                         "role": "user",
                         "parts": [{
                             "functionResponse": {
-                                "name": tool_call_id,
+                                "name": name,
                                 "response": response_value,
                             }
                         }],
@@ -126,18 +157,36 @@ impl Engine for GeminiChatClient {
         if !declarations.is_empty() {
             body["tools"] = json!([{ "functionDeclarations": declarations }]);
         }
+        if let Some(temperature) = self.params.temperature {
+            body["generationConfig"]["temperature"] = json!(temperature);
+        }
+        if let Some(max_tokens) = self.params.max_tokens {
+            body["generationConfig"]["maxOutputTokens"] = json!(max_tokens);
+        }
+        body
+    }
 
+    /// Non-streaming generateContent call.
+    async fn complete(&self, body: &Value) -> Result<ChatResponse> {
+        let url = self.url("generateContent");
         let resp: Value = self
-            .http
-            .post(&url)
-            .header("content-type", "application/json")
-            .json(&body)
-            .send()
-            .context("send gemini generateContent")?
-            .error_for_status()
-            .context("gemini generateContent status")?
-            .json()
-            .context("parse gemini generateContent response")?;
+            .retry
+            .run_async("gemini generateContent", || {
+                let request = self
+                    .http
+                    .post(&url)
+                    .header("content-type", "application/json")
+                    .json(body);
+                async move {
+                    retry::send_async(request)
+                        .await
+                        .context("send gemini generateContent")?
+                        .json()
+                        .await
+                        .context("parse gemini generateContent response")
+                }
+            })
+            .await?;
 
         // Parse response: candidates[0].content.parts[]
         let parts = resp
@@ -149,41 +198,115 @@ impl Engine for GeminiChatClient {
             .cloned()
             .unwrap_or_default();
 
-        let mut text_parts: Vec<String> = Vec::new();
-        let mut tool_calls: Vec<ToolCall> = Vec::new();
-
-        for part in &parts {
-            if let Some(text) = part.get("text").and_then(|v| v.as_str()) {
-                text_parts.push(text.to_string());
-            }
-            if let Some(fc) = part.get("functionCall") {
-                let name = fc
-                    .get("name")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("")
-                    .to_string();
-                let args = fc.get("args").cloned().unwrap_or(json!({}));
-                // Gemini doesn't provide tool call IDs; generate one
-                let id = format!("gemini_{}", ulid::Ulid::new());
-                tool_calls.push(ToolCall {
-                    id,
-                    name,
-                    arguments: args,
-                });
-            }
-        }
+        let (text_parts, tool_calls) = parse_parts(&parts);
 
         let content = if text_parts.is_empty() {
             None
         } else {
             Some(text_parts.join(""))
         };
+        let usage = crate::usage::from_gemini(&resp);
 
         Ok(ChatResponse {
             content,
             tool_calls,
+            usage,
         })
     }
+}
+
+#[async_trait]
+impl Engine for GeminiChatClient {
+    async fn chat(&self, messages: &[Value], tools: &[Value]) -> Result<ChatResponse> {
+        let body = self.request_body(messages, tools);
+        self.complete(&body).await
+    }
+
+    async fn chat_json(&self, messages: &[Value], schema: &JsonSchema) -> Result<ChatResponse> {
+        let mut body = self.request_body(messages, &[]);
+        body["generationConfig"]["responseMimeType"] = json!("application/json");
+        body["generationConfig"]["responseSchema"] = response_schema(&schema.schema);
+        self.complete(&body).await
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[Value],
+        tools: &[Value],
+        on_delta: &mut DeltaSink<'_>,
+    ) -> Result<ChatResponse> {
+        let url = format!("{}&alt=sse", self.url("streamGenerateContent"));
+        let body = self.request_body(messages, tools);
+
+        let resp = self
+            .retry
+            .run_async("gemini streamGenerateContent", || {
+                let request = self
+                    .http
+                    .post(&url)
+                    .header("content-type", "application/json")
+                    .json(&body);
+                async move {
+                    retry::send_async(request)
+                        .await
+                        .context("send gemini streamGenerateContent")
+                }
+            })
+            .await?;
+
+        // Each event is a partial GenerateContentResponse. Text parts are
+        // incremental fragments; functionCall parts always arrive whole.
+        let mut content = String::new();
+        let mut tool_calls: Vec<ToolCall> = Vec::new();
+        let mut usage = None;
+        sse::read_events(resp, |event| {
+            if event.data.is_empty() {
+                return Ok(());
+            }
+            let chunk: Value = serde_json::from_str(&event.data)
+                .context("parse gemini stream chunk")?;
+            if let Some(err) = chunk.get("error") {
+                return Err(anyhow!("gemini stream error: {err}"));
+            }
+            // Each chunk carries running totals; the last one wins
+            if let Some(u) = crate::usage::from_gemini(&chunk) {
+                usage = Some(u);
+            }
+            let parts = chunk
+                .get("candidates")
+                .and_then(|c| c.get(0))
+                .and_then(|c| c.get("content"))
+                .and_then(|c| c.get("parts"))
+                .and_then(|p| p.as_array())
+                .cloned()
+                .unwrap_or_default();
+            let (text_parts, calls) = parse_parts(&parts);
+            for text in text_parts {
+                if !text.is_empty() {
+                    on_delta(&text);
+                    content.push_str(&text);
+                }
+            }
+            tool_calls.extend(calls);
+            Ok(())
+        })
+        .await?;
+
+        let content = if content.is_empty() { None } else { Some(content) };
+        Ok(ChatResponse {
+            content,
+            tool_calls,
+            usage,
+        })
+    }
+
+    fn set_retry_hook(&mut self, hook: RetryHook) {
+        self.retry.on_retry = Some(hook);
+    }
+
+    fn set_params(&mut self, params: GenerationParams) {
+        self.params = params;
+    }
 
     fn set_model(&mut self, model: String) {
         self.model = model;
@@ -194,6 +317,88 @@ impl Engine for GeminiChatClient {
     }
 }
 
+/// Split response parts into text fragments and function calls.
+fn parse_parts(parts: &[Value]) -> (Vec<String>, Vec<ToolCall>) {
+    let mut text_parts: Vec<String> = Vec::new();
+    let mut tool_calls: Vec<ToolCall> = Vec::new();
+
+    for part in parts {
+        if let Some(text) = part.get("text").and_then(|v| v.as_str()) {
+            text_parts.push(text.to_string());
+        }
+        if let Some(fc) = part.get("functionCall") {
+            let name = fc
+                .get("name")
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .to_string();
+            let args = fc.get("args").cloned().unwrap_or(json!({}));
+            // Gemini doesn't provide tool call IDs; generate one
+            let id = format!("gemini_{}", ulid::Ulid::new());
+            tool_calls.push(ToolCall {
+                id,
+                name,
+                arguments: args,
+            });
+        }
+    }
+
+    (text_parts, tool_calls)
+}
+
+/// Gemini's `responseSchema` is an OpenAPI subset that rejects keywords such
+/// as `additionalProperties`; keep only the ones it understands.
+fn response_schema(schema: &Value) -> Value {
+    const SUPPORTED: &[&str] = &[
+        "type", "format", "description", "nullable", "enum", "properties", "required",
+        "items", "minItems", "maxItems", "minimum", "maximum", "anyOf", "propertyOrdering",
+    ];
+    match schema {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .filter(|(key, _)| SUPPORTED.contains(&key.as_str()))
+                .map(|(key, value)| {
+                    let value = match key.as_str() {
+                        "properties" => Value::Object(
+                            value
+                                .as_object()
+                                .map(|props| {
+                                    props
+                                        .iter()
+                                        .map(|(name, prop)| (name.clone(), response_schema(prop)))
+                                        .collect()
+                                })
+                                .unwrap_or_default(),
+                        ),
+                        "items" => response_schema(value),
+                        "anyOf" => Value::Array(
+                            value
+                                .as_array()
+                                .map(|branches| branches.iter().map(response_schema).collect())
+                                .unwrap_or_default(),
+                        ),
+                        _ => value.clone(),
+                    };
+                    (key.clone(), value)
+                })
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// Convert an OpenAI-style content part to a Gemini part. Gemini only fetches
+/// its own file URIs, so remote images are passed along as a text reference.
+fn convert_content_part(part: &Value) -> Option<Value> {
+    match crate::media::parse_part(part)? {
+        ContentPart::Text(text) => Some(json!({ "text": text })),
+        ContentPart::InlineImage { mime, data } => Some(json!({
+            "inlineData": { "mimeType": mime, "data": data },
+        })),
+        ContentPart::ImageUrl(url) => Some(json!({ "text": format!("[image: {url}]") })),
+    }
+}
+
 /// Convert an OpenAI tool schema to a Gemini functionDeclaration.
 fn convert_to_declaration(tool: &Value) -> Option<Value> {
     let func = tool.get("function")?;
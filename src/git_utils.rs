@@ -8,7 +8,8 @@ pub fn git_commit(repo_root: &Path, files: &[PathBuf], message: &str) -> Result<
     for file in files {
         add.arg(file);
     }
-    let status = add.status().context("git add")?;
+    // git's chatter goes to stderr so callers that own stdout (`j mcp serve`) stay clean
+    let status = add.stdout(std::io::stderr()).status().context("git add")?;
     if !status.success() {
         return Err(anyhow!("git add failed"));
     }
@@ -18,6 +19,7 @@ pub fn git_commit(repo_root: &Path, files: &[PathBuf], message: &str) -> Result<
         .arg("commit")
         .arg("-m")
         .arg(message)
+        .stdout(std::io::stderr())
         .status()
         .context("git commit")?;
     if !status.success() {
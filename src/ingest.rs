@@ -9,11 +9,12 @@ use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 use ulid::Ulid;
 
-use crate::agent::{run_agent_loop, AgentConfig};
+use crate::agent::{run_agent_loop, AgentConfig, CancelToken};
 use crate::embedding_index::build_knowledge_index;
 use crate::embeddings::EmbeddingClient;
 use crate::git_utils::git_commit;
 use crate::thread_store::{create_thread, ThreadMeta};
+use crate::tools::ToolRegistry;
 use crate::vault::resolve_vault;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -36,7 +37,11 @@ pub struct IngestOptions {
     pub source: Option<String>,
     pub tags: Vec<String>,
     pub title: Option<String>,
+    /// Model for this run instead of the `ingest` role's.
     pub model: Option<String>,
+    /// Keep stdout clean (progress goes to stderr, the agent's reply is
+    /// dropped), for callers that own stdout such as `j mcp serve`.
+    pub quiet: bool,
 }
 
 pub struct IngestResult {
@@ -46,7 +51,9 @@ pub struct IngestResult {
     pub proposal_count: usize,
 }
 
-pub fn run_ingest(options: IngestOptions) -> Result<IngestResult> {
+/// Copy `options.file` into the vault and run the ingestion agent over it,
+/// with the runtime config the caller set up.
+pub async fn run_ingest(options: IngestOptions) -> Result<IngestResult> {
     dotenvy::dotenv().ok();
 
     let vault = resolve_vault(options.vault);
@@ -103,7 +110,14 @@ pub fn run_ingest(options: IngestOptions) -> Result<IngestResult> {
     let source_content = render_source_file(&front_matter, &file_content)?;
     fs::write(&source_path, &source_content)?;
 
-    println!("Copied source to {}", source_path.display());
+    let progress = |line: String| {
+        if options.quiet {
+            eprintln!("{line}");
+        } else {
+            println!("{line}");
+        }
+    };
+    progress(format!("Copied source to {}", source_path.display()));
 
     // Create ingestion thread
     let thread_path = create_thread(&vault, None, None, Some(ThreadMeta {
@@ -117,10 +131,10 @@ pub fn run_ingest(options: IngestOptions) -> Result<IngestResult> {
     // Load ingestion system prompt
     let system_prompt = load_ingest_prompt(&vault, &slug, &source_id)?;
 
-    // Set up LLM engine
-    let mut client = crate::engine::create_engine()?;
-    if let Some(ref model_override) = options.model {
-        client.set_model(model_override.clone());
+    // Set up LLM engine from the caller's config; `model` overrides the role's
+    let mut client = crate::engine::create_role_engine(crate::config::Role::Ingest).await?;
+    if let Some(model) = &options.model {
+        client.set_model(model.clone());
     }
 
     // Build initial messages: system prompt + document content as user message
@@ -138,12 +152,16 @@ pub fn run_ingest(options: IngestOptions) -> Result<IngestResult> {
         thread_path: thread_path.clone(),
         max_turns: 20,
         allow_commit: false,
+        tools: Arc::new(ToolRegistry::load(&vault).await),
         tool_filter: Some(vec![
             "knowledge_apply".into(),
             "knowledge_read".into(),
             "knowledge_search".into(),
         ]),
-        event_sink: None,
+        // Any sink stops the loop streaming the reply to stdout
+        event_sink: options.quiet.then(|| tokio::sync::mpsc::unbounded_channel().0),
+        approver: None,
+        cancel: CancelToken::default(),
         deep_think_running: Arc::new(AtomicBool::new(false)),
         engine_name: None,
         model_name: None,
@@ -152,8 +170,8 @@ pub fn run_ingest(options: IngestOptions) -> Result<IngestResult> {
     // Snapshot proposal count before ingestion
     let proposals_before = count_proposals(&vault);
 
-    println!("Running ingestion agent...");
-    let _final_messages = run_agent_loop(&config, initial_messages, client.as_ref())?;
+    progress("Running ingestion agent...".to_string());
+    let _final_messages = run_agent_loop(&config, initial_messages, client.as_ref()).await?;
 
     // Update processing status to complete
     update_processing_status(&source_path, "complete")?;
@@ -163,11 +181,18 @@ pub fn run_ingest(options: IngestOptions) -> Result<IngestResult> {
 
     // Embed (best effort)
     let summary_path = vault.join("summaries/sources").join(format!("{slug}.md"));
-    if let Ok(embed_client) = EmbeddingClient::from_env() {
-        match build_knowledge_index(&vault, &embed_client) {
-            Ok(stats) => println!("Re-indexed: {} docs / {} chunks", stats.doc_count, stats.chunk_count),
-            Err(e) => eprintln!("Warning: embedding failed: {e}"),
-        }
+    // The embedding client is blocking, so build the index off the async workers
+    let index_vault = vault.clone();
+    let reindex = tokio::task::spawn_blocking(move || {
+        EmbeddingClient::from_env()
+            .ok()
+            .map(|embed_client| build_knowledge_index(&index_vault, &embed_client, &CancelToken::default()))
+    })
+    .await?;
+    match reindex {
+        Some(Ok(stats)) => progress(format!("Re-indexed: {} docs / {} chunks", stats.doc_count, stats.chunk_count)),
+        Some(Err(e)) => eprintln!("Warning: embedding failed: {e}"),
+        None => {}
     }
 
     // Git commit (best effort)
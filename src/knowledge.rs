@@ -6,7 +6,9 @@ use std::fs;
 use std::path::{Path, PathBuf};
 use ulid::Ulid;
 
-use crate::audit::LedgerEntry;
+use crate::audit::{doc_history, hash_str, read_ledger, read_object, store_object, LedgerEntry};
+use crate::diff::unified_diff;
+use crate::vault::confine;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SourceRef {
@@ -35,7 +37,7 @@ pub struct FrontMatter {
     pub summary: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct KnowledgePatch {
     pub doc_path: String,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -84,10 +86,8 @@ pub fn apply_patch(
     change_summary: &str,
 ) -> Result<ApplyResult> {
     let patch_for_ledger = patch.clone();
+    confine(vault_path, Path::new(&patch.doc_path), "knowledge")?;
     let doc_path = vault_path.join(&patch.doc_path);
-    if !doc_path.starts_with(vault_path) {
-        return Err(anyhow!("doc_path must be within vault"));
-    }
 
     let mut body = String::new();
     let now = Utc::now();
@@ -178,6 +178,11 @@ pub fn apply_patch(
         None
     };
     let new_content = render_markdown(&front_matter, &body)?;
+    // Keep both versions so the change can be reverted
+    if let Some(prior) = &prior_content {
+        store_object(vault_path, prior)?;
+    }
+    store_object(vault_path, &new_content)?;
     fs::create_dir_all(doc_path.parent().unwrap_or(Path::new(".")))?;
     fs::write(&doc_path, new_content.as_bytes())
         .with_context(|| format!("write {}", doc_path.display()))?;
@@ -198,6 +203,138 @@ pub fn apply_patch(
     Ok(ApplyResult { doc_path, ledger_entry })
 }
 
+/// Restore `doc_path` to an earlier version: the content right after ledger
+/// entry `target` (a `led_` id), or the stored object with hash (prefix)
+/// `target`. Recorded as a `revert` ledger op, which can itself be reverted.
+pub fn revert_doc(
+    vault_path: &Path,
+    doc_path: &str,
+    target: &str,
+    author: &str,
+    reason: &str,
+) -> Result<ApplyResult> {
+    confine(vault_path, Path::new(doc_path), "knowledge")?;
+    let full_path = vault_path.join(doc_path);
+
+    let (hash, content) = resolve_version(vault_path, doc_path, target)?;
+    let from_ledger = target.starts_with("led_").then_some(target);
+
+    let prior_content = if full_path.exists() {
+        Some(fs::read_to_string(&full_path).with_context(|| format!("read {}", full_path.display()))?)
+    } else {
+        None
+    };
+    if prior_content.as_deref().map(hash_str).as_deref() == Some(hash.as_str()) {
+        return Err(anyhow!("{doc_path} is already at version {}", &hash[..12]));
+    }
+    if let Some(prior) = &prior_content {
+        store_object(vault_path, prior)?;
+    }
+    let doc_id = parse_markdown(&content).map(|(fm, _)| fm.id).unwrap_or_default();
+    fs::create_dir_all(full_path.parent().unwrap_or(Path::new(".")))?;
+    fs::write(&full_path, content.as_bytes())
+        .with_context(|| format!("write {}", full_path.display()))?;
+
+    let change_summary = match &from_ledger {
+        Some(ledger_id) => format!("Reverted to {} (after {ledger_id})", &hash[..12]),
+        None => format!("Reverted to {}", &hash[..12]),
+    };
+    let ledger_entry = LedgerEntry::from_change(
+        author,
+        reason,
+        None,
+        "revert",
+        &KnowledgePatch { doc_path: doc_path.to_string(), ..Default::default() },
+        prior_content.as_deref(),
+        &content,
+        &doc_id,
+        doc_path,
+        &change_summary,
+    );
+
+    Ok(ApplyResult { doc_path: full_path, ledger_entry })
+}
+
+/// A stored version of `doc_path`: the content written by ledger entry `spec`
+/// (a `led_` id), or the object whose hash starts with `spec`. A hash must be
+/// one the ledger recorded for `doc_path`, so another doc's content can't be
+/// written over it.
+pub fn resolve_version(vault_path: &Path, doc_path: &str, spec: &str) -> Result<(String, String)> {
+    if !spec.starts_with("led_") {
+        let (hash, content) = read_object(vault_path, spec)?;
+        let recorded = doc_history(vault_path, doc_path)?
+            .iter()
+            .any(|entry| entry.new_hash == hash || entry.prev_hash.as_deref() == Some(hash.as_str()));
+        if !recorded {
+            return Err(anyhow!("{spec} is not a recorded version of {doc_path}"));
+        }
+        return Ok((hash, content));
+    }
+    let entry = read_ledger(&vault_path.join("audit/ledger.jsonl"))?
+        .into_iter()
+        .find(|entry| entry.ledger_id == spec)
+        .ok_or_else(|| anyhow!("no ledger entry {spec}"))?;
+    if entry.doc_path != doc_path {
+        return Err(anyhow!("ledger entry {spec} changed {}, not {doc_path}", entry.doc_path));
+    }
+    read_object(vault_path, &entry.new_hash)
+        .map_err(|e| anyhow!("{e:#}; the version written by {spec} predates the object store"))
+}
+
+/// Unified diff of the change a ledger entry made. None when either side
+/// predates the object store.
+pub fn entry_diff(vault_path: &Path, entry: &LedgerEntry) -> Option<String> {
+    let old = match &entry.prev_hash {
+        Some(hash) => read_object(vault_path, hash).ok()?.1,
+        None => String::new(),
+    };
+    let (_, new) = read_object(vault_path, &entry.new_hash).ok()?;
+    let old_label = match &entry.prev_hash {
+        Some(hash) => format!("{}@{}", entry.doc_path, &hash[..12]),
+        None => "/dev/null".to_string(),
+    };
+    let new_label = format!("{}@{}", entry.doc_path, &entry.new_hash[..12]);
+    Some(unified_diff(&old, &new, &old_label, &new_label, 3))
+}
+
+/// Unified diff between two versions of `doc_path`. `from` defaults to the
+/// version before the most recent ledger entry and `to` to the file on disk.
+pub fn diff_doc(vault_path: &Path, doc_path: &str, from: Option<&str>, to: Option<&str>) -> Result<String> {
+    confine(vault_path, Path::new(doc_path), "knowledge")?;
+    let (old_label, old) = match from {
+        Some(spec) => {
+            let (hash, content) = resolve_version(vault_path, doc_path, spec)?;
+            (format!("{doc_path}@{}", &hash[..12]), content)
+        }
+        None => {
+            let last = doc_history(vault_path, doc_path)?
+                .pop()
+                .ok_or_else(|| anyhow!("no ledger history for {doc_path}"))?;
+            match last.prev_hash {
+                Some(hash) => {
+                    let (hash, content) = read_object(vault_path, &hash).map_err(|e| {
+                        anyhow!("{e:#}; the version before {} predates the object store", last.ledger_id)
+                    })?;
+                    (format!("{doc_path}@{}", &hash[..12]), content)
+                }
+                None => ("/dev/null".to_string(), String::new()),
+            }
+        }
+    };
+    let (new_label, new) = match to {
+        Some(spec) => {
+            let (hash, content) = resolve_version(vault_path, doc_path, spec)?;
+            (format!("{doc_path}@{}", &hash[..12]), content)
+        }
+        None => {
+            let full_path = vault_path.join(doc_path);
+            let content = fs::read_to_string(&full_path).unwrap_or_default();
+            (format!("{doc_path}@current"), content)
+        }
+    };
+    Ok(unified_diff(&old, &new, &old_label, &new_label, 3))
+}
+
 pub fn read_doc(path: &Path) -> Result<KnowledgeDoc> {
     let content = fs::read_to_string(path)
         .with_context(|| format!("read {}", path.display()))?;
@@ -243,3 +380,386 @@ fn render_markdown(front_matter: &FrontMatter, body: &str) -> Result<String> {
     out.push_str(body);
     Ok(out)
 }
+
+/// Front matter field to order `list_docs` results by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum DocSort {
+    Updated,
+    Created,
+    Title,
+    Confidence,
+}
+
+impl DocSort {
+    pub fn parse(value: &str) -> Result<Self> {
+        <Self as clap::ValueEnum>::from_str(value, true)
+            .map_err(|_| anyhow!("unknown sort: {value} (expected updated, created, title or confidence)"))
+    }
+}
+
+/// Filters for `list_docs`. Unset fields match every doc; `tags` must all be present.
+#[derive(Debug, Clone)]
+pub struct DocQuery {
+    pub doc_type: Option<String>,
+    pub tags: Vec<String>,
+    pub status: Option<String>,
+    pub min_confidence: Option<f64>,
+    pub max_confidence: Option<f64>,
+    /// Inclusive bounds on `updated_at`.
+    pub updated_since: Option<DateTime<Utc>>,
+    pub updated_until: Option<DateTime<Utc>>,
+    pub sort: DocSort,
+    /// None: newest, most confident and A-Z first.
+    pub ascending: Option<bool>,
+    pub offset: usize,
+    pub limit: usize,
+}
+
+impl Default for DocQuery {
+    fn default() -> Self {
+        Self {
+            doc_type: None,
+            tags: Vec::new(),
+            status: None,
+            min_confidence: None,
+            max_confidence: None,
+            updated_since: None,
+            updated_until: None,
+            sort: DocSort::Updated,
+            ascending: None,
+            offset: 0,
+            limit: 20,
+        }
+    }
+}
+
+/// The front matter fields worth showing in a listing.
+#[derive(Debug, Clone, Serialize)]
+pub struct DocListing {
+    pub doc_path: String,
+    pub id: String,
+    pub title: String,
+    #[serde(rename = "type")]
+    pub doc_type: String,
+    pub status: String,
+    pub tags: Vec<String>,
+    pub confidence: f64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub summary: String,
+}
+
+/// One page of `list_docs` results; `total` counts every match.
+#[derive(Debug, Clone, Serialize)]
+pub struct DocPage {
+    pub total: usize,
+    pub offset: usize,
+    pub next_offset: Option<usize>,
+    pub docs: Vec<DocListing>,
+}
+
+/// Knowledge docs whose front matter matches `query`, sorted and paginated.
+/// Docs without readable front matter are skipped.
+pub fn list_docs(vault: &Path, query: &DocQuery) -> Result<DocPage> {
+    // Tags are stored without the leading '#' people tend to type
+    let wanted_tags: Vec<&str> = query.tags.iter().map(|t| t.trim_start_matches('#')).collect();
+    let mut matches = Vec::new();
+    for path in crate::tools::knowledge::walk_markdown(&vault.join("knowledge"))? {
+        let Ok(doc) = read_doc(&path) else {
+            continue;
+        };
+        let fm = doc.front_matter;
+        let keep = query.doc_type.as_ref().is_none_or(|t| fm.doc_type.eq_ignore_ascii_case(t))
+            && query.status.as_ref().is_none_or(|s| fm.status.eq_ignore_ascii_case(s))
+            && wanted_tags
+                .iter()
+                .all(|want| fm.tags.iter().any(|tag| tag.trim_start_matches('#').eq_ignore_ascii_case(want)))
+            && query.min_confidence.is_none_or(|min| fm.confidence >= min)
+            && query.max_confidence.is_none_or(|max| fm.confidence <= max)
+            && query.updated_since.is_none_or(|since| fm.updated_at >= since)
+            && query.updated_until.is_none_or(|until| fm.updated_at <= until);
+        if keep {
+            let rel = path.strip_prefix(vault).unwrap_or(&path);
+            matches.push(DocListing {
+                doc_path: rel.to_string_lossy().to_string(),
+                id: fm.id,
+                title: fm.title,
+                doc_type: fm.doc_type,
+                status: fm.status,
+                tags: fm.tags,
+                confidence: fm.confidence,
+                created_at: fm.created_at,
+                updated_at: fm.updated_at,
+                summary: fm.summary,
+            });
+        }
+    }
+
+    matches.sort_by(|a, b| {
+        match query.sort {
+            DocSort::Updated => a.updated_at.cmp(&b.updated_at),
+            DocSort::Created => a.created_at.cmp(&b.created_at),
+            DocSort::Title => a.title.to_lowercase().cmp(&b.title.to_lowercase()),
+            DocSort::Confidence => a.confidence.total_cmp(&b.confidence),
+        }
+    });
+    if !query.ascending.unwrap_or(query.sort == DocSort::Title) {
+        matches.reverse();
+    }
+
+    let total = matches.len();
+    let docs: Vec<DocListing> = matches.into_iter().skip(query.offset).take(query.limit).collect();
+    let end = query.offset + docs.len();
+    Ok(DocPage {
+        total,
+        offset: query.offset,
+        next_offset: (end < total).then_some(end),
+        docs,
+    })
+}
+
+/// Parse a `list_docs` date bound: RFC 3339, or YYYY-MM-DD meaning the start
+/// of that day (or its end, for an upper bound).
+pub fn parse_date_bound(value: &str, end_of_day: bool) -> Result<DateTime<Utc>> {
+    if let Ok(ts) = DateTime::parse_from_rfc3339(value) {
+        return Ok(ts.with_timezone(&Utc));
+    }
+    let date = chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| anyhow!("invalid date: {value} (expected YYYY-MM-DD)"))?;
+    let time = if end_of_day {
+        chrono::NaiveTime::from_hms_opt(23, 59, 59).expect("valid time")
+    } else {
+        chrono::NaiveTime::MIN
+    };
+    Ok(date.and_time(time).and_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh vault holding the given `(doc_path, front matter)` docs.
+    fn vault_with(name: &str, docs: &[(&str, &str)]) -> PathBuf {
+        let vault = std::env::temp_dir().join(format!("j-knowledge-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&vault);
+        for (doc_path, front_matter) in docs {
+            let path = vault.join(doc_path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, format!("---\n{front_matter}sources: []\nsupersedes: []\n---\nBody.\n")).unwrap();
+        }
+        vault
+    }
+
+    fn front_matter(title: &str, doc_type: &str, tags: &str, confidence: f64, updated: &str) -> String {
+        format!(
+            "id: mem_{title}\ntitle: {title}\ntype: {doc_type}\nstatus: active\ntags: {tags}\n\
+             confidence: {confidence}\ncreated_at: 2026-01-01T00:00:00Z\nupdated_at: {updated}\n"
+        )
+    }
+
+    fn sample_vault(name: &str) -> PathBuf {
+        vault_with(
+            name,
+            &[
+                ("knowledge/people/ada.md", &front_matter("Ada", "person", "[math, '#history']", 0.9, "2026-03-01T10:00:00Z")),
+                ("knowledge/people/alan.md", &front_matter("alan", "person", "[math]", 0.6, "2026-03-05T10:00:00Z")),
+                ("knowledge/projects/engine.md", &front_matter("Engine", "project", "[history]", 0.3, "2026-02-01T10:00:00Z")),
+                ("knowledge/notes/broken.md", "not: [valid\n"),
+            ],
+        )
+    }
+
+    fn titles(page: &DocPage) -> Vec<&str> {
+        page.docs.iter().map(|d| d.title.as_str()).collect()
+    }
+
+    #[test]
+    fn lists_newest_first_and_skips_unreadable_docs() {
+        let vault = sample_vault("default");
+        let page = list_docs(&vault, &DocQuery::default()).unwrap();
+        assert_eq!(page.total, 3);
+        assert_eq!(titles(&page), ["alan", "Ada", "Engine"]);
+        assert_eq!(page.docs[1].doc_path, "knowledge/people/ada.md");
+        let _ = fs::remove_dir_all(&vault);
+    }
+
+    #[test]
+    fn filters_combine() {
+        let vault = sample_vault("filters");
+        let query = DocQuery {
+            doc_type: Some("Person".into()),
+            tags: vec!["#MATH".into()],
+            min_confidence: Some(0.7),
+            ..Default::default()
+        };
+        assert_eq!(titles(&list_docs(&vault, &query).unwrap()), ["Ada"]);
+
+        // Tags match with or without the stored '#'
+        let query = DocQuery { tags: vec!["history".into()], ..Default::default() };
+        assert_eq!(titles(&list_docs(&vault, &query).unwrap()), ["Ada", "Engine"]);
+        let _ = fs::remove_dir_all(&vault);
+    }
+
+    #[test]
+    fn date_bounds_are_inclusive() {
+        let vault = sample_vault("dates");
+        let query = DocQuery {
+            updated_since: Some(parse_date_bound("2026-02-01", false).unwrap()),
+            updated_until: Some(parse_date_bound("2026-03-01", true).unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(titles(&list_docs(&vault, &query).unwrap()), ["Ada", "Engine"]);
+        let _ = fs::remove_dir_all(&vault);
+    }
+
+    #[test]
+    fn sort_direction_defaults_per_key() {
+        let vault = sample_vault("sort");
+        let by_title = DocQuery { sort: DocSort::Title, ..Default::default() };
+        assert_eq!(titles(&list_docs(&vault, &by_title).unwrap()), ["Ada", "alan", "Engine"]);
+
+        let by_confidence = DocQuery { sort: DocSort::Confidence, ascending: Some(true), ..Default::default() };
+        assert_eq!(titles(&list_docs(&vault, &by_confidence).unwrap()), ["Engine", "alan", "Ada"]);
+        let _ = fs::remove_dir_all(&vault);
+    }
+
+    #[test]
+    fn pages_report_next_offset() {
+        let vault = sample_vault("paging");
+        let first = list_docs(&vault, &DocQuery { limit: 2, ..Default::default() }).unwrap();
+        assert_eq!((first.total, first.next_offset), (3, Some(2)));
+        assert_eq!(titles(&first), ["alan", "Ada"]);
+
+        let last = list_docs(&vault, &DocQuery { offset: 2, limit: 2, ..Default::default() }).unwrap();
+        assert_eq!(last.next_offset, None);
+        assert_eq!(titles(&last), ["Engine"]);
+
+        let past_end = list_docs(&vault, &DocQuery { offset: 10, ..Default::default() }).unwrap();
+        assert!(past_end.docs.is_empty());
+        assert_eq!(past_end.next_offset, None);
+        let _ = fs::remove_dir_all(&vault);
+    }
+
+    #[test]
+    fn missing_knowledge_dir_is_an_empty_page() {
+        let vault = vault_with("empty", &[]);
+        let page = list_docs(&vault, &DocQuery::default()).unwrap();
+        assert_eq!(page.total, 0);
+    }
+
+    #[test]
+    fn date_bound_accepts_days_and_timestamps() {
+        assert_eq!(parse_date_bound("2026-03-01", false).unwrap().to_rfc3339(), "2026-03-01T00:00:00+00:00");
+        assert_eq!(parse_date_bound("2026-03-01", true).unwrap().to_rfc3339(), "2026-03-01T23:59:59+00:00");
+        assert_eq!(
+            parse_date_bound("2026-03-01T12:00:00+02:00", true).unwrap().to_rfc3339(),
+            "2026-03-01T10:00:00+00:00"
+        );
+        let err = parse_date_bound("March 1st", false).unwrap_err();
+        assert_eq!(err.to_string(), "invalid date: March 1st (expected YYYY-MM-DD)");
+    }
+
+    /// Apply `patch` and record it, as the knowledge tools do.
+    fn apply(vault: &Path, patch: KnowledgePatch) -> LedgerEntry {
+        let result = apply_patch(vault, patch, "test", "test", None, "change").unwrap();
+        crate::audit::append_ledger(&vault.join("audit/ledger.jsonl"), &result.ledger_entry).unwrap();
+        result.ledger_entry
+    }
+
+    fn note(doc_path: &str, title: Option<&str>, body: &str) -> KnowledgePatch {
+        KnowledgePatch {
+            doc_path: doc_path.into(),
+            title: title.map(String::from),
+            doc_type: title.map(|_| "note".into()),
+            body_append: Some(body.into()),
+            ..Default::default()
+        }
+    }
+
+    /// A vault where `knowledge/notes/a.md` went through two versions and
+    /// `knowledge/notes/b.md` through one.
+    fn history_vault(name: &str) -> (PathBuf, LedgerEntry, LedgerEntry, LedgerEntry) {
+        let vault = vault_with(name, &[]);
+        fs::create_dir_all(vault.join("audit")).unwrap();
+        let first = apply(&vault, note("knowledge/notes/a.md", Some("A"), "one"));
+        let second = apply(&vault, note("knowledge/notes/a.md", None, "two"));
+        let other = apply(&vault, note("knowledge/notes/b.md", Some("B"), "secret"));
+        (vault, first, second, other)
+    }
+
+    fn revert_err(vault: &Path, doc_path: &str, target: &str) -> String {
+        match revert_doc(vault, doc_path, target, "test", "test") {
+            Ok(result) => panic!("reverted {doc_path} to {target}: {}", result.ledger_entry.new_hash),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn revert_by_ledger_id_or_hash_restores_content() {
+        let (vault, first, second, _) = history_vault("revert");
+        let path = vault.join("knowledge/notes/a.md");
+        let first_content = read_object(&vault, &first.new_hash).unwrap().1;
+
+        let result = revert_doc(&vault, "knowledge/notes/a.md", &first.ledger_id, "test", "undo").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), first_content);
+        assert_eq!(result.ledger_entry.op, "revert");
+        assert_eq!(result.ledger_entry.prev_hash.as_deref(), Some(second.new_hash.as_str()));
+        crate::audit::append_ledger(&vault.join("audit/ledger.jsonl"), &result.ledger_entry).unwrap();
+
+        // Back again by hash prefix; the revert itself is now history
+        revert_doc(&vault, "knowledge/notes/a.md", &second.new_hash[..10], "test", "redo").unwrap();
+        assert_eq!(hash_str(&fs::read_to_string(&path).unwrap()), second.new_hash);
+
+        let err = revert_err(&vault, "knowledge/notes/a.md", &second.new_hash);
+        assert!(err.contains("is already at version"), "{err}");
+        let _ = fs::remove_dir_all(&vault);
+    }
+
+    #[test]
+    fn revert_refuses_another_docs_versions() {
+        let (vault, _, _, other) = history_vault("provenance");
+        let err = revert_err(&vault, "knowledge/notes/a.md", &other.new_hash);
+        assert!(err.contains("is not a recorded version of knowledge/notes/a.md"), "{err}");
+        let err = revert_err(&vault, "knowledge/notes/a.md", &other.ledger_id);
+        assert!(err.contains("changed knowledge/notes/b.md"), "{err}");
+        assert!(!fs::read_to_string(vault.join("knowledge/notes/a.md")).unwrap().contains("secret"));
+        let _ = fs::remove_dir_all(&vault);
+    }
+
+    #[test]
+    fn revert_refuses_paths_outside_knowledge() {
+        let (vault, first, _, _) = history_vault("traversal");
+        for doc_path in ["../outside.md", "knowledge/../../outside.md", "/tmp/outside.md", "notes/a.md", "audit/ledger.jsonl"] {
+            let err = revert_err(&vault, doc_path, &first.new_hash);
+            assert!(err.ends_with("is not under knowledge/"), "{doc_path}: {err}");
+        }
+        assert!(diff_doc(&vault, "knowledge/../audit/ledger.jsonl", None, None).is_err());
+        let _ = fs::remove_dir_all(&vault);
+    }
+
+    #[test]
+    fn entry_diff_shows_the_change_an_entry_made() {
+        let (vault, first, second, _) = history_vault("entry-diff");
+        let created = entry_diff(&vault, &first).unwrap();
+        assert!(created.starts_with("--- /dev/null\n+++ knowledge/notes/a.md@"), "{created}");
+        assert!(created.contains("\n+one\n"), "{created}");
+
+        let changed = entry_diff(&vault, &second).unwrap();
+        let labels = format!(
+            "--- knowledge/notes/a.md@{}\n+++ knowledge/notes/a.md@{}\n",
+            &first.new_hash[..12],
+            &second.new_hash[..12]
+        );
+        assert!(changed.starts_with(&labels), "{changed}");
+        assert!(changed.contains("\n one\n+two\n"), "{changed}");
+        let _ = fs::remove_dir_all(&vault);
+    }
+
+    #[test]
+    fn entry_diff_is_none_without_stored_objects() {
+        let (vault, _, second, _) = history_vault("entry-diff-missing");
+        fs::remove_dir_all(vault.join("audit/objects")).unwrap();
+        assert!(entry_diff(&vault, &second).is_none());
+        let _ = fs::remove_dir_all(&vault);
+    }
+}
@@ -0,0 +1,95 @@
+use anyhow::{anyhow, Context, Result};
+use reqwest::Client;
+use serde_json::Value;
+use std::time::Duration;
+use tokio::net::TcpStream;
+
+/// Quick reachability check for a local server (no HTTP round-trip).
+pub async fn is_reachable(base_url: &str) -> bool {
+    let host_port = match host_port(base_url) {
+        Some(hp) => hp,
+        None => return false,
+    };
+    let addrs = match tokio::net::lookup_host(host_port).await {
+        Ok(addrs) => addrs,
+        Err(_) => return false,
+    };
+    for addr in addrs {
+        let connect = tokio::time::timeout(Duration::from_millis(300), TcpStream::connect(addr));
+        if matches!(connect.await, Ok(Ok(_))) {
+            return true;
+        }
+    }
+    false
+}
+
+/// List model ids served by a local OpenAI-compatible server.
+/// Tries `/v1/models` first, then Ollama's native `/api/tags`.
+pub async fn discover_models(base_url: &str, api_key: &str) -> Result<Vec<String>> {
+    let http = Client::builder()
+        .timeout(Duration::from_secs(5))
+        .build()
+        .context("build http client")?;
+    let base = base_url.trim_end_matches('/');
+
+    let mut req = http.get(format!("{base}/v1/models"));
+    if !api_key.is_empty() {
+        req = req.bearer_auth(api_key);
+    }
+    if let Ok(resp) = req.send().await.and_then(|r| r.error_for_status()) {
+        let body: Value = resp.json().await.context("parse model list")?;
+        let ids: Vec<String> = body
+            .get("data")
+            .and_then(|d| d.as_array())
+            .map(|items| {
+                items
+                    .iter()
+                    .filter_map(|m| m.get("id").and_then(|v| v.as_str()))
+                    .map(|s| s.to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+        return Ok(ids);
+    }
+
+    let body: Value = http
+        .get(format!("{base}/api/tags"))
+        .send()
+        .await
+        .context("list local models")?
+        .error_for_status()
+        .context("list local models status")?
+        .json()
+        .await
+        .context("parse ollama tags")?;
+    body.get("models")
+        .and_then(|m| m.as_array())
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|m| m.get("name").and_then(|v| v.as_str()))
+                .map(|s| s.to_string())
+                .collect()
+        })
+        .ok_or_else(|| anyhow!("unexpected model list response from {base}"))
+}
+
+/// Extract "host:port" from an http(s) URL, applying the scheme's default port.
+fn host_port(base_url: &str) -> Option<String> {
+    let (scheme, rest) = base_url.split_once("://")?;
+    let authority = rest.split('/').next()?;
+    let authority = authority.rsplit('@').next()?;
+    if authority.is_empty() {
+        return None;
+    }
+    let has_port = match authority.rfind(']') {
+        // IPv6 literal: port follows the closing bracket
+        Some(idx) => authority[idx..].contains(':'),
+        None => authority.contains(':'),
+    };
+    if has_port {
+        return Some(authority.to_string());
+    }
+    let port = if scheme == "https" { 443 } else { 80 };
+    Some(format!("{authority}:{port}"))
+}
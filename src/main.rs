@@ -1,17 +1,30 @@
 mod agent;
 mod anthropic;
 mod audit;
+mod config;
 mod embedding_index;
 mod embeddings;
 mod engine;
+mod fallback;
 mod gateway;
 mod gemini_chat;
 mod git_utils;
 mod ingest;
 mod knowledge;
+mod local;
+mod mcp_server;
+mod media;
 mod openai;
 mod chat;
+mod context;
+mod diff;
+mod replay;
+mod retry;
+mod schema;
+mod sse;
 mod thread_store;
+mod tools;
+mod usage;
 mod vault;
 
 use anyhow::{Context, Result};
@@ -20,10 +33,13 @@ use serde_json::Value;
 use std::fs;
 use std::path::PathBuf;
 
-use crate::audit::append_ledger;
+use crate::audit::{append_ledger, doc_history};
 use crate::git_utils::git_commit;
 use crate::ingest::{run_ingest, IngestOptions};
-use crate::knowledge::{apply_patch, KnowledgePatch};
+use crate::knowledge::{
+    apply_patch, diff_doc, entry_diff, list_docs, parse_date_bound, revert_doc, DocQuery, DocSort,
+    KnowledgePatch,
+};
 use crate::chat::{run_chat, ChatOptions};
 use crate::thread_store::{append_event, build_event, create_thread, list_threads, read_thread, EventType, Role};
 use crate::vault::{init_vault, resolve_vault};
@@ -73,16 +89,16 @@ enum Commands {
         /// Override the LLM model
         #[arg(long)]
         model: Option<String>,
-        /// Override the LLM engine (openai, anthropic, gemini)
+        /// Override the LLM engine (openai, anthropic, gemini, local, replay)
         #[arg(long)]
         engine: Option<String>,
         /// Allow the agent to commit changes to git
         #[arg(long, default_value_t = false)]
         allow_commit: bool,
-        /// Number of thread history events to load
-        #[arg(long, default_value_t = 50)]
-        history: usize,
-        /// Skip daemon and run agent loop directly (sync, in-process)
+        /// Max thread history events to load (default: whatever fits the model's context window; 0 = none)
+        #[arg(long)]
+        history: Option<usize>,
+        /// Skip daemon and run agent loop directly (in-process)
         #[arg(long, default_value_t = false)]
         direct: bool,
     },
@@ -103,6 +119,21 @@ enum Commands {
         #[command(subcommand)]
         command: GatewayCommand,
     },
+    /// Report LLM token usage and cost by day and model
+    Usage {
+        /// Vault path (default: j_vault)
+        #[arg(long)]
+        vault: Option<PathBuf>,
+        /// Only count the last N days (including today)
+        #[arg(long, conflicts_with = "since")]
+        days: Option<u32>,
+        /// Only count events on or after this date (YYYY-MM-DD)
+        #[arg(long)]
+        since: Option<String>,
+        /// Print the report as JSON
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
     /// Ingest a markdown document into the vault as a source
     Ingest {
         /// Path to the markdown file to ingest
@@ -123,6 +154,39 @@ enum Commands {
         #[arg(long)]
         model: Option<String>,
     },
+    /// Inspect the runtime configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Model Context Protocol integration
+    Mcp {
+        #[command(subcommand)]
+        command: McpCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum McpCommand {
+    /// Serve the vault's knowledge tools and docs to MCP clients over stdio
+    Serve {
+        /// Vault path (default: j_vault)
+        #[arg(long)]
+        vault: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Print every effective setting and where its value came from
+    Show {
+        /// Vault path (default: j_vault)
+        #[arg(long)]
+        vault: Option<PathBuf>,
+        /// Print as JSON
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
 }
 
 #[derive(Subcommand)]
@@ -228,15 +292,33 @@ enum GatewayCommand {
         #[arg(long, default_value_t = 50)]
         limit: usize,
     },
+    /// Show token usage and cost for a session
+    Usage {
+        /// Session key (default: "main")
+        #[arg(default_value = "main")]
+        session_key: String,
+    },
+    /// Cancel the run in progress on a session
+    Cancel {
+        /// Session key (default: "main")
+        #[arg(default_value = "main")]
+        session_key: String,
+    },
     /// Send a message to a session
     Send {
         /// Session key
         session_key: String,
         /// Message content
         message: String,
+        /// Attach an image file (png, jpg, gif, webp); repeatable
+        #[arg(long = "image")]
+        images: Vec<PathBuf>,
         /// Block until agent responds (optional timeout in seconds, default 120)
         #[arg(long)]
         wait: Option<Option<u64>>,
+        /// If the session is busy, cancel its run instead of queueing behind it
+        #[arg(long)]
+        interrupt: bool,
     },
 }
 
@@ -263,6 +345,102 @@ enum KnowledgeCommand {
         #[arg(long, default_value_t = false)]
         commit: bool,
     },
+    /// Restore a doc to an earlier version and record a revert in the ledger
+    Revert {
+        /// Doc path relative to the vault, e.g. knowledge/projects/foo.md
+        doc: String,
+        /// Ledger id (restore the version it wrote) or version hash prefix
+        #[arg(long)]
+        to: String,
+        /// Vault path (default: j_vault)
+        #[arg(long)]
+        vault: Option<PathBuf>,
+        /// Author attribution for the change
+        #[arg(long, default_value = "user")]
+        author: String,
+        /// Human-readable reason for the change
+        #[arg(long, default_value = "revert")]
+        reason: String,
+        /// Commit the change to git after reverting
+        #[arg(long, default_value_t = false)]
+        commit: bool,
+    },
+    /// Show the ledger history of a doc, newest first
+    Log {
+        /// Doc path relative to the vault, or doc id
+        doc: String,
+        /// Vault path (default: j_vault)
+        #[arg(long)]
+        vault: Option<PathBuf>,
+        /// Show the diff each change made
+        #[arg(long, short = 'p', default_value_t = false)]
+        patch: bool,
+        /// Most recent changes to show
+        #[arg(long, short = 'n')]
+        limit: Option<usize>,
+        /// Print the entries as JSON
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// Unified diff between two versions of a doc
+    Diff {
+        /// Doc path relative to the vault, or doc id
+        doc: String,
+        /// Older version: ledger id or hash prefix (default: before the latest change)
+        #[arg(long)]
+        from: Option<String>,
+        /// Newer version: ledger id or hash prefix (default: the current file)
+        #[arg(long)]
+        to: Option<String>,
+        /// Vault path (default: j_vault)
+        #[arg(long)]
+        vault: Option<PathBuf>,
+    },
+    /// List knowledge docs filtered by front matter
+    List {
+        /// Vault path (default: j_vault)
+        #[arg(long)]
+        vault: Option<PathBuf>,
+        /// Only docs of this type (person, project, pref, ...)
+        #[arg(long = "type")]
+        doc_type: Option<String>,
+        /// Only docs with this tag; repeatable, all must match
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        /// Only docs with this status
+        #[arg(long)]
+        status: Option<String>,
+        /// Minimum confidence (0-1)
+        #[arg(long)]
+        min_confidence: Option<f64>,
+        /// Maximum confidence (0-1)
+        #[arg(long)]
+        max_confidence: Option<f64>,
+        /// Only docs updated in the last N days (including today)
+        #[arg(long, conflicts_with = "since")]
+        days: Option<u32>,
+        /// Only docs updated on or after this date (YYYY-MM-DD)
+        #[arg(long)]
+        since: Option<String>,
+        /// Only docs updated on or before this date (YYYY-MM-DD)
+        #[arg(long)]
+        until: Option<String>,
+        /// Sort field
+        #[arg(long, value_enum, default_value_t = DocSort::Updated)]
+        sort: DocSort,
+        /// Sort ascending (default: newest, most confident and A-Z first)
+        #[arg(long, default_value_t = false)]
+        asc: bool,
+        /// Skip this many results
+        #[arg(long, default_value_t = 0)]
+        offset: usize,
+        /// Max results to show
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+        /// Print the page as JSON
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
 }
 
 #[tokio::main]
@@ -272,11 +450,10 @@ async fn main() -> Result<()> {
         Commands::Gateway { command } => {
             match command {
                 GatewayCommand::Start => {
+                    let vault = resolve_vault(std::env::var("J_VAULT").ok().map(PathBuf::from));
+                    let config = config::init(&vault, config::CliOverrides::default())?;
                     tracing_subscriber::fmt()
-                        .with_env_filter(
-                            tracing_subscriber::EnvFilter::try_from_default_env()
-                                .unwrap_or_else(|_| "info".into()),
-                        )
+                        .with_env_filter(tracing_subscriber::EnvFilter::new(&config.log_level.value))
                         .init();
                     gateway::run_daemon().await?;
                 }
@@ -303,8 +480,14 @@ async fn main() -> Result<()> {
                 GatewayCommand::History { session_key, limit } => {
                     gateway::handle_history(&session_key, limit).await?;
                 }
-                GatewayCommand::Send { session_key, message, wait } => {
-                    gateway::handle_send(&session_key, &message, wait).await?;
+                GatewayCommand::Usage { session_key } => {
+                    gateway::handle_usage(&session_key).await?;
+                }
+                GatewayCommand::Cancel { session_key } => {
+                    gateway::handle_cancel(&session_key).await?;
+                }
+                GatewayCommand::Send { session_key, message, images, wait, interrupt } => {
+                    gateway::handle_send(&session_key, &message, &images, wait, interrupt).await?;
                 }
             }
         }
@@ -417,13 +600,136 @@ async fn main() -> Result<()> {
                     git_commit(&repo_root, &[result.doc_path, ledger_path], &message)?;
                 }
             }
+            KnowledgeCommand::Revert {
+                doc,
+                to,
+                vault,
+                author,
+                reason,
+                commit,
+            } => {
+                let vault = resolve_vault(vault);
+                let result = revert_doc(&vault, &doc, &to, &author, &reason)?;
+                let ledger_path = vault.join("audit/ledger.jsonl");
+                append_ledger(&ledger_path, &result.ledger_entry)?;
+                println!(
+                    "{} ({})",
+                    result.ledger_entry.change_summary, result.ledger_entry.ledger_id
+                );
+                if commit {
+                    let repo_root = PathBuf::from(".");
+                    git_commit(&repo_root, &[result.doc_path, ledger_path], &format!("memory: {reason}"))?;
+                }
+            }
+            KnowledgeCommand::Log {
+                doc,
+                vault,
+                patch,
+                limit,
+                json,
+            } => {
+                let vault = resolve_vault(vault);
+                let history = doc_history(&vault, &doc)?;
+                let shown: Vec<_> = history.iter().rev().take(limit.unwrap_or(usize::MAX)).collect();
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&shown)?);
+                } else if shown.is_empty() {
+                    println!("No ledger history for {doc}.");
+                } else {
+                    for entry in shown {
+                        println!(
+                            "{}  {}  {}  {}  {}",
+                            entry.ledger_id,
+                            entry.ts.format("%Y-%m-%d %H:%M"),
+                            entry.author,
+                            entry.op,
+                            &entry.new_hash[..12]
+                        );
+                        if !entry.change_summary.is_empty() {
+                            println!("    {}", entry.change_summary);
+                        }
+                        println!("    reason: {}", entry.reason);
+                        if patch {
+                            match entry_diff(&vault, entry) {
+                                Some(diff) => println!("\n{diff}"),
+                                None => println!("    (versions not stored)\n"),
+                            }
+                        }
+                    }
+                }
+            }
+            KnowledgeCommand::Diff { doc, from, to, vault } => {
+                let vault = resolve_vault(vault);
+                let doc_path = doc_history(&vault, &doc)?
+                    .last()
+                    .map(|entry| entry.doc_path.clone())
+                    .unwrap_or(doc);
+                let diff = diff_doc(&vault, &doc_path, from.as_deref(), to.as_deref())?;
+                if diff.is_empty() {
+                    println!("No differences.");
+                } else {
+                    print!("{diff}");
+                }
+            }
+            KnowledgeCommand::List {
+                vault,
+                doc_type,
+                tags,
+                status,
+                min_confidence,
+                max_confidence,
+                days,
+                since,
+                until,
+                sort,
+                asc,
+                offset,
+                limit,
+                json,
+            } => {
+                let vault = resolve_vault(vault);
+                let updated_since = match (days, since) {
+                    (Some(days), _) => Some(
+                        (chrono::Utc::now().date_naive() - chrono::Days::new(days.saturating_sub(1) as u64))
+                            .and_time(chrono::NaiveTime::MIN)
+                            .and_utc(),
+                    ),
+                    (None, Some(value)) => Some(parse_date_bound(&value, false)?),
+                    (None, None) => None,
+                };
+                let query = DocQuery {
+                    doc_type,
+                    tags,
+                    status,
+                    min_confidence,
+                    max_confidence,
+                    updated_since,
+                    updated_until: until.map(|value| parse_date_bound(&value, true)).transpose()?,
+                    sort,
+                    ascending: asc.then_some(true),
+                    offset,
+                    limit,
+                };
+                let page = list_docs(&vault, &query)?;
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&page)?);
+                } else {
+                    print_doc_page(&page);
+                }
+            }
         },
         Commands::Index { vault } => {
             use crate::embedding_index::build_knowledge_index;
+            use crate::agent::CancelToken;
             use crate::embeddings::EmbeddingClient;
             let vault = resolve_vault(vault);
-            let client = EmbeddingClient::from_env()?;
-            let stats = build_knowledge_index(&vault, &client)?;
+            config::init(&vault, config::CliOverrides::default())?;
+            // The embedding client is blocking; keep it off the async runtime
+            let stats = tokio::task::spawn_blocking(move || {
+                let client = EmbeddingClient::from_env()?;
+                build_knowledge_index(&vault, &client, &CancelToken::default())
+            })
+            .await??;
             println!(
                 "Indexed {} docs / {} chunks ({} {})",
                 stats.doc_count, stats.chunk_count, stats.provider, stats.model
@@ -462,12 +768,13 @@ async fn main() -> Result<()> {
         Commands::BackfillSummaries { vault, model, dry_run } => {
             use crate::knowledge::read_doc;
 
-            dotenvy::dotenv().ok();
             let vault = resolve_vault(vault);
-            let mut client = crate::engine::create_engine()?;
-            if let Some(ref model_override) = model {
-                client.set_model(model_override.clone());
-            }
+            config::init(&vault, config::CliOverrides {
+                role: Some(config::Role::Summarize),
+                engine: None,
+                model,
+            })?;
+            let client = crate::engine::create_role_engine(config::Role::Summarize).await?;
 
             let root = vault.join("knowledge");
             let mut stack = vec![root.clone()];
@@ -491,18 +798,34 @@ async fn main() -> Result<()> {
 
             println!("Found {} docs without summaries.", docs_to_update.len());
 
+            #[derive(serde::Deserialize)]
+            struct DocSummary {
+                summary: String,
+            }
+            let summary_schema = crate::schema::JsonSchema::new(
+                "document_summary",
+                serde_json::json!({
+                    "type": "object",
+                    "properties": { "summary": { "type": "string", "minLength": 1 } },
+                    "required": ["summary"],
+                    "additionalProperties": false,
+                }),
+            );
+
             for path in &docs_to_update {
                 let doc = read_doc(path)?;
                 let body_excerpt: String = doc.body.chars().take(500).collect();
                 let prompt = format!(
-                    "Write a single-line summary (max 150 chars) describing this entire document. Be specific and concrete. Return ONLY the summary line, nothing else.\n\nTitle: {}\nType: {}\nContent:\n{}",
+                    "Write a single-line summary (max 150 chars) describing this entire document. Be specific and concrete.\n\nTitle: {}\nType: {}\nContent:\n{}",
                     doc.front_matter.title, doc.front_matter.doc_type, body_excerpt
                 );
                 let messages = vec![
                     serde_json::json!({"role": "user", "content": prompt}),
                 ];
-                let response = client.chat(&messages, &[])?;
-                let summary = response.content.unwrap_or_default().trim().to_string();
+                let response = client.chat_structured(&messages, &summary_schema).await?;
+                // No thread records this call, so log it for `j usage`
+                crate::usage::record_call(&vault, "backfill_summaries", client.model(), response.usage)?;
+                let summary = response.parse::<DocSummary>()?.summary.trim().to_string();
 
                 let rel = path.strip_prefix(&vault).unwrap_or(path).to_string_lossy();
                 if dry_run {
@@ -521,6 +844,24 @@ async fn main() -> Result<()> {
                 println!("\nDone. Review changes and commit when ready.");
             }
         }
+        Commands::Usage { vault, days, since, json } => {
+            let vault = resolve_vault(vault);
+            // Prices come from the vault's config
+            config::init(&vault, config::CliOverrides::default())?;
+            let since = match (days, since) {
+                (Some(days), _) => Some(
+                    chrono::Utc::now().date_naive() - chrono::Days::new(days.saturating_sub(1) as u64),
+                ),
+                (None, Some(value)) => Some(chrono::NaiveDate::parse_from_str(&value, "%Y-%m-%d")?),
+                (None, None) => None,
+            };
+            let report = crate::usage::vault_report(&vault, since)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                print_usage_report(&report);
+            }
+        }
         Commands::Ingest {
             file,
             vault,
@@ -529,6 +870,7 @@ async fn main() -> Result<()> {
             title,
             model,
         } => {
+            config::init(&resolve_vault(vault.clone()), config::CliOverrides::default())?;
             let result = run_ingest(IngestOptions {
                 vault,
                 file,
@@ -536,16 +878,99 @@ async fn main() -> Result<()> {
                 tags,
                 title,
                 model,
-            })?;
+                quiet: false,
+            })
+            .await?;
             println!("\nIngested: {}", result.source_path.display());
             println!("Summary:  {}", result.summary_path.display());
             println!("Thread:   {}", result.thread_path.display());
             println!("Proposals: {}", result.proposal_count);
         }
+        Commands::Config { command } => match command {
+            ConfigCommand::Show { vault, json } => {
+                let vault = resolve_vault(vault);
+                let config = config::load(&vault, config::CliOverrides::default())?;
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&config.to_json())?);
+                } else {
+                    let entries = config.entries();
+                    let key_width = entries.iter().map(|(k, _, _)| k.len()).max().unwrap_or(0);
+                    let value_width = entries.iter().map(|(_, v, _)| v.len()).max().unwrap_or(0);
+                    for (key, value, source) in entries {
+                        println!("{key:<key_width$}  {value:<value_width$}  ({source})");
+                    }
+                }
+            }
+        },
+        Commands::Mcp { command } => match command {
+            McpCommand::Serve { vault } => {
+                let vault = resolve_vault(vault);
+                config::init(&vault, config::CliOverrides::default())?;
+                mcp_server::serve(vault).await?;
+            }
+        },
     }
     Ok(())
 }
 
+fn print_doc_page(page: &crate::knowledge::DocPage) {
+    if page.docs.is_empty() {
+        println!("No matching docs.");
+        return;
+    }
+    for doc in &page.docs {
+        let tags = if doc.tags.is_empty() {
+            String::new()
+        } else {
+            format!("  [{}]", doc.tags.join(", "))
+        };
+        println!(
+            "{}  {:<8} {:<9} {:.2}  {}  {}{tags}",
+            doc.updated_at.format("%Y-%m-%d"),
+            doc.doc_type,
+            doc.status,
+            doc.confidence,
+            doc.doc_path,
+            doc.title
+        );
+    }
+    let shown = page.offset + page.docs.len();
+    match page.next_offset {
+        Some(next) => println!("{}-{shown} of {} (next: --offset {next})", page.offset + 1, page.total),
+        None => println!("{}-{shown} of {}", page.offset + 1, page.total),
+    }
+}
+
+fn print_usage_report(report: &crate::usage::UsageReport) {
+    if report.total.calls == 0 {
+        println!("No usage recorded.");
+        return;
+    }
+    let row = |label: &str, t: &crate::usage::UsageTotals| {
+        let unpriced = if t.unpriced_calls > 0 {
+            format!("  ({} unpriced)", t.unpriced_calls)
+        } else {
+            String::new()
+        };
+        let cached = t.cache_read_tokens + t.cache_write_tokens;
+        let cached = if cached > 0 { format!("  ({cached} cached in)") } else { String::new() };
+        println!(
+            "  {:<28} {:>6} calls {:>12} in {:>10} out  ${:>9.4}{cached}{unpriced}",
+            label, t.calls, t.input_tokens, t.output_tokens, t.cost_usd
+        );
+    };
+    println!("By day:");
+    for (day, totals) in &report.by_day {
+        row(&day.to_string(), totals);
+    }
+    println!("By model:");
+    for (model, totals) in &report.by_model {
+        row(model, totals);
+    }
+    println!("Total:");
+    row("all", &report.total);
+}
+
 fn parse_json(value: &str, label: &str) -> Result<Value> {
     serde_json::from_str(value).with_context(|| format!("parse {label} JSON"))
 }
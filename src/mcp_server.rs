@@ -0,0 +1,310 @@
+//! `j mcp serve`: the vault as a Model Context Protocol server over stdio.
+//!
+//! Tools are the built-in vault tools plus `ingest`; knowledge docs are
+//! resources at `j://knowledge/...`. Writes go through the same tools the
+//! agent uses, so every change lands via `apply_patch` and the ledger. Tool
+//! calls follow the vault's permission policy, with "ask" treated as deny.
+
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+use crate::agent::{authorize, AgentConfig, CancelToken};
+use crate::config::ToolPolicy;
+use crate::engine::ToolCall;
+use crate::ingest::{run_ingest, IngestOptions};
+use crate::knowledge::read_doc;
+use crate::thread_store::{create_thread, ThreadMeta};
+use crate::tools::knowledge::walk_markdown;
+use crate::tools::ToolRegistry;
+
+const PROTOCOL_VERSION: &str = "2025-03-26";
+
+/// Registry tools offered to MCP clients.
+const EXPOSED_TOOLS: &[&str] = &["knowledge_search", "knowledge_read", "knowledge_apply", "thread_read"];
+
+const RESOURCE_SCHEME: &str = "j://";
+
+struct Server {
+    vault: PathBuf,
+    config: AgentConfig,
+    /// `clientInfo.name` from initialize, used to attribute writes.
+    client_name: String,
+    /// Where permission decisions are logged; created at the first one.
+    decisions_thread: Option<PathBuf>,
+}
+
+/// Serve requests from stdin until it closes. Only protocol messages go to
+/// stdout; diagnostics go to stderr.
+pub async fn serve(vault: PathBuf) -> Result<()> {
+    if !vault.exists() {
+        return Err(anyhow!("vault does not exist: {}. Run `j vault init` first.", vault.display()));
+    }
+    let mut server = Server {
+        config: AgentConfig {
+            vault_path: vault.clone(),
+            // No current thread; thread_read must name one
+            thread_path: PathBuf::new(),
+            max_turns: 0,
+            allow_commit: false,
+            tools: Arc::new(ToolRegistry::builtin()),
+            tool_filter: Some(EXPOSED_TOOLS.iter().map(|s| s.to_string()).collect()),
+            event_sink: None,
+            approver: None,
+            cancel: CancelToken::default(),
+            deep_think_running: Arc::new(AtomicBool::new(false)),
+            engine_name: None,
+            model_name: None,
+        },
+        vault,
+        client_name: "unknown".to_string(),
+        decisions_thread: None,
+    };
+
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut stdout = tokio::io::stdout();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match serde_json::from_str::<Value>(&line) {
+            Ok(message) => server.handle(message).await,
+            Err(e) => Some(error_response(Value::Null, -32700, &format!("parse error: {e}"))),
+        };
+        if let Some(reply) = reply {
+            let mut out = serde_json::to_string(&reply)?;
+            out.push('\n');
+            stdout.write_all(out.as_bytes()).await?;
+            stdout.flush().await?;
+        }
+    }
+    Ok(())
+}
+
+impl Server {
+    /// Response to one message; None for notifications.
+    async fn handle(&mut self, message: Value) -> Option<Value> {
+        let id = message.get("id").cloned()?;
+        let method = message.get("method").and_then(|m| m.as_str()).unwrap_or("");
+        let params = message.get("params").cloned().unwrap_or_else(|| json!({}));
+        let result = match method {
+            "initialize" => Ok(self.initialize(&params)),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(self.list_tools()),
+            "tools/call" => Ok(self.call_tool(&params).await),
+            "resources/list" => self.list_resources(),
+            "resources/read" => self.read_resource(&params),
+            _ => return Some(error_response(id, -32601, &format!("method not found: {method}"))),
+        };
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(e) => error_response(id, -32602, &format!("{e:#}")),
+        })
+    }
+
+    fn initialize(&mut self, params: &Value) -> Value {
+        if let Some(name) = params.pointer("/clientInfo/name").and_then(|n| n.as_str()) {
+            self.client_name = name.to_string();
+        }
+        eprintln!("j mcp: client {} connected", self.client_name);
+        json!({
+            "protocolVersion": PROTOCOL_VERSION,
+            "capabilities": { "tools": {}, "resources": {} },
+            "serverInfo": { "name": "j", "version": env!("CARGO_PKG_VERSION") }
+        })
+    }
+
+    fn list_tools(&self) -> Value {
+        let mut tools: Vec<Value> = self
+            .config
+            .tools
+            .iter()
+            .filter(|tool| EXPOSED_TOOLS.contains(&tool.name()))
+            .map(|tool| {
+                json!({
+                    "name": tool.name(),
+                    "description": tool.description(),
+                    "inputSchema": tool.parameters(),
+                    "annotations": { "readOnlyHint": tool.read_only() }
+                })
+            })
+            .collect();
+        tools.push(json!({
+            "name": "ingest",
+            "description": "Ingest a markdown file into the vault as a source. An agent summarises it and proposes knowledge updates.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "file": { "type": "string", "description": "Path to the markdown file to ingest." },
+                    "title": { "type": "string", "description": "Document title (default: derived from the file name)." },
+                    "source": { "type": "string", "description": "Provenance, e.g. notion or chatgpt-export." },
+                    "tags": { "type": "array", "items": { "type": "string" } }
+                },
+                "required": ["file"]
+            },
+            "annotations": { "readOnlyHint": false }
+        }));
+        json!({ "tools": tools })
+    }
+
+    /// Tool failures are reported in the result with `isError`, per the spec,
+    /// so the calling model can see them.
+    async fn call_tool(&mut self, params: &Value) -> Value {
+        let name = params.get("name").and_then(|n| n.as_str()).unwrap_or("");
+        let args = params.get("arguments").cloned().unwrap_or_else(|| json!({}));
+        let outcome = match name {
+            "ingest" => self.ingest(&args).await,
+            _ => self.run_registry_tool(name, args).await,
+        };
+        match outcome {
+            Ok(data) => {
+                let mut result = json!({
+                    "content": [{ "type": "text", "text": data.to_string() }],
+                    "isError": false
+                });
+                if data.is_object() {
+                    result["structuredContent"] = data;
+                }
+                result
+            }
+            Err(e) => json!({
+                "content": [{ "type": "text", "text": format!("{e:#}") }],
+                "isError": true
+            }),
+        }
+    }
+
+    async fn run_registry_tool(&mut self, name: &str, mut args: Value) -> Result<Value> {
+        let tool = self
+            .config
+            .tools
+            .get(name, self.config.tool_filter.as_deref())
+            .cloned()
+            .ok_or_else(|| anyhow!("unknown tool: {name}"))?;
+        if name == "thread_read" && args.get("thread").is_none() {
+            return Err(anyhow!("thread required"));
+        }
+        // Ledger entries name the connected client unless the caller says otherwise
+        if name == "knowledge_apply"
+            && args.get("author").is_none()
+            && let Some(obj) = args.as_object_mut()
+        {
+            obj.insert("author".into(), json!(format!("mcp:{}", self.client_name)));
+        }
+        let violations = tool.validate(&args);
+        if !violations.is_empty() {
+            return Err(anyhow!("invalid arguments: {}", crate::schema::describe(&violations)));
+        }
+
+        // The same policy as in a run. Nobody can answer "ask" over stdio, so
+        // those calls are refused like any denial
+        let mut config = self.config.clone();
+        if crate::config::current().permissions.policy(name, tool.permission()) != ToolPolicy::Allow {
+            config.thread_path = self.decisions_thread()?;
+        }
+        let call = ToolCall { id: String::new(), name: name.to_string(), arguments: args };
+        if let Some(refusal) = authorize(&config, &call).await? {
+            return Err(anyhow!(refusal));
+        }
+        let args = call.arguments;
+        tokio::task::spawn_blocking(move || tool.execute(&args, &config))
+            .await
+            .map_err(|e| anyhow!("tool task panicked: {e}"))?
+    }
+
+    fn decisions_thread(&mut self) -> Result<PathBuf> {
+        if let Some(path) = &self.decisions_thread {
+            return Ok(path.clone());
+        }
+        let path = create_thread(
+            &self.vault,
+            None,
+            None,
+            Some(ThreadMeta {
+                kind: "mcp".into(),
+                agent: Some(format!("mcp:{}", self.client_name)),
+                model: None,
+                engine: None,
+                base_url: None,
+            }),
+        )?;
+        self.decisions_thread = Some(path.clone());
+        Ok(path)
+    }
+
+    async fn ingest(&self, args: &Value) -> Result<Value> {
+        let file = args
+            .get("file")
+            .and_then(|f| f.as_str())
+            .ok_or_else(|| anyhow!("file required"))?;
+        let text = |key: &str| args.get(key).and_then(|v| v.as_str()).map(str::to_string);
+        let tags = args
+            .get("tags")
+            .and_then(|t| t.as_array())
+            .map(|tags| tags.iter().filter_map(|t| t.as_str().map(str::to_string)).collect())
+            .unwrap_or_default();
+        let result = run_ingest(IngestOptions {
+            vault: Some(self.vault.clone()),
+            file: PathBuf::from(file),
+            source: text("source").or_else(|| Some(format!("mcp:{}", self.client_name))),
+            tags,
+            title: text("title"),
+            model: None,
+            quiet: true,
+        })
+        .await?;
+        Ok(json!({
+            "source_path": result.source_path,
+            "summary_path": result.summary_path,
+            "thread_path": result.thread_path,
+            "proposal_count": result.proposal_count
+        }))
+    }
+
+    fn list_resources(&self) -> Result<Value> {
+        let mut paths = walk_markdown(&self.vault.join("knowledge"))?;
+        paths.sort();
+        let resources: Vec<Value> = paths
+            .iter()
+            .filter_map(|path| {
+                let rel = path.strip_prefix(&self.vault).ok()?.to_string_lossy().to_string();
+                // Docs without front matter are still listed, named by path
+                let (name, description) = match read_doc(path) {
+                    Ok(doc) => (doc.front_matter.title, doc.front_matter.summary),
+                    Err(_) => (rel.clone(), String::new()),
+                };
+                Some(json!({
+                    "uri": format!("{RESOURCE_SCHEME}{rel}"),
+                    "name": name,
+                    "description": description,
+                    "mimeType": "text/markdown"
+                }))
+            })
+            .collect();
+        Ok(json!({ "resources": resources }))
+    }
+
+    fn read_resource(&self, params: &Value) -> Result<Value> {
+        let uri = params
+            .get("uri")
+            .and_then(|u| u.as_str())
+            .ok_or_else(|| anyhow!("uri required"))?;
+        let rel = uri
+            .strip_prefix(RESOURCE_SCHEME)
+            .ok_or_else(|| anyhow!("unknown resource: {uri}"))?;
+        let path = crate::vault::confine(&self.vault, Path::new(rel), "knowledge")
+            .map_err(|_| anyhow!("unknown resource: {uri}"))?;
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("read {uri}: {e}"))?;
+        Ok(json!({
+            "contents": [{ "uri": uri, "mimeType": "text/markdown", "text": text }]
+        }))
+    }
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
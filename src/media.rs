@@ -0,0 +1,133 @@
+use anyhow::{anyhow, Context, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
+use chrono::{Datelike, Utc};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use ulid::Ulid;
+
+/// Largest image sent to a provider (OpenAI's per-image limit).
+pub const MAX_IMAGE_BYTES: usize = 20 * 1024 * 1024;
+
+/// Image content travels through the agent in OpenAI's format:
+/// `{"type": "image_url", "image_url": {"url": "data:image/png;base64,..."}}`.
+/// Engines convert parts to their native blocks via `parse_part`.
+pub enum ContentPart<'a> {
+    Text(&'a str),
+    /// Base64 image data from a `data:` URL.
+    InlineImage { mime: &'a str, data: &'a str },
+    /// Remote image the provider fetches itself.
+    ImageUrl(&'a str),
+}
+
+pub fn parse_part(part: &Value) -> Option<ContentPart<'_>> {
+    match part.get("type")?.as_str()? {
+        "text" => Some(ContentPart::Text(part.get("text")?.as_str()?)),
+        "image_url" => {
+            let url = part.get("image_url")?.get("url")?.as_str()?;
+            match url.strip_prefix("data:").and_then(|rest| rest.split_once(";base64,")) {
+                Some((mime, data)) => Some(ContentPart::InlineImage { mime, data }),
+                None => Some(ContentPart::ImageUrl(url)),
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Image MIME type from the file extension, or None for non-images.
+pub fn image_mime(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    match ext.as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        _ => None,
+    }
+}
+
+/// Resolve a vault-relative path (e.g. "media/food/pizza.png"), refusing
+/// anything outside `media/`.
+pub fn resolve_media_path(vault_path: &Path, rel_path: &str) -> Result<PathBuf> {
+    if rel_path.contains("..") || rel_path.starts_with('/') || rel_path.contains('\\') {
+        return Err(anyhow!("invalid media path: {rel_path}"));
+    }
+    let media_dir = vault_path.join("media");
+    let full_path = vault_path.join(rel_path);
+    let canonical_file = full_path
+        .canonicalize()
+        .with_context(|| format!("media not found: {rel_path}"))?;
+    let canonical_media = media_dir.canonicalize().unwrap_or(media_dir);
+    if !canonical_file.starts_with(&canonical_media) {
+        return Err(anyhow!("path is outside media/: {rel_path}"));
+    }
+    Ok(canonical_file)
+}
+
+/// Load a vault media image as an inline image content part.
+pub fn image_part(vault_path: &Path, rel_path: &str) -> Result<Value> {
+    let path = resolve_media_path(vault_path, rel_path)?;
+    let mime = image_mime(&path).ok_or_else(|| anyhow!("not a supported image: {rel_path}"))?;
+    let bytes = fs::read(&path).with_context(|| format!("read {rel_path}"))?;
+    if bytes.len() > MAX_IMAGE_BYTES {
+        return Err(anyhow!("image too large ({} bytes): {rel_path}", bytes.len()));
+    }
+    Ok(json!({
+        "type": "image_url",
+        "image_url": { "url": format!("data:{mime};base64,{}", BASE64.encode(&bytes)) }
+    }))
+}
+
+/// Store an uploaded image under `media/attachments/YYYY/MM/DD/` and return
+/// its vault-relative path.
+pub fn save_attachment(vault_path: &Path, name: &str, data_base64: &str) -> Result<String> {
+    let name: String = name
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or(name)
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') { c } else { '_' })
+        .collect();
+    if image_mime(Path::new(&name)).is_none() {
+        return Err(anyhow!("unsupported attachment type: {name} (png, jpg, gif, webp)"));
+    }
+    let bytes = BASE64
+        .decode(data_base64.trim())
+        .with_context(|| format!("attachment {name} is not valid base64"))?;
+    if bytes.len() > MAX_IMAGE_BYTES {
+        return Err(anyhow!("attachment too large ({} bytes): {name}", bytes.len()));
+    }
+    let now = Utc::now();
+    let rel_path = format!(
+        "media/attachments/{:04}/{:02}/{:02}/{}-{name}",
+        now.year(),
+        now.month(),
+        now.day(),
+        Ulid::new()
+    );
+    let full_path = vault_path.join(&rel_path);
+    if let Some(parent) = full_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&full_path, &bytes).with_context(|| format!("write {rel_path}"))?;
+    Ok(rel_path)
+}
+
+/// Context message for an `attachment_added` event. Its content is
+/// `{"path": "media/...", "name": "..."}`; a file that has since been removed
+/// is replaced by a note so the conversation still makes sense.
+pub fn attachment_message(vault_path: &Path, content: &Value) -> Value {
+    let path = content.get("path").and_then(|v| v.as_str()).unwrap_or("");
+    let name = content.get("name").and_then(|v| v.as_str()).unwrap_or(path);
+    match image_part(vault_path, path) {
+        Ok(image) => json!({
+            "role": "user",
+            "content": [{ "type": "text", "text": format!("[attached image: {name}]") }, image]
+        }),
+        Err(e) => json!({
+            "role": "user",
+            "content": format!("[attached image unavailable: {name} ({e})]")
+        }),
+    }
+}
@@ -1,15 +1,21 @@
 use anyhow::{anyhow, Context, Result};
-use reqwest::blocking::Client;
+use async_trait::async_trait;
+use reqwest::Client;
 use serde_json::{json, Value};
 use std::time::Duration;
 
-use crate::engine::{ChatResponse, Engine, ToolCall};
+use crate::engine::{ChatResponse, DeltaSink, Engine, GenerationParams, ToolCall};
+use crate::retry::{self, RetryHook, RetryPolicy};
+use crate::schema::JsonSchema;
+use crate::sse;
 
 pub struct OpenAIClient {
     api_key: String,
     base_url: String,
     http: Client,
     model: String,
+    params: GenerationParams,
+    retry: RetryPolicy,
 }
 
 impl OpenAIClient {
@@ -23,31 +29,65 @@ impl OpenAIClient {
             base_url,
             http,
             model,
+            params: GenerationParams::default(),
+            retry: RetryPolicy::default(),
         }
     }
-}
 
-impl Engine for OpenAIClient {
-    fn chat(&self, messages: &[Value], tools: &[Value]) -> Result<ChatResponse> {
-        let url = format!("{}/v1/chat/completions", self.base_url.trim_end_matches('/'));
-        let body = json!({
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    fn request_body(&self, messages: &[Value], tools: &[Value]) -> Value {
+        let mut body = json!({
             "model": self.model,
             "messages": messages,
-            "tools": tools,
-            "tool_choice": "auto"
         });
+        // Many OpenAI-compatible servers reject an empty tools array
+        if !tools.is_empty() {
+            body["tools"] = Value::Array(tools.to_vec());
+            body["tool_choice"] = Value::String("auto".into());
+        }
+        if let Some(temperature) = self.params.temperature {
+            body["temperature"] = json!(temperature);
+        }
+        if let Some(max_tokens) = self.params.max_tokens {
+            body["max_completion_tokens"] = json!(max_tokens);
+        }
+        body
+    }
+
+    fn url(&self) -> String {
+        format!("{}/v1/chat/completions", self.base_url.trim_end_matches('/'))
+    }
+
+    /// POST the body with bearer auth; local servers may run without a key.
+    fn post(&self, body: &Value) -> reqwest::RequestBuilder {
+        let req = self.http.post(self.url()).json(body);
+        if self.api_key.is_empty() {
+            req
+        } else {
+            req.bearer_auth(&self.api_key)
+        }
+    }
 
+    /// Non-streaming completion: send `body`, parse the first choice.
+    async fn complete(&self, body: &Value) -> Result<ChatResponse> {
         let resp: Value = self
-            .http
-            .post(url)
-            .bearer_auth(&self.api_key)
-            .json(&body)
-            .send()
-            .context("send chat completion")?
-            .error_for_status()
-            .context("chat completion status")?
-            .json()
-            .context("parse chat completion response")?;
+            .retry
+            .run_async("chat completion", || {
+                let request = self.post(body);
+                async move {
+                    retry::send_async(request)
+                        .await
+                        .context("send chat completion")?
+                        .json()
+                        .await
+                        .context("parse chat completion response")
+                }
+            })
+            .await?;
 
         let message = resp
             .get("choices")
@@ -61,8 +101,58 @@ impl Engine for OpenAIClient {
             .map(|s| s.to_string());
 
         let tool_calls = parse_tool_calls(message)?;
+        let usage = crate::usage::from_openai(&resp);
+
+        Ok(ChatResponse { content, tool_calls, usage })
+    }
+}
+
+#[async_trait]
+impl Engine for OpenAIClient {
+    async fn chat(&self, messages: &[Value], tools: &[Value]) -> Result<ChatResponse> {
+        let body = self.request_body(messages, tools);
+        self.complete(&body).await
+    }
+
+    async fn chat_json(&self, messages: &[Value], schema: &JsonSchema) -> Result<ChatResponse> {
+        let mut body = self.request_body(messages, &[]);
+        body["response_format"] = json!({
+            "type": "json_schema",
+            "json_schema": { "name": schema.name, "schema": schema.schema, "strict": true },
+        });
+        self.complete(&body).await
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[Value],
+        tools: &[Value],
+        on_delta: &mut DeltaSink<'_>,
+    ) -> Result<ChatResponse> {
+        let mut body = self.request_body(messages, tools);
+        body["stream"] = Value::Bool(true);
+        // Without this the stream carries no usage block
+        body["stream_options"] = json!({ "include_usage": true });
+
+        let resp = self
+            .retry
+            .run_async("chat completion", || {
+                let request = self.post(&body);
+                async move { retry::send_async(request).await.context("send chat completion") }
+            })
+            .await?;
 
-        Ok(ChatResponse { content, tool_calls })
+        let mut state = StreamState::default();
+        sse::read_events(resp, |event| state.handle(&event, on_delta)).await?;
+        state.finish()
+    }
+
+    fn set_retry_hook(&mut self, hook: RetryHook) {
+        self.retry.on_retry = Some(hook);
+    }
+
+    fn set_params(&mut self, params: GenerationParams) {
+        self.params = params;
     }
 
     fn set_model(&mut self, model: String) {
@@ -106,3 +196,109 @@ fn parse_tool_calls(message: &Value) -> Result<Vec<ToolCall>> {
     }
     Ok(calls)
 }
+
+/// Accumulates `chat.completion.chunk` events into a full response.
+/// Tool calls arrive as fragments keyed by `index`; the id and name come in the
+/// first fragment and `arguments` is a JSON string split across many chunks.
+#[derive(Default)]
+struct StreamState {
+    content: String,
+    calls: Vec<PartialToolCall>,
+    usage: Option<crate::usage::Usage>,
+}
+
+#[derive(Default)]
+struct PartialToolCall {
+    id: String,
+    name: String,
+    arguments: String,
+}
+
+impl StreamState {
+    fn handle(
+        &mut self,
+        event: &sse::SseEvent,
+        on_delta: &mut DeltaSink<'_>,
+    ) -> Result<()> {
+        if event.data == "[DONE]" {
+            return Ok(());
+        }
+        let chunk: Value =
+            serde_json::from_str(&event.data).context("parse chat completion chunk")?;
+        if let Some(err) = chunk.get("error") {
+            return Err(anyhow!("chat completion stream error: {err}"));
+        }
+        // With include_usage the last chunk has empty choices and a usage block
+        if let Some(usage) = crate::usage::from_openai(&chunk) {
+            self.usage = Some(usage);
+        }
+        let delta = match chunk
+            .get("choices")
+            .and_then(|choices| choices.get(0))
+            .and_then(|choice| choice.get("delta"))
+        {
+            Some(delta) => delta,
+            None => return Ok(()),
+        };
+
+        if let Some(text) = delta.get("content").and_then(|v| v.as_str())
+            && !text.is_empty()
+        {
+            self.content.push_str(text);
+            on_delta(text);
+        }
+
+        if let Some(Value::Array(items)) = delta.get("tool_calls") {
+            for item in items {
+                let index = item.get("index").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+                while self.calls.len() <= index {
+                    self.calls.push(PartialToolCall::default());
+                }
+                let call = &mut self.calls[index];
+                if let Some(id) = item.get("id").and_then(|v| v.as_str()) {
+                    call.id = id.to_string();
+                }
+                if let Some(function) = item.get("function") {
+                    if let Some(name) = function.get("name").and_then(|v| v.as_str()) {
+                        call.name.push_str(name);
+                    }
+                    if let Some(args) = function.get("arguments").and_then(|v| v.as_str()) {
+                        call.arguments.push_str(args);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<ChatResponse> {
+        let mut tool_calls = Vec::new();
+        for call in self.calls {
+            if call.name.is_empty() {
+                continue;
+            }
+            let args_str = if call.arguments.trim().is_empty() {
+                "{}"
+            } else {
+                call.arguments.as_str()
+            };
+            let arguments: Value =
+                serde_json::from_str(args_str).context("parse tool arguments as JSON")?;
+            tool_calls.push(ToolCall {
+                id: call.id,
+                name: call.name,
+                arguments,
+            });
+        }
+        let content = if self.content.is_empty() {
+            None
+        } else {
+            Some(self.content)
+        };
+        Ok(ChatResponse {
+            content,
+            tool_calls,
+            usage: self.usage,
+        })
+    }
+}
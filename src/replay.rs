@@ -0,0 +1,182 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+
+use crate::engine::{ChatResponse, DeltaSink, Engine};
+use crate::schema::JsonSchema;
+
+/// One recorded request/response pair. A fixture file is JSONL of these.
+/// Only `response` is required when hand-writing fixtures; `model`,
+/// `messages` and `tools` are written by record mode for inspection.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Exchange {
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub ts: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub messages: Option<Vec<Value>>,
+    /// Names of the tools offered on this call.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub tools: Option<Vec<String>>,
+    pub response: ChatResponse,
+}
+
+/// Engine that answers from a fixture file instead of the network.
+/// Responses are served in file order; running past the end is an error so a
+/// test that makes more calls than scripted fails loudly. Each engine instance
+/// (e.g. the one built for title generation) replays from the start.
+pub struct ReplayEngine {
+    path: PathBuf,
+    responses: Mutex<VecDeque<ChatResponse>>,
+    model: String,
+}
+
+impl ReplayEngine {
+    pub fn load(path: &Path, model: String) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("read replay fixture {}", path.display()))?;
+        let mut responses = VecDeque::new();
+        for (idx, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let exchange: Exchange = serde_json::from_str(line)
+                .with_context(|| format!("parse {} line {}", path.display(), idx + 1))?;
+            responses.push_back(exchange.response);
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            responses: Mutex::new(responses),
+            model,
+        })
+    }
+}
+
+#[async_trait]
+impl Engine for ReplayEngine {
+    async fn chat(&self, _messages: &[Value], _tools: &[Value]) -> Result<ChatResponse> {
+        self.responses
+            .lock()
+            .map_err(|_| anyhow!("replay fixture lock poisoned"))?
+            .pop_front()
+            .ok_or_else(|| anyhow!("replay fixture exhausted: {}", self.path.display()))
+    }
+
+    fn set_model(&mut self, model: String) {
+        self.model = model;
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+}
+
+/// Wraps a real engine and appends every exchange to a fixture file that
+/// `ReplayEngine` can later play back.
+pub struct RecordingEngine {
+    inner: Box<dyn Engine>,
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl RecordingEngine {
+    pub fn new(inner: Box<dyn Engine>, path: PathBuf) -> Self {
+        Self {
+            inner,
+            path,
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn record(&self, messages: &[Value], tools: &[Value], response: &ChatResponse) -> Result<()> {
+        let exchange = Exchange {
+            ts: Some(Utc::now()),
+            model: Some(self.inner.model().to_string()),
+            messages: Some(messages.to_vec()),
+            tools: Some(
+                tools
+                    .iter()
+                    .filter_map(|t| {
+                        t.get("function")
+                            .and_then(|f| f.get("name"))
+                            .and_then(|n| n.as_str())
+                            .map(|s| s.to_string())
+                    })
+                    .collect(),
+            ),
+            response: response.clone(),
+        };
+        let line = serde_json::to_string(&exchange)?;
+        let _guard = self.lock.lock().map_err(|_| anyhow!("record lock poisoned"))?;
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("open record file {}", self.path.display()))?;
+        file.write_all(line.as_bytes())?;
+        file.write_all(b"\n")?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Engine for RecordingEngine {
+    async fn chat(&self, messages: &[Value], tools: &[Value]) -> Result<ChatResponse> {
+        let response = self.inner.chat(messages, tools).await?;
+        self.record(messages, tools, &response)?;
+        Ok(response)
+    }
+
+    async fn chat_json(&self, messages: &[Value], schema: &JsonSchema) -> Result<ChatResponse> {
+        let response = self.inner.chat_json(messages, schema).await?;
+        self.record(messages, &[], &response)?;
+        Ok(response)
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[Value],
+        tools: &[Value],
+        on_delta: &mut DeltaSink<'_>,
+    ) -> Result<ChatResponse> {
+        let response = self.inner.chat_stream(messages, tools, on_delta).await?;
+        self.record(messages, tools, &response)?;
+        Ok(response)
+    }
+
+    fn set_retry_hook(&mut self, hook: crate::retry::RetryHook) {
+        self.inner.set_retry_hook(hook);
+    }
+
+    fn set_fallback_hook(&mut self, hook: crate::fallback::FallbackHook) {
+        self.inner.set_fallback_hook(hook);
+    }
+
+    fn active_engine(&self) -> Option<crate::engine::EngineKind> {
+        self.inner.active_engine()
+    }
+
+    fn set_params(&mut self, params: crate::engine::GenerationParams) {
+        self.inner.set_params(params);
+    }
+
+    fn set_model(&mut self, model: String) {
+        self.inner.set_model(model);
+    }
+
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+}
@@ -0,0 +1,379 @@
+use anyhow::{anyhow, Result};
+use rand::Rng;
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+use std::env;
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// Called before each retry sleep, e.g. to forward the retry to gateway clients.
+pub type RetryHook = Arc<dyn Fn(&RetryNotice) + Send + Sync>;
+
+/// Details of a single retry, passed to the `RetryHook`.
+#[derive(Debug, Clone)]
+pub struct RetryNotice {
+    /// What was being attempted, e.g. "openai chat".
+    pub label: String,
+    /// 1-based number of the retry about to happen.
+    pub attempt: u32,
+    pub max_retries: u32,
+    pub delay: Duration,
+    pub reason: String,
+}
+
+/// Error marker for failures worth retrying (429, 5xx, Anthropic 529
+/// overloaded, timeouts, dropped connections). Anything else fails fast.
+#[derive(Debug)]
+pub struct Transient {
+    pub reason: String,
+    /// Server-requested delay from `Retry-After` / `retry-after-ms`.
+    pub retry_after: Option<Duration>,
+}
+
+impl fmt::Display for Transient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.reason)
+    }
+}
+
+impl std::error::Error for Transient {}
+
+/// Exponential backoff with jitter.
+#[derive(Clone)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub on_retry: Option<RetryHook>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 4,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            on_retry: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Read `{PREFIX}_MAX_RETRIES`, `{PREFIX}_RETRY_BASE_MS` and `{PREFIX}_RETRY_MAX_MS`
+    /// (e.g. `ANTHROPIC_MAX_RETRIES`), falling back to the `LLM_*` equivalents.
+    /// Unlike `EngineConfig`, the provider-specific value wins so one engine can
+    /// be tuned without affecting the rest.
+    pub fn from_env(prefix: &str) -> Self {
+        let defaults = Self::default();
+        let read = |suffix: &str| -> Option<u64> {
+            env::var(format!("{prefix}_{suffix}"))
+                .or_else(|_| env::var(format!("LLM_{suffix}")))
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+        };
+        Self {
+            max_retries: read("MAX_RETRIES")
+                .map(|n| n as u32)
+                .unwrap_or(defaults.max_retries),
+            base_delay: read("RETRY_BASE_MS")
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.base_delay),
+            max_delay: read("RETRY_MAX_MS")
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.max_delay),
+            on_retry: None,
+        }
+    }
+
+    /// Run `attempt` until it succeeds, fails with a non-`Transient` error, or
+    /// the retry budget is spent. Blocking; for the embedding client.
+    pub fn run<T>(&self, label: &str, mut attempt: impl FnMut() -> Result<T>) -> Result<T> {
+        let mut retries = 0;
+        loop {
+            let err = match attempt() {
+                Ok(value) => return Ok(value),
+                Err(err) => err,
+            };
+            let delay = self.next_delay(label, &err, retries).ok_or_else(|| self.give_up(label, err, retries))?;
+            retries += 1;
+            thread::sleep(delay);
+        }
+    }
+
+    /// Async version of `run`, used by the chat engines.
+    pub async fn run_async<T, F, Fut>(&self, label: &str, mut attempt: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut retries = 0;
+        loop {
+            let err = match attempt().await {
+                Ok(value) => return Ok(value),
+                Err(err) => err,
+            };
+            let delay = self.next_delay(label, &err, retries).ok_or_else(|| self.give_up(label, err, retries))?;
+            retries += 1;
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// Decide whether `err` should be retried after `retries` earlier retries.
+    /// Returns the delay and reports the retry to the hook (or stderr), or None
+    /// if the error isn't `Transient` or the budget is spent. Public for callers
+    /// whose retry loop can't be expressed as a single closure.
+    pub fn next_delay(&self, label: &str, err: &anyhow::Error, retries: u32) -> Option<Duration> {
+        let transient = err.downcast_ref::<Transient>()?;
+        if retries >= self.max_retries {
+            return None;
+        }
+        let attempt = retries + 1;
+        // A server asking for longer than `max_delay` doesn't get to stall us
+        let delay = transient
+            .retry_after
+            .map(|after| after.min(self.max_delay))
+            .unwrap_or_else(|| self.backoff(attempt));
+        let notice = RetryNotice {
+            label: label.to_string(),
+            attempt,
+            max_retries: self.max_retries,
+            delay,
+            reason: transient.reason.clone(),
+        };
+        match self.on_retry {
+            Some(ref hook) => hook(&notice),
+            None => eprintln!(
+                "[{label}: {}, retry {}/{} in {:.1}s]",
+                notice.reason,
+                attempt,
+                self.max_retries,
+                delay.as_secs_f64()
+            ),
+        }
+        Some(delay)
+    }
+
+    /// Final error once retrying stops; notes the retries if any were spent.
+    pub fn give_up(&self, label: &str, err: anyhow::Error, retries: u32) -> anyhow::Error {
+        if err.downcast_ref::<Transient>().is_some() && retries > 0 {
+            err.context(format!("{label}: giving up after {retries} retries"))
+        } else {
+            err
+        }
+    }
+
+    /// Delay before retry number `retry` (1-based): base * 2^(retry-1), capped
+    /// at `max_delay`, then jittered into the upper half of that range.
+    fn backoff(&self, retry: u32) -> Duration {
+        let exp = self
+            .base_delay
+            .saturating_mul(1u32 << (retry - 1).min(16))
+            .min(self.max_delay);
+        let half = exp / 2;
+        let jitter_ms = rand::thread_rng().gen_range(0..=half.as_millis() as u64);
+        half + Duration::from_millis(jitter_ms)
+    }
+}
+
+/// Send a request and check its status. Retryable failures come back as
+/// `Transient` errors for `RetryPolicy::run`; other error statuses include the
+/// response body, which usually says what was wrong with the request.
+pub fn send(request: reqwest::blocking::RequestBuilder) -> Result<reqwest::blocking::Response> {
+    let resp = request.send().map_err(classify_error)?;
+    match classify_status(resp.status(), resp.headers()) {
+        Some(transient) => Err(anyhow!(transient)),
+        None if resp.status().is_success() => Ok(resp),
+        None => {
+            let status = resp.status();
+            let body = resp.text().unwrap_or_default();
+            Err(anyhow!("HTTP {}: {}", status, body.trim()))
+        }
+    }
+}
+
+/// Async version of `send`, used by the chat engines.
+pub async fn send_async(request: reqwest::RequestBuilder) -> Result<reqwest::Response> {
+    let resp = request.send().await.map_err(classify_error)?;
+    match classify_status(resp.status(), resp.headers()) {
+        Some(transient) => Err(anyhow!(transient)),
+        None if resp.status().is_success() => Ok(resp),
+        None => {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            Err(anyhow!("HTTP {}: {}", status, body.trim()))
+        }
+    }
+}
+
+/// Timeouts and refused connections are worth retrying; anything else
+/// (bad URL, TLS setup) is not.
+fn classify_error(err: reqwest::Error) -> anyhow::Error {
+    if err.is_timeout() || err.is_connect() {
+        anyhow!(Transient {
+            reason: err.to_string(),
+            retry_after: None,
+        })
+    } else {
+        err.into()
+    }
+}
+
+fn classify_status(status: StatusCode, headers: &HeaderMap) -> Option<Transient> {
+    if !is_retryable(status) {
+        return None;
+    }
+    Some(Transient {
+        reason: status_reason(status),
+        retry_after: retry_after(headers),
+    })
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    // 529 is Anthropic's "overloaded"
+    matches!(status.as_u16(), 408 | 429 | 500 | 502 | 503 | 504 | 529)
+}
+
+fn status_reason(status: StatusCode) -> String {
+    match status.as_u16() {
+        429 => "rate limited (429)".to_string(),
+        529 => "overloaded (529)".to_string(),
+        _ => format!("HTTP {status}"),
+    }
+}
+
+/// Parse `retry-after-ms` (OpenAI) or `Retry-After` as seconds or an HTTP date.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    if let Some(ms) = headers
+        .get("retry-after-ms")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<f64>().ok())
+    {
+        return Some(Duration::from_millis(ms.max(0.0) as u64));
+    }
+    let value = headers.get(reqwest::header::RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse::<f64>() {
+        return Some(Duration::from_millis((secs.max(0.0) * 1000.0) as u64));
+    }
+    let when = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let wait = when.signed_duration_since(chrono::Utc::now());
+    Some(wait.to_std().unwrap_or(Duration::ZERO))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in pairs {
+            map.insert(*name, HeaderValue::from_str(value).unwrap());
+        }
+        map
+    }
+
+    fn quiet_policy(max_retries: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_retries,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(4),
+            on_retry: Some(Arc::new(|_| {})),
+        }
+    }
+
+    fn transient() -> anyhow::Error {
+        anyhow!(Transient { reason: "rate limited (429)".into(), retry_after: None })
+    }
+
+    #[test]
+    fn retry_after_ms_takes_precedence() {
+        let h = headers(&[("retry-after-ms", "250"), ("retry-after", "9")]);
+        assert_eq!(retry_after(&h), Some(Duration::from_millis(250)));
+    }
+
+    #[test]
+    fn retry_after_seconds_and_fractions() {
+        assert_eq!(retry_after(&headers(&[("retry-after", "2")])), Some(Duration::from_secs(2)));
+        assert_eq!(retry_after(&headers(&[("retry-after", "1.5")])), Some(Duration::from_millis(1500)));
+    }
+
+    #[test]
+    fn retry_after_http_date_in_the_past_is_zero() {
+        let h = headers(&[("retry-after", "Wed, 21 Oct 2015 07:28:00 GMT")]);
+        assert_eq!(retry_after(&h), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn retry_after_missing_or_garbage() {
+        assert_eq!(retry_after(&HeaderMap::new()), None);
+        assert_eq!(retry_after(&headers(&[("retry-after", "soon")])), None);
+    }
+
+    #[test]
+    fn backoff_doubles_within_jitter_and_caps() {
+        let policy = RetryPolicy {
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(1000),
+            ..RetryPolicy::default()
+        };
+        for (retry, full) in [(1, 100), (2, 200), (3, 400), (4, 800), (5, 1000), (30, 1000)] {
+            let delay = policy.backoff(retry).as_millis() as u64;
+            assert!((full / 2..=full).contains(&delay), "retry {retry}: {delay}ms outside {}..={full}", full / 2);
+        }
+    }
+
+    #[test]
+    fn only_transient_errors_retry() {
+        let policy = quiet_policy(3);
+        assert!(policy.next_delay("t", &anyhow!("HTTP 400: bad request"), 0).is_none());
+        assert!(policy.next_delay("t", &transient(), 0).is_some());
+        assert!(policy.next_delay("t", &transient(), 3).is_none());
+    }
+
+    #[test]
+    fn server_delay_overrides_backoff() {
+        let policy = RetryPolicy { max_delay: Duration::from_secs(30), ..quiet_policy(1) };
+        let err = anyhow!(Transient { reason: "busy".into(), retry_after: Some(Duration::from_secs(7)) });
+        assert_eq!(policy.next_delay("t", &err, 0), Some(Duration::from_secs(7)));
+    }
+
+    #[test]
+    fn server_delay_is_capped_at_max_delay() {
+        let policy = RetryPolicy { max_delay: Duration::from_secs(30), ..quiet_policy(1) };
+        let err = anyhow!(Transient { reason: "busy".into(), retry_after: Some(Duration::from_secs(86_400)) });
+        assert_eq!(policy.next_delay("t", &err, 0), Some(Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn run_gives_up_after_budget() {
+        let calls = AtomicU32::new(0);
+        let err = quiet_policy(2)
+            .run("probe", || -> Result<()> {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Err(transient())
+            })
+            .unwrap_err();
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert!(format!("{err:#}").contains("giving up after 2 retries"));
+    }
+
+    #[test]
+    fn run_returns_first_success() {
+        let calls = AtomicU32::new(0);
+        let value = quiet_policy(4)
+            .run("probe", || {
+                if calls.fetch_add(1, Ordering::SeqCst) < 2 {
+                    Err(transient())
+                } else {
+                    Ok(42)
+                }
+            })
+            .unwrap();
+        assert_eq!(value, 42);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,317 @@
+use serde_json::Value;
+use std::fmt;
+
+/// A named JSON Schema for structured output. Providers that enforce schemas
+/// strictly (OpenAI) expect every object schema to list all of its properties
+/// under `required` and set `additionalProperties: false`; the top level must
+/// be an object schema (Anthropic passes it as a tool's `input_schema`).
+#[derive(Debug, Clone)]
+pub struct JsonSchema {
+    /// Identifier sent to the provider, e.g. "session_title".
+    pub name: String,
+    pub schema: Value,
+}
+
+impl JsonSchema {
+    pub fn new(name: &str, schema: Value) -> Self {
+        Self {
+            name: name.to_string(),
+            schema,
+        }
+    }
+}
+
+/// One way a value fails its schema, located by JSON pointer (RFC 6901).
+#[derive(Debug, Clone)]
+pub struct Violation {
+    pub pointer: String,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let at = if self.pointer.is_empty() { "/" } else { &self.pointer };
+        write!(f, "{at}: {}", self.message)
+    }
+}
+
+/// Join violations into one line for error messages.
+pub fn describe(violations: &[Violation]) -> String {
+    violations
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Check `value` against `schema`. Supports the subset of JSON Schema used for
+/// tool parameters and structured output: `type`, `enum`, `const`,
+/// `properties`, `required`, `additionalProperties`, `items`, `anyOf`/`oneOf`,
+/// and the length/count/range bounds. Unknown keywords are ignored.
+pub fn validate(schema: &Value, value: &Value) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    check(schema, value, "", &mut violations);
+    violations
+}
+
+fn check(schema: &Value, value: &Value, pointer: &str, out: &mut Vec<Violation>) {
+    let schema = match schema {
+        Value::Object(map) => map,
+        // `true` / missing schema accepts anything; `false` accepts nothing
+        Value::Bool(false) => {
+            out.push(violation(pointer, "no value is allowed here".into()));
+            return;
+        }
+        _ => return,
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let allowed: Vec<&str> = match expected {
+            Value::String(name) => vec![name.as_str()],
+            Value::Array(names) => names.iter().filter_map(|n| n.as_str()).collect(),
+            _ => Vec::new(),
+        };
+        if !allowed.is_empty() && !allowed.iter().any(|name| is_type(value, name)) {
+            out.push(violation(
+                pointer,
+                format!("expected {}, got {}", allowed.join(" or "), type_name(value)),
+            ));
+            // Further keywords would only repeat the type mismatch
+            return;
+        }
+    }
+
+    if let Some(options) = schema.get("enum").and_then(|v| v.as_array())
+        && !options.contains(value)
+    {
+        let listed: Vec<String> = options.iter().map(|o| o.to_string()).collect();
+        out.push(violation(pointer, format!("must be one of {}", listed.join(", "))));
+    }
+    if let Some(expected) = schema.get("const")
+        && expected != value
+    {
+        out.push(violation(pointer, format!("must be {expected}")));
+    }
+
+    for key in ["anyOf", "oneOf"] {
+        if let Some(branches) = schema.get(key).and_then(|v| v.as_array()) {
+            let matching = branches
+                .iter()
+                .filter(|branch| validate(branch, value).is_empty())
+                .count();
+            let ok = if key == "oneOf" { matching == 1 } else { matching > 0 };
+            if !ok {
+                out.push(violation(pointer, format!("does not match {key} schemas")));
+            }
+        }
+    }
+
+    match value {
+        Value::Object(map) => {
+            let properties = schema.get("properties").and_then(|v| v.as_object());
+            if let Some(required) = schema.get("required").and_then(|v| v.as_array()) {
+                for name in required.iter().filter_map(|n| n.as_str()) {
+                    if !map.contains_key(name) {
+                        out.push(violation(
+                            &child(pointer, name),
+                            "required property is missing".into(),
+                        ));
+                    }
+                }
+            }
+            for (name, item) in map {
+                let item_pointer = child(pointer, name);
+                match properties.and_then(|p| p.get(name)) {
+                    Some(item_schema) => check(item_schema, item, &item_pointer, out),
+                    None => match schema.get("additionalProperties") {
+                        Some(Value::Bool(false)) => {
+                            out.push(violation(&item_pointer, "unknown property".into()))
+                        }
+                        Some(extra @ Value::Object(_)) => check(extra, item, &item_pointer, out),
+                        _ => {}
+                    },
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(min) = schema.get("minItems").and_then(|v| v.as_u64())
+                && (items.len() as u64) < min
+            {
+                out.push(violation(pointer, format!("must have at least {min} items")));
+            }
+            if let Some(max) = schema.get("maxItems").and_then(|v| v.as_u64())
+                && (items.len() as u64) > max
+            {
+                out.push(violation(pointer, format!("must have at most {max} items")));
+            }
+            if let Some(item_schema) = schema.get("items") {
+                for (idx, item) in items.iter().enumerate() {
+                    check(item_schema, item, &format!("{pointer}/{idx}"), out);
+                }
+            }
+        }
+        Value::String(text) => {
+            let len = text.chars().count() as u64;
+            if let Some(min) = schema.get("minLength").and_then(|v| v.as_u64())
+                && len < min
+            {
+                out.push(violation(pointer, format!("must be at least {min} characters")));
+            }
+            if let Some(max) = schema.get("maxLength").and_then(|v| v.as_u64())
+                && len > max
+            {
+                out.push(violation(pointer, format!("must be at most {max} characters")));
+            }
+        }
+        Value::Number(number) => {
+            let n = number.as_f64().unwrap_or_default();
+            let bound = |key: &str| schema.get(key).and_then(|v| v.as_f64());
+            if let Some(min) = bound("minimum")
+                && n < min
+            {
+                out.push(violation(pointer, format!("must be >= {min}")));
+            }
+            if let Some(max) = bound("maximum")
+                && n > max
+            {
+                out.push(violation(pointer, format!("must be <= {max}")));
+            }
+            if let Some(min) = bound("exclusiveMinimum")
+                && n <= min
+            {
+                out.push(violation(pointer, format!("must be > {min}")));
+            }
+            if let Some(max) = bound("exclusiveMaximum")
+                && n >= max
+            {
+                out.push(violation(pointer, format!("must be < {max}")));
+            }
+        }
+        _ => {}
+    }
+}
+
+fn is_type(value: &Value, name: &str) -> bool {
+    match name {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|n| n.fract() == 0.0),
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Append a property name to a JSON pointer, escaping `~` and `/`.
+fn child(pointer: &str, name: &str) -> String {
+    format!("{pointer}/{}", name.replace('~', "~0").replace('/', "~1"))
+}
+
+fn violation(pointer: &str, message: String) -> Violation {
+    Violation {
+        pointer: pointer.to_string(),
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn pointers(schema: &Value, value: &Value) -> Vec<String> {
+        validate(schema, value).into_iter().map(|v| v.pointer).collect()
+    }
+
+    #[test]
+    fn valid_value_has_no_violations() {
+        let schema = json!({
+            "type": "object",
+            "properties": {"name": {"type": "string"}, "tags": {"type": "array", "items": {"type": "string"}}},
+            "required": ["name"],
+        });
+        assert!(validate(&schema, &json!({"name": "ada", "tags": ["math"]})).is_empty());
+    }
+
+    #[test]
+    fn missing_required_points_at_the_property() {
+        let schema = json!({"type": "object", "required": ["doc_path"]});
+        assert_eq!(pointers(&schema, &json!({})), ["/doc_path"]);
+    }
+
+    #[test]
+    fn nested_properties_and_array_items_extend_the_pointer() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "filter": {
+                    "type": "object",
+                    "properties": {"tags": {"type": "array", "items": {"type": "string"}}},
+                },
+            },
+        });
+        let value = json!({"filter": {"tags": ["ok", 3, "ok", null]}});
+        assert_eq!(pointers(&schema, &value), ["/filter/tags/1", "/filter/tags/3"]);
+    }
+
+    #[test]
+    fn additional_properties_false_flags_unknown_keys() {
+        let schema = json!({
+            "type": "object",
+            "properties": {"a": {"type": "string"}},
+            "additionalProperties": false,
+        });
+        let violations = validate(&schema, &json!({"a": "x", "b": 1}));
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].pointer, "/b");
+        assert_eq!(violations[0].message, "unknown property");
+    }
+
+    #[test]
+    fn additional_properties_schema_checks_extra_values() {
+        let schema = json!({"type": "object", "additionalProperties": {"type": "integer"}});
+        assert_eq!(pointers(&schema, &json!({"a": 1, "b": "two"})), ["/b"]);
+    }
+
+    #[test]
+    fn type_mismatch_stops_further_checks() {
+        let schema = json!({"type": "string", "minLength": 3, "enum": ["abc"]});
+        let violations = validate(&schema, &json!(7));
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].pointer, "");
+        assert_eq!(violations[0].message, "expected string, got number");
+    }
+
+    #[test]
+    fn enum_lists_the_options() {
+        let schema = json!({"type": "object", "properties": {"sort": {"enum": ["title", "updated_at"]}}});
+        let violations = validate(&schema, &json!({"sort": "size"}));
+        assert_eq!(violations[0].pointer, "/sort");
+        assert_eq!(violations[0].message, r#"must be one of "title", "updated_at""#);
+    }
+
+    #[test]
+    fn pointer_escapes_tilde_and_slash() {
+        let schema = json!({"type": "object", "additionalProperties": false});
+        assert_eq!(pointers(&schema, &json!({"a/b~c": 1})), ["/a~1b~0c"]);
+    }
+
+    #[test]
+    fn root_violation_displays_as_slash() {
+        let violations = validate(&json!({"type": "array"}), &json!({}));
+        assert_eq!(describe(&violations), "/: expected array, got object");
+    }
+}
@@ -0,0 +1,153 @@
+use anyhow::{Context, Result};
+
+/// A single server-sent event: optional `event:` name plus joined `data:` lines.
+#[derive(Debug, Clone)]
+pub struct SseEvent {
+    pub event: Option<String>,
+    pub data: String,
+}
+
+/// Incremental SSE parser. Feed it raw body chunks as they arrive; it returns
+/// every event completed by that chunk. Partial lines (including split UTF-8
+/// sequences) are buffered until the next chunk.
+#[derive(Default)]
+pub struct SseParser {
+    buf: Vec<u8>,
+    event: Option<String>,
+    data: Vec<String>,
+}
+
+impl SseParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        self.buf.extend_from_slice(chunk);
+        let mut events = Vec::new();
+        while let Some(pos) = self.buf.iter().position(|&b| b == b'\n') {
+            let raw: Vec<u8> = self.buf.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&raw);
+            let line = line.trim_end_matches(['\n', '\r']);
+            if let Some(event) = self.feed_line(line) {
+                events.push(event);
+            }
+        }
+        events
+    }
+
+    /// Flush a trailing event if the stream ended without a blank line.
+    pub fn finish(&mut self) -> Option<SseEvent> {
+        if !self.buf.is_empty() {
+            let raw = std::mem::take(&mut self.buf);
+            let line = String::from_utf8_lossy(&raw).trim_end_matches('\r').to_string();
+            if let Some(event) = self.feed_line(&line) {
+                return Some(event);
+            }
+        }
+        self.dispatch()
+    }
+
+    fn feed_line(&mut self, line: &str) -> Option<SseEvent> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        if line.starts_with(':') {
+            return None; // comment / keep-alive
+        }
+        let (field, value) = match line.split_once(':') {
+            Some((f, v)) => (f, v.strip_prefix(' ').unwrap_or(v)),
+            None => (line, ""),
+        };
+        match field {
+            "event" => self.event = Some(value.to_string()),
+            "data" => self.data.push(value.to_string()),
+            _ => {} // id, retry: unused
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        if self.data.is_empty() && self.event.is_none() {
+            return None;
+        }
+        let event = SseEvent {
+            event: self.event.take(),
+            data: self.data.join("\n"),
+        };
+        self.data.clear();
+        Some(event)
+    }
+}
+
+/// Read an SSE stream from an async response body, calling `on_event` for each event.
+pub async fn read_events(
+    mut resp: reqwest::Response,
+    mut on_event: impl FnMut(SseEvent) -> Result<()>,
+) -> Result<()> {
+    let mut parser = SseParser::new();
+    while let Some(chunk) = resp.chunk().await.context("read event stream")? {
+        for event in parser.push(&chunk) {
+            on_event(event)?;
+        }
+    }
+    if let Some(event) = parser.finish() {
+        on_event(event)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_split_across_chunks() {
+        let mut parser = SseParser::new();
+        assert!(parser.push(b"event: message_start\nda").is_empty());
+        let events = parser.push(b"ta: {\"a\":1}\n\ndata: two\n");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event.as_deref(), Some("message_start"));
+        assert_eq!(events[0].data, "{\"a\":1}");
+
+        let events = parser.push(b"\n");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event, None);
+        assert_eq!(events[0].data, "two");
+    }
+
+    #[test]
+    fn multibyte_character_split_between_chunks() {
+        let bytes = "data: héllo\n\n".as_bytes();
+        // Split inside the two-byte 'é'
+        let split = bytes.iter().position(|&b| b == 0xC3).unwrap() + 1;
+        let mut parser = SseParser::new();
+        assert!(parser.push(&bytes[..split]).is_empty());
+        let events = parser.push(&bytes[split..]);
+        assert_eq!(events[0].data, "héllo");
+    }
+
+    #[test]
+    fn data_lines_join_and_comments_are_skipped() {
+        let mut parser = SseParser::new();
+        let events = parser.push(b": keep-alive\r\ndata: a\r\ndata:b\r\nid: 7\r\n\r\n");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].data, "a\nb");
+    }
+
+    #[test]
+    fn blank_lines_alone_dispatch_nothing() {
+        let mut parser = SseParser::new();
+        assert!(parser.push(b"\n\n: ping\n\n").is_empty());
+        assert!(parser.finish().is_none());
+    }
+
+    #[test]
+    fn finish_flushes_unterminated_event() {
+        let mut parser = SseParser::new();
+        assert!(parser.push(b"data: [DONE]").is_empty());
+        let event = parser.finish().unwrap();
+        assert_eq!(event.data, "[DONE]");
+        assert!(parser.finish().is_none());
+    }
+}
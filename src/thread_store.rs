@@ -18,6 +18,10 @@ pub enum EventType {
     AttachmentAdded,
     InnerMonologue,
     TitleGenerated,
+    /// Rolling summary of earlier turns that no longer fit the context window.
+    ContextSummary,
+    /// A tool call was approved or denied under the permission policy.
+    PermissionDecision,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, clap::ValueEnum)]
@@ -51,6 +55,12 @@ pub struct ThreadEvent {
     pub engine: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub model: Option<String>,
+    /// Tokens used by the LLM call that produced this event.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub usage: Option<crate::usage::Usage>,
+    /// Cost of that call in USD, priced when the event was written.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub cost_usd: Option<f64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -202,6 +212,8 @@ pub fn build_event(
         reason,
         engine: None,
         model: None,
+        usage: None,
+        cost_usd: None,
     }
 }
 
@@ -230,6 +242,8 @@ pub fn build_event_with_engine(
         reason,
         engine,
         model,
+        usage: None,
+        cost_usd: None,
     }
 }
 
@@ -0,0 +1,123 @@
+//! Tool output too large for the context window is written to
+//! `<vault>/artifacts/tool_output/` and replaced by a preview; `artifact_read`
+//! pages through the rest.
+
+use anyhow::{anyhow, Context, Result};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{PermissionClass, Tool};
+use crate::agent::AgentConfig;
+
+/// Characters of serialised output a tool may return before it spills.
+pub const DEFAULT_OUTPUT_BUDGET: usize = 16_000;
+
+/// Page size when `artifact_read` is called without a limit.
+const DEFAULT_PAGE: usize = 8_000;
+
+const ARTIFACT_DIR: &str = "artifacts/tool_output";
+
+fn artifact_path(vault: &Path, artifact_id: &str) -> PathBuf {
+    vault.join(ARTIFACT_DIR).join(format!("{artifact_id}.json"))
+}
+
+/// If `data` serialises to more than `budget` characters, write it to an
+/// artifact and return the preview that replaces it. None when it fits.
+pub fn spill(vault: &Path, tool_name: &str, data: &Value, budget: usize) -> Result<Option<Value>> {
+    if serde_json::to_string(data)?.chars().count() <= budget {
+        return Ok(None);
+    }
+    // Pretty-printed so pages break at readable places
+    let text = serde_json::to_string_pretty(data)?;
+    let artifact_id = format!("art_{}", ulid::Ulid::new());
+    let path = artifact_path(vault, &artifact_id);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, &text).with_context(|| format!("write {}", path.display()))?;
+
+    // Leave room for the envelope so the preview result stays within budget
+    let preview: String = text.chars().take(budget / 2).collect();
+    let total_chars = text.chars().count();
+    Ok(Some(json!({
+        "truncated": true,
+        "tool": tool_name,
+        "preview": preview,
+        "artifact_id": artifact_id,
+        "artifact_path": format!("{ARTIFACT_DIR}/{artifact_id}.json"),
+        "total_chars": total_chars,
+        "next_offset": preview.chars().count(),
+        "hint": "Output was too large. Call artifact_read with this artifact_id and next_offset to read more."
+    })))
+}
+
+pub struct ArtifactRead;
+
+impl Tool for ArtifactRead {
+    fn name(&self) -> &str {
+        "artifact_read"
+    }
+
+    fn description(&self) -> &str {
+        "Read part of a tool output that was too large to return in full. Offsets and limits count characters."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "artifact_id": { "type": "string", "description": "artifact_id from a truncated tool result, e.g. art_01J..." },
+                "offset": { "type": "integer", "minimum": 0, "description": "Character offset to start at (default 0)." },
+                "limit": { "type": "integer", "minimum": 1, "maximum": DEFAULT_OUTPUT_BUDGET, "description": "Characters to return (default 8000)." },
+                "reason": { "type": "string" }
+            },
+            "required": ["artifact_id", "reason"]
+        })
+    }
+
+    fn permission(&self) -> PermissionClass {
+        PermissionClass::Read
+    }
+
+    fn output_budget(&self) -> Option<usize> {
+        // Pages are bounded by `limit`; spilling them again would loop
+        None
+    }
+
+    fn describe_call(&self, args: &Value) -> Option<String> {
+        let id = args.get("artifact_id").and_then(|v| v.as_str())?;
+        let offset = args.get("offset").and_then(|v| v.as_u64()).unwrap_or(0);
+        Some(format!("{id} @{offset}"))
+    }
+
+    fn execute(&self, args: &Value, config: &AgentConfig) -> Result<Value> {
+        let artifact_id = args
+            .get("artifact_id")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow!("artifact_read requires 'artifact_id'"))?;
+        if !artifact_id.starts_with("art_") || !artifact_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(anyhow!("invalid artifact_id: {artifact_id}"));
+        }
+        let offset = args.get("offset").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+        let limit = args
+            .get("limit")
+            .and_then(|v| v.as_u64())
+            .map(|n| n as usize)
+            .unwrap_or(DEFAULT_PAGE)
+            .min(DEFAULT_OUTPUT_BUDGET);
+
+        let path = artifact_path(&config.vault_path, artifact_id);
+        let text = fs::read_to_string(&path).map_err(|e| anyhow!("read artifact {artifact_id}: {e}"))?;
+        let total_chars = text.chars().count();
+        let content: String = text.chars().skip(offset).take(limit).collect();
+        let end = offset + content.chars().count();
+        Ok(json!({
+            "artifact_id": artifact_id,
+            "offset": offset,
+            "content": content,
+            "total_chars": total_chars,
+            "next_offset": (end < total_chars).then_some(end)
+        }))
+    }
+}
@@ -0,0 +1,89 @@
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use super::{PermissionClass, Tool};
+use crate::agent::{deep_think_background, AgentConfig, AgentEvent};
+
+pub struct DeepThink;
+
+impl Tool for DeepThink {
+    fn name(&self) -> &str {
+        "deep_think"
+    }
+
+    fn description(&self) -> &str {
+        "Trigger deep thinking. Calls a slower model to reflect on the conversation, search knowledge, and produce inner monologue. The result is appended to the thread as internal context visible on your next turn. Use when the conversation would benefit from deeper analysis, pattern recognition, or knowledge retrieval. The inner monologue will NOT be shown to the user."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "prompt": {
+                    "type": "string",
+                    "description": "What to think about. If omitted, reflects on the overall conversation."
+                },
+                "reason": { "type": "string" }
+            },
+            "required": ["reason"]
+        })
+    }
+
+    fn permission(&self) -> PermissionClass {
+        PermissionClass::Execute
+    }
+
+    fn describe_call(&self, args: &Value) -> Option<String> {
+        let prompt = args.get("prompt").and_then(|v| v.as_str())?;
+        Some(prompt.chars().take(80).collect())
+    }
+
+    fn execute(&self, args: &Value, config: &AgentConfig) -> Result<Value> {
+        let prompt = args.get("prompt").and_then(|v| v.as_str()).unwrap_or("").to_string();
+        let reason = args
+            .get("reason")
+            .and_then(|v| v.as_str())
+            .unwrap_or("deep_think")
+            .to_string();
+
+        // Check if already running
+        if config.deep_think_running.compare_exchange(
+            false, true, Ordering::SeqCst, Ordering::SeqCst,
+        ).is_err() {
+            return Ok(json!({ "status": "already_running" }));
+        }
+
+        let vault_path = config.vault_path.to_path_buf();
+        let thread_path = config.thread_path.to_path_buf();
+        let tools = Arc::clone(&config.tools);
+        let running = Arc::clone(&config.deep_think_running);
+        let event_sink = config.event_sink.clone();
+
+        // Tools run on a blocking thread inside the runtime, so the handle is available
+        tokio::runtime::Handle::current().spawn(async move {
+            let result = deep_think_background(
+                &vault_path,
+                &thread_path,
+                &prompt,
+                &reason,
+                tools,
+            )
+            .await;
+            match result {
+                Ok(monologue) => {
+                    if let Some(ref sink) = event_sink {
+                        let _ = sink.send(AgentEvent::DeepThinkComplete { monologue });
+                    }
+                }
+                Err(e) => {
+                    eprintln!("deep_think background error: {e}");
+                }
+            }
+            running.store(false, Ordering::SeqCst);
+        });
+
+        Ok(json!({ "status": "queued" }))
+    }
+}
@@ -0,0 +1,549 @@
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{PermissionClass, Tool};
+use crate::agent::AgentConfig;
+use crate::audit::{append_ledger, doc_history};
+use crate::embedding_index::{build_knowledge_index, search_knowledge_index};
+use crate::embeddings::EmbeddingClient;
+use crate::git_utils::git_commit;
+use crate::knowledge::{
+    apply_patch, diff_doc, entry_diff, list_docs, parse_date_bound, read_doc, revert_doc,
+    DocQuery, DocSort, KnowledgePatch,
+};
+
+pub struct KnowledgeApply;
+
+impl Tool for KnowledgeApply {
+    fn name(&self) -> &str {
+        "knowledge_apply"
+    }
+
+    fn description(&self) -> &str {
+        "Create or update a knowledge document. The patch object controls what gets written. IMPORTANT: use body_append to write body content; without it the doc will have an empty body."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "patch": {
+                    "type": "object",
+                    "properties": {
+                        "doc_path": { "type": "string", "description": "Path relative to vault root, e.g. summaries/sources/my-doc.md or knowledge/projects/foo.md" },
+                        "title": { "type": "string", "description": "Document title (required for new docs)" },
+                        "type": { "type": "string", "description": "Doc type: source_summary, project, person, preference, system (required for new docs)" },
+                        "status": { "type": "string", "description": "e.g. active (default)" },
+                        "confidence": { "type": "number", "description": "0.0-1.0 confidence score" },
+                        "tags_add": { "type": "array", "items": { "type": "string" }, "description": "Tags to add" },
+                        "tags_remove": { "type": "array", "items": { "type": "string" }, "description": "Tags to remove" },
+                        "body_append": { "type": "string", "description": "Markdown content to write as the document body. THIS IS HOW YOU WRITE CONTENT. Without it the doc will be empty." },
+                        "sources_add": { "type": "array", "items": { "type": "object", "properties": { "thread_id": { "type": "string" }, "event_ids": { "type": "array", "items": { "type": "string" } } } }, "description": "Source references (optional)" },
+                        "supersedes_add": { "type": "array", "items": { "type": "string" }, "description": "IDs of docs this supersedes" },
+                        "summary": { "type": "string", "description": "One-line description of the entire document (not the change). Max 150 chars. Required for new docs, updates the existing summary on existing docs." }
+                    },
+                    "required": ["doc_path"]
+                },
+                "author": { "type": "string" },
+                "reason": { "type": "string" },
+                "change_summary": { "type": "string", "description": "One-line description of what this specific mutation does. Max 150 chars. Example: 'Created project doc for J Gateway with tech stack and architecture'" },
+                "proposal_id": { "type": "string" },
+                "commit": { "type": "boolean" }
+            },
+            "required": ["patch", "author", "reason"]
+        })
+    }
+
+    fn permission(&self) -> PermissionClass {
+        PermissionClass::Write
+    }
+
+    fn execute(&self, args: &Value, config: &AgentConfig) -> Result<Value> {
+        let vault = &config.vault_path;
+        let patch_value = args
+            .get("patch")
+            .ok_or_else(|| anyhow!("patch required"))?
+            .clone();
+        let patch: KnowledgePatch = serde_json::from_value(patch_value)?;
+        let author = args
+            .get("author")
+            .and_then(|val| val.as_str())
+            .unwrap_or("assistant");
+        let reason = args
+            .get("reason")
+            .and_then(|val| val.as_str())
+            .unwrap_or("tool_call");
+        let proposal_id = args
+            .get("proposal_id")
+            .and_then(|val| val.as_str())
+            .map(|s| s.to_string());
+        let commit = args.get("commit").and_then(|val| val.as_bool()).unwrap_or(false);
+        if commit && !config.allow_commit {
+            return Err(anyhow!("commit requested but allow_commit is false"));
+        }
+
+        let change_summary = args
+            .get("change_summary")
+            .and_then(|val| val.as_str())
+            .unwrap_or("");
+        let result = apply_patch(vault, patch, author, reason, proposal_id.clone(), change_summary)?;
+        let ledger_path = vault.join("audit/ledger.jsonl");
+        append_ledger(&ledger_path, &result.ledger_entry)?;
+
+        if commit {
+            let repo_root = PathBuf::from(".");
+            let message = match &proposal_id {
+                Some(id) => format!("{id}: {reason}"),
+                None => format!("memory: {reason}"),
+            };
+            git_commit(&repo_root, &[result.doc_path.clone(), ledger_path.clone()], &message)?;
+        }
+
+        Ok(json!({
+            "doc_path": result.doc_path,
+            "ledger_id": result.ledger_entry.ledger_id
+        }))
+    }
+}
+
+pub struct KnowledgeRevert;
+
+impl Tool for KnowledgeRevert {
+    fn name(&self) -> &str {
+        "knowledge_revert"
+    }
+
+    fn description(&self) -> &str {
+        "Restore a knowledge document to an earlier version. 'to' is a ledger_id (the version that entry wrote) or a version hash (at least 8 hex characters). The revert is itself recorded in the ledger."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "doc_path": { "type": "string", "description": "Path relative to vault root, e.g. knowledge/projects/foo.md" },
+                "to": { "type": "string", "description": "ledger_id (led_...) or version hash / hash prefix to restore" },
+                "author": { "type": "string" },
+                "reason": { "type": "string" },
+                "commit": { "type": "boolean" }
+            },
+            "required": ["doc_path", "to", "author", "reason"]
+        })
+    }
+
+    fn permission(&self) -> PermissionClass {
+        PermissionClass::Write
+    }
+
+    fn describe_call(&self, args: &Value) -> Option<String> {
+        let doc_path = args.get("doc_path").and_then(|v| v.as_str())?;
+        let to = args.get("to").and_then(|v| v.as_str())?;
+        Some(format!("{doc_path} -> {to}"))
+    }
+
+    fn execute(&self, args: &Value, config: &AgentConfig) -> Result<Value> {
+        let vault = &config.vault_path;
+        let doc_path = args
+            .get("doc_path")
+            .and_then(|val| val.as_str())
+            .ok_or_else(|| anyhow!("doc_path required"))?;
+        let to = args
+            .get("to")
+            .and_then(|val| val.as_str())
+            .ok_or_else(|| anyhow!("to required"))?;
+        let author = args
+            .get("author")
+            .and_then(|val| val.as_str())
+            .unwrap_or("assistant");
+        let reason = args
+            .get("reason")
+            .and_then(|val| val.as_str())
+            .unwrap_or("tool_call");
+        let commit = args.get("commit").and_then(|val| val.as_bool()).unwrap_or(false);
+        if commit && !config.allow_commit {
+            return Err(anyhow!("commit requested but allow_commit is false"));
+        }
+
+        let result = revert_doc(vault, doc_path, to, author, reason)?;
+        let ledger_path = vault.join("audit/ledger.jsonl");
+        append_ledger(&ledger_path, &result.ledger_entry)?;
+
+        if commit {
+            let repo_root = PathBuf::from(".");
+            git_commit(
+                &repo_root,
+                &[result.doc_path.clone(), ledger_path.clone()],
+                &format!("memory: {reason}"),
+            )?;
+        }
+
+        Ok(json!({
+            "doc_path": result.doc_path,
+            "ledger_id": result.ledger_entry.ledger_id,
+            "version": result.ledger_entry.new_hash,
+            "change_summary": result.ledger_entry.change_summary
+        }))
+    }
+}
+
+pub struct KnowledgeHistory;
+
+impl Tool for KnowledgeHistory {
+    fn name(&self) -> &str {
+        "knowledge_history"
+    }
+
+    fn description(&self) -> &str {
+        "Edit history of a knowledge document from the audit ledger: who changed it, when, why, and what changed. Use it to explain how a belief evolved. Pass from/to (ledger_id or version hash) to get a unified diff between two versions instead."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "doc": { "type": "string", "description": "Doc path relative to vault root (knowledge/people/foo.md) or doc id (mem_...)" },
+                "include_diffs": { "type": "boolean", "description": "Include a unified diff for each change (default false)" },
+                "limit": { "type": "integer", "minimum": 1, "maximum": 100, "description": "Most recent changes to return (default 20)" },
+                "from": { "type": "string", "description": "Diff mode: older version, as ledger_id or hash. Defaults to the version before the latest change." },
+                "to": { "type": "string", "description": "Diff mode: newer version, as ledger_id or hash. Defaults to the current file." },
+                "reason": { "type": "string" }
+            },
+            "required": ["doc", "reason"]
+        })
+    }
+
+    fn permission(&self) -> PermissionClass {
+        PermissionClass::Read
+    }
+
+    fn describe_call(&self, args: &Value) -> Option<String> {
+        args.get("doc").and_then(|v| v.as_str()).map(|s| s.to_string())
+    }
+
+    fn execute(&self, args: &Value, config: &AgentConfig) -> Result<Value> {
+        let vault = &config.vault_path;
+        let doc = args
+            .get("doc")
+            .and_then(|val| val.as_str())
+            .ok_or_else(|| anyhow!("knowledge_history requires 'doc'"))?;
+        let history = doc_history(vault, doc)?;
+        // An id resolves to the path it was last written under
+        let doc_path = history.last().map(|e| e.doc_path.clone()).unwrap_or_else(|| doc.to_string());
+
+        let from = args.get("from").and_then(|val| val.as_str());
+        let to = args.get("to").and_then(|val| val.as_str());
+        if from.is_some() || to.is_some() {
+            let diff = diff_doc(vault, &doc_path, from, to)?;
+            return Ok(json!({
+                "doc_path": doc_path,
+                "from": from,
+                "to": to.unwrap_or("current"),
+                "diff": diff
+            }));
+        }
+
+        let include_diffs = args.get("include_diffs").and_then(|val| val.as_bool()).unwrap_or(false);
+        let limit = args.get("limit").and_then(|val| val.as_u64()).unwrap_or(20) as usize;
+        let entries: Vec<Value> = history
+            .iter()
+            .rev()
+            .take(limit)
+            .map(|entry| {
+                let mut item = json!({
+                    "ledger_id": entry.ledger_id,
+                    "ts": entry.ts,
+                    "author": entry.author,
+                    "reason": entry.reason,
+                    "op": entry.op,
+                    "change_summary": entry.change_summary,
+                    "doc_path": entry.doc_path,
+                    "prev_hash": entry.prev_hash,
+                    "new_hash": entry.new_hash
+                });
+                if include_diffs {
+                    // Null when the versions predate the object store
+                    item["diff"] = json!(entry_diff(vault, entry));
+                }
+                item
+            })
+            .collect();
+        Ok(json!({
+            "doc_path": doc_path,
+            "doc_id": history.last().map(|e| e.doc_id.clone()),
+            "total": history.len(),
+            "entries": entries
+        }))
+    }
+}
+
+pub struct KnowledgeRead;
+
+impl Tool for KnowledgeRead {
+    fn name(&self) -> &str {
+        "knowledge_read"
+    }
+
+    fn description(&self) -> &str {
+        "Read a knowledge document from the vault."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "doc_path": { "type": "string", "description": "Path relative to the vault root, e.g. knowledge/prefs/interaction.md" },
+                "include_body": { "type": "boolean", "description": "Include body content (default true)." },
+                "reason": { "type": "string" }
+            },
+            "required": ["doc_path", "reason"]
+        })
+    }
+
+    fn permission(&self) -> PermissionClass {
+        PermissionClass::Read
+    }
+
+    fn describe_call(&self, args: &Value) -> Option<String> {
+        args.get("doc_path").and_then(|v| v.as_str()).map(str::to_string)
+    }
+
+    fn execute(&self, args: &Value, config: &AgentConfig) -> Result<Value> {
+        let vault = &config.vault_path;
+        let doc_path = args
+            .get("doc_path")
+            .and_then(|val| val.as_str())
+            .ok_or_else(|| anyhow!("doc_path required"))?;
+        let include_body = args
+            .get("include_body")
+            .and_then(|val| val.as_bool())
+            .unwrap_or(true);
+        let full_path = crate::vault::confine(vault, Path::new(doc_path), "knowledge")?;
+        let doc = read_doc(&full_path)?;
+        if include_body {
+            Ok(json!({ "doc_path": doc_path, "front_matter": doc.front_matter, "body": doc.body }))
+        } else {
+            Ok(json!({ "doc_path": doc_path, "front_matter": doc.front_matter }))
+        }
+    }
+}
+
+pub struct KnowledgeSearch;
+
+impl Tool for KnowledgeSearch {
+    fn name(&self) -> &str {
+        "knowledge_search"
+    }
+
+    fn description(&self) -> &str {
+        "Search knowledge documents for a substring match."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": { "type": "string" },
+                "mode": { "type": "string", "description": "auto|vector|substring (default auto)" },
+                "limit": { "type": "integer" },
+                "reason": { "type": "string" }
+            },
+            "required": ["query", "reason"]
+        })
+    }
+
+    fn permission(&self) -> PermissionClass {
+        PermissionClass::Read
+    }
+
+    fn describe_call(&self, args: &Value) -> Option<String> {
+        args.get("query").and_then(|v| v.as_str()).map(str::to_string)
+    }
+
+    fn execute(&self, args: &Value, config: &AgentConfig) -> Result<Value> {
+        let vault = &config.vault_path;
+        let query = args
+            .get("query")
+            .and_then(|val| val.as_str())
+            .ok_or_else(|| anyhow!("query required"))?
+            .to_lowercase();
+        let limit = args.get("limit").and_then(|val| val.as_u64()).unwrap_or(10) as usize;
+        let mode = args
+            .get("mode")
+            .and_then(|val| val.as_str())
+            .unwrap_or("auto");
+
+        if mode == "vector" || mode == "auto" {
+            if let Ok(client) = EmbeddingClient::from_env() {
+                if let Ok(hits) = search_knowledge_index(vault, &client, &query, limit) {
+                    let items: Vec<Value> = hits
+                        .into_iter()
+                        .map(|hit| {
+                            json!({
+                                "doc_path": hit.doc_path,
+                                "chunk_id": hit.chunk_id,
+                                "score": hit.score,
+                                "excerpt": hit.excerpt
+                            })
+                        })
+                        .collect();
+                    return Ok(json!({ "mode": "vector", "count": items.len(), "matches": items }));
+                }
+            }
+            if mode == "vector" {
+                return Err(anyhow!("vector search unavailable (missing index or provider)"));
+            }
+        }
+
+        let root = vault.join("knowledge");
+        let mut matches = Vec::new();
+        for path in walk_markdown(&root)? {
+            let content = fs::read_to_string(&path)?;
+            let haystack = content.to_lowercase();
+            if let Some(idx) = haystack.find(&query) {
+                let excerpt = excerpt_at(&content, idx, 80);
+                let rel = path.strip_prefix(vault).unwrap_or(&path).to_string_lossy().to_string();
+                matches.push(json!({
+                    "doc_path": rel,
+                    "excerpt": excerpt
+                }));
+                if matches.len() >= limit {
+                    break;
+                }
+            }
+        }
+        Ok(json!({ "mode": "substring", "count": matches.len(), "matches": matches }))
+    }
+}
+
+pub struct KnowledgeList;
+
+impl Tool for KnowledgeList {
+    fn name(&self) -> &str {
+        "knowledge_list"
+    }
+
+    fn description(&self) -> &str {
+        "List knowledge documents by front matter: type, tags, status, confidence and when they were last updated. Use it to browse, e.g. all person docs tagged investor updated this month. Results are sorted and paginated."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "type": { "type": "string", "description": "Doc type, e.g. person, project, pref." },
+                "tags": { "type": "array", "items": { "type": "string" }, "description": "Docs must have every one of these tags." },
+                "status": { "type": "string", "description": "e.g. active, archived." },
+                "min_confidence": { "type": "number", "minimum": 0, "maximum": 1 },
+                "max_confidence": { "type": "number", "minimum": 0, "maximum": 1 },
+                "updated_since": { "type": "string", "description": "YYYY-MM-DD or RFC 3339, inclusive." },
+                "updated_until": { "type": "string", "description": "YYYY-MM-DD or RFC 3339, inclusive." },
+                "sort": { "type": "string", "enum": ["updated", "created", "title", "confidence"], "description": "Default updated." },
+                "order": { "type": "string", "enum": ["asc", "desc"], "description": "Default desc, or asc for title." },
+                "offset": { "type": "integer", "minimum": 0 },
+                "limit": { "type": "integer", "minimum": 1, "maximum": 100, "description": "Default 20." },
+                "reason": { "type": "string" }
+            },
+            "required": ["reason"]
+        })
+    }
+
+    fn permission(&self) -> PermissionClass {
+        PermissionClass::Read
+    }
+
+    fn execute(&self, args: &Value, config: &AgentConfig) -> Result<Value> {
+        let text = |key: &str| args.get(key).and_then(|v| v.as_str());
+        let date = |key: &str, end_of_day: bool| text(key).map(|v| parse_date_bound(v, end_of_day)).transpose();
+        let query = DocQuery {
+            doc_type: text("type").map(str::to_string),
+            tags: args
+                .get("tags")
+                .and_then(|v| v.as_array())
+                .map(|tags| tags.iter().filter_map(|t| t.as_str().map(str::to_string)).collect())
+                .unwrap_or_default(),
+            status: text("status").map(str::to_string),
+            min_confidence: args.get("min_confidence").and_then(|v| v.as_f64()),
+            max_confidence: args.get("max_confidence").and_then(|v| v.as_f64()),
+            updated_since: date("updated_since", false)?,
+            updated_until: date("updated_until", true)?,
+            sort: text("sort").map(DocSort::parse).transpose()?.unwrap_or(DocSort::Updated),
+            ascending: text("order").map(|o| o == "asc"),
+            offset: args.get("offset").and_then(|v| v.as_u64()).unwrap_or(0) as usize,
+            limit: args.get("limit").and_then(|v| v.as_u64()).unwrap_or(20).min(100) as usize,
+        };
+        Ok(serde_json::to_value(list_docs(&config.vault_path, &query)?)?)
+    }
+}
+
+pub struct KnowledgeIndex;
+
+impl Tool for KnowledgeIndex {
+    fn name(&self) -> &str {
+        "knowledge_index"
+    }
+
+    fn description(&self) -> &str {
+        "Build or rebuild the knowledge embedding index."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "reason": { "type": "string" }
+            },
+            "required": ["reason"]
+        })
+    }
+
+    /// Calls the embeddings API for every chunk.
+    fn permission(&self) -> PermissionClass {
+        PermissionClass::Execute
+    }
+
+    fn execute(&self, _args: &Value, config: &AgentConfig) -> Result<Value> {
+        let client = EmbeddingClient::from_env()?;
+        let stats = build_knowledge_index(&config.vault_path, &client, &config.cancel)?;
+        Ok(json!({
+            "doc_count": stats.doc_count,
+            "chunk_count": stats.chunk_count,
+            "index_path": stats.index_path,
+            "provider": stats.provider,
+            "model": stats.model
+        }))
+    }
+}
+
+pub fn walk_markdown(root: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    if !root.exists() {
+        return Ok(files);
+    }
+    let mut stack = vec![root.to_path_buf()];
+    while let Some(dir) = stack.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if path.is_dir() {
+                stack.push(path);
+            } else if path.extension().and_then(|s| s.to_str()) == Some("md") {
+                files.push(path);
+            }
+        }
+    }
+    Ok(files)
+}
+
+fn excerpt_at(content: &str, idx: usize, radius: usize) -> String {
+    let mut start = idx.saturating_sub(radius);
+    let mut end = usize::min(content.len(), idx + radius);
+    while start < content.len() && !content.is_char_boundary(start) {
+        start += 1;
+    }
+    while end > start && !content.is_char_boundary(end) {
+        end -= 1;
+    }
+    content[start..end].replace('\n', " ")
+}
@@ -0,0 +1,223 @@
+//! Tools declared in `<vault>/tools/*.yml` and backed by an external command.
+//!
+//! ```yaml
+//! name: weather
+//! description: Current weather for a city.
+//! parameters:
+//!   type: object
+//!   properties:
+//!     city: { type: string }
+//!     reason: { type: string }
+//!   required: [city, reason]
+//! command: [python3, tools/weather.py]
+//! timeout_secs: 20
+//! env: [WEATHER_API_KEY]
+//! permission: execute
+//! output_budget: 4000
+//! ```
+//!
+//! The command runs in the vault directory with the call arguments as JSON on
+//! stdin and must print a JSON result on stdout. A non-zero exit is an error
+//! carrying stderr. Only `PATH`, `J_VAULT` and the variables listed in `env`
+//! reach the child. Results longer than `output_budget` characters spill to an
+//! artifact.
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use super::{PermissionClass, Tool};
+use crate::agent::AgentConfig;
+
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    name: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default = "default_parameters")]
+    parameters: Value,
+    command: Vec<String>,
+    #[serde(default = "default_timeout")]
+    timeout_secs: u64,
+    #[serde(default)]
+    env: Vec<String>,
+    #[serde(default = "default_permission")]
+    permission: PermissionClass,
+    #[serde(default = "default_output_budget")]
+    output_budget: usize,
+}
+
+fn default_parameters() -> Value {
+    serde_json::json!({ "type": "object", "properties": {} })
+}
+
+fn default_timeout() -> u64 {
+    DEFAULT_TIMEOUT_SECS
+}
+
+fn default_permission() -> PermissionClass {
+    PermissionClass::Execute
+}
+
+fn default_output_budget() -> usize {
+    super::artifact::DEFAULT_OUTPUT_BUDGET
+}
+
+pub struct ManifestTool {
+    manifest: Manifest,
+    /// The manifest file, for listings.
+    pub path: PathBuf,
+}
+
+impl ManifestTool {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
+        let manifest: Manifest =
+            serde_yaml::from_str(&content).with_context(|| format!("parse {}", path.display()))?;
+        if manifest.name.is_empty() || !manifest.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            bail!("{}: invalid tool name {:?}", path.display(), manifest.name);
+        }
+        if manifest.command.is_empty() {
+            bail!("{}: command must not be empty", path.display());
+        }
+        if manifest.parameters.get("type").and_then(|t| t.as_str()) != Some("object") {
+            bail!("{}: parameters must be a JSON schema of type object", path.display());
+        }
+        Ok(Self { manifest, path: path.to_path_buf() })
+    }
+}
+
+/// All manifests in `<vault>/tools`, sorted by file name. A broken manifest is
+/// reported and skipped so one bad file doesn't take every tool down.
+pub fn discover(vault: &Path) -> Vec<ManifestTool> {
+    let dir = vault.join("tools");
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| matches!(p.extension().and_then(|s| s.to_str()), Some("yml" | "yaml")))
+        .collect();
+    paths.sort();
+    paths
+        .iter()
+        .filter_map(|path| match ManifestTool::load(path) {
+            Ok(tool) => Some(tool),
+            Err(e) => {
+                eprintln!("Warning: skipping tool manifest: {e:#}");
+                None
+            }
+        })
+        .collect()
+}
+
+impl Tool for ManifestTool {
+    fn name(&self) -> &str {
+        &self.manifest.name
+    }
+
+    fn description(&self) -> &str {
+        &self.manifest.description
+    }
+
+    fn parameters(&self) -> Value {
+        self.manifest.parameters.clone()
+    }
+
+    fn permission(&self) -> PermissionClass {
+        self.manifest.permission
+    }
+
+    fn output_budget(&self) -> Option<usize> {
+        Some(self.manifest.output_budget)
+    }
+
+    fn source(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    fn execute(&self, args: &Value, config: &AgentConfig) -> Result<Value> {
+        let name = &self.manifest.name;
+        let (program, rest) = self.manifest.command.split_first().expect("validated on load");
+
+        let mut cmd = Command::new(program);
+        cmd.args(rest)
+            .current_dir(&config.vault_path)
+            .env_clear()
+            .env("J_VAULT", &config.vault_path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        for var in std::iter::once("PATH").chain(self.manifest.env.iter().map(String::as_str)) {
+            if let Ok(value) = std::env::var(var) {
+                cmd.env(var, value);
+            }
+        }
+
+        let input = serde_json::to_string(args)?;
+        let mut child = cmd.spawn().map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                anyhow!("{name}: {program} not found")
+            } else {
+                anyhow!("{name}: spawn {program}: {e}")
+            }
+        })?;
+
+        // Drain both pipes on their own threads so a chatty child can't block on a full pipe
+        let mut stdout = child.stdout.take().expect("piped");
+        let mut stderr = child.stderr.take().expect("piped");
+        let stdout_reader = std::thread::spawn(move || {
+            let mut buf = Vec::new();
+            let _ = stdout.read_to_end(&mut buf);
+            buf
+        });
+        let stderr_reader = std::thread::spawn(move || {
+            let mut buf = Vec::new();
+            let _ = stderr.read_to_end(&mut buf);
+            buf
+        });
+
+        // Written from its own thread too: a child that never reads stdin would
+        // otherwise block us on a full pipe before the timeout loop starts.
+        // Dropping the handle at the end closes it so the child sees EOF.
+        let mut stdin = child.stdin.take().expect("piped");
+        std::thread::spawn(move || {
+            // A command that ignores its input may exit before reading it
+            let _ = stdin.write_all(input.as_bytes());
+        });
+
+        let deadline = Instant::now() + Duration::from_secs(self.manifest.timeout_secs);
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if Instant::now() >= deadline {
+                let _ = child.kill();
+                let _ = child.wait();
+                bail!("{name}: timed out after {}s", self.manifest.timeout_secs);
+            }
+            if config.cancel.is_cancelled() {
+                let _ = child.kill();
+                let _ = child.wait();
+                bail!("{name}: cancelled");
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        };
+
+        let stdout = stdout_reader.join().unwrap_or_default();
+        let stderr = stderr_reader.join().unwrap_or_default();
+        if !status.success() {
+            let stderr = String::from_utf8_lossy(&stderr);
+            bail!("{name} failed ({status}): {}", stderr.trim());
+        }
+        serde_json::from_slice(&stdout).with_context(|| format!("{name}: stdout is not valid JSON"))
+    }
+}
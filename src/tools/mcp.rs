@@ -0,0 +1,388 @@
+//! Model Context Protocol client. Each server in `mcp.servers` is started (or
+//! connected to) when the tool registry loads, and its tools are registered
+//! as `<server>__<tool>`.
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::{json, Value};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::Mutex;
+
+use super::{PermissionClass, Tool};
+use crate::agent::AgentConfig;
+use crate::config::{McpServerSettings, McpTransport};
+use crate::sse::SseParser;
+
+const PROTOCOL_VERSION: &str = "2025-03-26";
+
+/// Function names are limited to 64 characters by the chat APIs.
+const MAX_TOOL_NAME: usize = 64;
+
+struct StdioPipes {
+    // Held so the server is killed when the client goes away
+    _child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+struct HttpEndpoint {
+    http: reqwest::Client,
+    url: String,
+    bearer_token: Option<String>,
+    session_id: StdMutex<Option<String>>,
+}
+
+enum Transport {
+    Stdio(Box<Mutex<StdioPipes>>),
+    Http(HttpEndpoint),
+}
+
+/// A connected MCP server.
+pub struct McpClient {
+    name: String,
+    transport: Transport,
+    timeout: Duration,
+    next_id: AtomicU64,
+}
+
+impl McpClient {
+    /// Start or connect to the server and complete the initialize handshake.
+    pub async fn connect(settings: &McpServerSettings) -> Result<Self> {
+        let transport = match &settings.transport {
+            McpTransport::Stdio { command, args, env } => {
+                let mut child = Command::new(command)
+                    .args(args)
+                    .envs(env)
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::inherit())
+                    .kill_on_drop(true)
+                    .spawn()
+                    .with_context(|| format!("start {command}"))?;
+                let stdin = child.stdin.take().expect("piped");
+                let stdout = BufReader::new(child.stdout.take().expect("piped"));
+                Transport::Stdio(Box::new(Mutex::new(StdioPipes { _child: child, stdin, stdout })))
+            }
+            McpTransport::Http { url, bearer_token_env } => {
+                let bearer_token = match bearer_token_env {
+                    Some(var) => Some(std::env::var(var).with_context(|| format!("{var} not set"))?),
+                    None => None,
+                };
+                Transport::Http(HttpEndpoint {
+                    http: reqwest::Client::new(),
+                    url: url.clone(),
+                    bearer_token,
+                    session_id: StdMutex::new(None),
+                })
+            }
+        };
+        let client = Self {
+            name: settings.name.clone(),
+            transport,
+            timeout: settings.timeout,
+            next_id: AtomicU64::new(1),
+        };
+        client
+            .request(
+                "initialize",
+                json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "clientInfo": { "name": "j", "version": env!("CARGO_PKG_VERSION") }
+                }),
+            )
+            .await?;
+        client.notify("notifications/initialized").await?;
+        Ok(client)
+    }
+
+    /// Every tool the server offers, following pagination.
+    pub async fn list_tools(&self) -> Result<Vec<Value>> {
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor {
+                Some(c) => json!({ "cursor": c }),
+                None => json!({}),
+            };
+            let result = self.request("tools/list", params).await?;
+            if let Some(page) = result.get("tools").and_then(|t| t.as_array()) {
+                tools.extend(page.iter().cloned());
+            }
+            cursor = result.get("nextCursor").and_then(|c| c.as_str()).map(str::to_string);
+            if cursor.is_none() {
+                return Ok(tools);
+            }
+        }
+    }
+
+    /// Call a tool. A result flagged `isError` becomes an error carrying its text.
+    pub async fn call_tool(&self, name: &str, arguments: &Value) -> Result<Value> {
+        let result = self
+            .request("tools/call", json!({ "name": name, "arguments": arguments }))
+            .await?;
+        let content = result.get("content").cloned().unwrap_or_else(|| json!([]));
+        if result.get("isError").and_then(|e| e.as_bool()).unwrap_or(false) {
+            let text: Vec<&str> = content
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|block| block.get("text").and_then(|t| t.as_str()))
+                .collect();
+            bail!("{}", text.join("\n"));
+        }
+        match result.get("structuredContent") {
+            Some(structured) => Ok(structured.clone()),
+            None => Ok(json!({ "content": content })),
+        }
+    }
+
+    async fn request(&self, method: &str, params: Value) -> Result<Value> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let message = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        let response = tokio::time::timeout(self.timeout, self.exchange(&message, Some(id)))
+            .await
+            .map_err(|_| anyhow!("mcp {}: {method} timed out after {}s", self.name, self.timeout.as_secs()))?
+            .map_err(|e| anyhow!("mcp {}: {method}: {e:#}", self.name))?
+            .ok_or_else(|| anyhow!("mcp {}: {method}: no response", self.name))?;
+        if let Some(error) = response.get("error") {
+            let message = error.get("message").and_then(|m| m.as_str()).unwrap_or("unknown error");
+            bail!("mcp {}: {method}: {message}", self.name);
+        }
+        Ok(response.get("result").cloned().unwrap_or(Value::Null))
+    }
+
+    async fn notify(&self, method: &str) -> Result<()> {
+        let message = json!({ "jsonrpc": "2.0", "method": method });
+        tokio::time::timeout(self.timeout, self.exchange(&message, None))
+            .await
+            .map_err(|_| anyhow!("mcp {}: {method} timed out", self.name))?
+            .map_err(|e| anyhow!("mcp {}: {method}: {e:#}", self.name))?;
+        Ok(())
+    }
+
+    /// Send one message and, for a request, wait for the response with `id`.
+    async fn exchange(&self, message: &Value, id: Option<u64>) -> Result<Option<Value>> {
+        match &self.transport {
+            Transport::Stdio(pipes) => {
+                let mut pipes = pipes.lock().await;
+                let mut line = serde_json::to_string(message)?;
+                line.push('\n');
+                pipes.stdin.write_all(line.as_bytes()).await?;
+                pipes.stdin.flush().await?;
+                let Some(id) = id else {
+                    return Ok(None);
+                };
+                loop {
+                    let mut line = String::new();
+                    if pipes.stdout.read_line(&mut line).await? == 0 {
+                        bail!("server exited");
+                    }
+                    // Servers may log to stdout; anything that isn't JSON-RPC is skipped
+                    let Ok(incoming) = serde_json::from_str::<Value>(&line) else {
+                        continue;
+                    };
+                    if is_response_to(&incoming, id) {
+                        return Ok(Some(incoming));
+                    }
+                    if let Some(reply) = reject_server_request(&incoming) {
+                        let mut reply = serde_json::to_string(&reply)?;
+                        reply.push('\n');
+                        pipes.stdin.write_all(reply.as_bytes()).await?;
+                        pipes.stdin.flush().await?;
+                    }
+                }
+            }
+            Transport::Http(endpoint) => endpoint.post(message, id).await,
+        }
+    }
+}
+
+impl HttpEndpoint {
+    async fn post(&self, message: &Value, id: Option<u64>) -> Result<Option<Value>> {
+        let mut request = self
+            .http
+            .post(&self.url)
+            .header("Accept", "application/json, text/event-stream")
+            .header("MCP-Protocol-Version", PROTOCOL_VERSION)
+            .json(message);
+        if let Some(token) = &self.bearer_token {
+            request = request.bearer_auth(token);
+        }
+        if let Some(session) = self.session_id.lock().unwrap().clone() {
+            request = request.header("Mcp-Session-Id", session);
+        }
+        let mut resp = request.send().await?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            bail!("HTTP {status}: {body}");
+        }
+        if let Some(session) = resp.headers().get("mcp-session-id").and_then(|v| v.to_str().ok()) {
+            *self.session_id.lock().unwrap() = Some(session.to_string());
+        }
+        let Some(id) = id else {
+            return Ok(None);
+        };
+
+        let is_sse = resp
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|ct| ct.starts_with("text/event-stream"));
+        if !is_sse {
+            let body: Value = resp.json().await.context("parse response")?;
+            // A batch reply is an array; pick ours out of it
+            let found = match body {
+                Value::Array(items) => items.into_iter().find(|item| is_response_to(item, id)),
+                item => Some(item).filter(|item| is_response_to(item, id)),
+            };
+            return Ok(found);
+        }
+
+        // The stream may carry server notifications before the response
+        let mut parser = SseParser::new();
+        while let Some(chunk) = resp.chunk().await.context("read event stream")? {
+            for event in parser.push(&chunk) {
+                if let Ok(incoming) = serde_json::from_str::<Value>(&event.data)
+                    && is_response_to(&incoming, id)
+                {
+                    return Ok(Some(incoming));
+                }
+            }
+        }
+        Ok(parser
+            .finish()
+            .and_then(|event| serde_json::from_str::<Value>(&event.data).ok())
+            .filter(|incoming| is_response_to(incoming, id)))
+    }
+}
+
+fn is_response_to(message: &Value, id: u64) -> bool {
+    message.get("id").and_then(|i| i.as_u64()) == Some(id)
+        && (message.get("result").is_some() || message.get("error").is_some())
+}
+
+/// J advertises no client capabilities, so any request from the server
+/// (sampling, roots, elicitation) is answered with "method not found".
+/// Notifications need no reply.
+fn reject_server_request(message: &Value) -> Option<Value> {
+    let id = message.get("id")?;
+    let method = message.get("method")?.as_str()?;
+    Some(json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": -32601, "message": format!("method not found: {method}") }
+    }))
+}
+
+/// One tool offered by an MCP server.
+pub struct McpTool {
+    client: Arc<McpClient>,
+    /// Namespaced name the model sees.
+    name: String,
+    /// Name on the server.
+    remote_name: String,
+    description: String,
+    parameters: Value,
+    read_only: bool,
+}
+
+impl McpTool {
+    fn from_listing(client: &Arc<McpClient>, listing: &Value) -> Option<Self> {
+        let remote_name = listing.get("name")?.as_str()?.to_string();
+        let mut parameters = listing
+            .get("inputSchema")
+            .cloned()
+            .unwrap_or_else(|| json!({ "type": "object" }));
+        // Some servers omit `properties` for argument-less tools, which OpenAI rejects
+        if parameters.get("properties").is_none() {
+            parameters["properties"] = json!({});
+        }
+        Some(Self {
+            client: Arc::clone(client),
+            name: namespaced(&client.name, &remote_name),
+            remote_name,
+            description: listing
+                .get("description")
+                .and_then(|d| d.as_str())
+                .unwrap_or_default()
+                .to_string(),
+            parameters,
+            read_only: listing
+                .pointer("/annotations/readOnlyHint")
+                .and_then(|h| h.as_bool())
+                .unwrap_or(false),
+        })
+    }
+}
+
+/// `<server>__<tool>`, limited to the characters and length function names allow.
+fn namespaced(server: &str, tool: &str) -> String {
+    format!("{server}__{tool}")
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' { c } else { '_' })
+        .take(MAX_TOOL_NAME)
+        .collect()
+}
+
+impl Tool for McpTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn parameters(&self) -> Value {
+        self.parameters.clone()
+    }
+
+    /// Servers mark read-only tools with `readOnlyHint`; anything else may
+    /// have side effects outside the vault.
+    fn permission(&self) -> PermissionClass {
+        if self.read_only {
+            PermissionClass::Read
+        } else {
+            PermissionClass::Execute
+        }
+    }
+
+    fn source(&self) -> String {
+        format!("mcp:{}", self.client.name)
+    }
+
+    fn execute(&self, args: &Value, config: &AgentConfig) -> Result<Value> {
+        // Tools run on a blocking thread inside the runtime, so the handle is available
+        tokio::runtime::Handle::current()
+            .block_on(config.cancel.guard(self.client.call_tool(&self.remote_name, args)))
+    }
+}
+
+/// Connect to every configured server and list its tools. A server that
+/// fails to start is reported and skipped.
+pub async fn mount(servers: &[McpServerSettings]) -> Vec<McpTool> {
+    let connections = servers.iter().map(|settings| async move {
+        let client = Arc::new(McpClient::connect(settings).await?);
+        let listings = client.list_tools().await?;
+        Ok::<_, anyhow::Error>(
+            listings
+                .iter()
+                .filter_map(|listing| McpTool::from_listing(&client, listing))
+                .collect::<Vec<_>>(),
+        )
+    });
+    let mut tools = Vec::new();
+    for (settings, result) in servers.iter().zip(futures_util::future::join_all(connections).await) {
+        match result {
+            Ok(mounted) => tools.extend(mounted),
+            Err(e) => eprintln!("Warning: skipping MCP server {}: {e:#}", settings.name),
+        }
+    }
+    tools
+}
@@ -0,0 +1,232 @@
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::time::Duration;
+
+use super::{PermissionClass, Tool};
+use crate::agent::{AgentConfig, CancelToken};
+
+pub struct Draw;
+
+impl Tool for Draw {
+    fn name(&self) -> &str {
+        "draw"
+    }
+
+    fn description(&self) -> &str {
+        "Draw an image onto the user's canvas using rcast. Accepts a URL or local file path. Use this to show images, diagrams, or visual content to the user."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "source": { "type": "string", "description": "URL or local file path of the image to display." },
+                "overlay": { "type": "boolean", "description": "If true, overlay on existing canvas instead of clearing." },
+                "reason": { "type": "string" }
+            },
+            "required": ["source", "reason"]
+        })
+    }
+
+    fn permission(&self) -> PermissionClass {
+        PermissionClass::Execute
+    }
+
+    fn execute(&self, args: &Value, config: &AgentConfig) -> Result<Value> {
+        let source = args
+            .get("source")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow!("draw requires 'source' (URL or file path)"))?;
+
+        // If source looks like a URL, download to a temp file first.
+        let file_path = if source.starts_with("http://") || source.starts_with("https://") {
+            let resp = reqwest::blocking::get(source)?;
+            if !resp.status().is_success() {
+                return Err(anyhow!("failed to download {source}: HTTP {}", resp.status()));
+            }
+            let bytes = resp.bytes()?;
+            let ext = source.rsplit('.').next().unwrap_or("png");
+            let tmp = std::env::temp_dir().join(format!("j_draw.{ext}"));
+            fs::write(&tmp, &bytes)?;
+            if config.cancel.is_cancelled() {
+                return Err(anyhow!("draw: cancelled"));
+            }
+            tmp
+        } else if Path::new(source).is_absolute() {
+            PathBuf::from(source)
+        } else {
+            // Resolve relative paths against the vault root
+            config.vault_path.join(source)
+        };
+
+        let mut cmd = Command::new("rcast");
+        cmd.arg("draw").arg(&file_path);
+
+        if let Some(true) = args.get("overlay").and_then(|v| v.as_bool()) {
+            cmd.arg("--overlay");
+        }
+
+        let child = cmd.stdout(Stdio::null()).stderr(Stdio::piped()).spawn()?;
+        let Some(output) = wait_or_cancel(child, &config.cancel)? else {
+            return Err(anyhow!("draw: cancelled"));
+        };
+        if output.status.success() {
+            Ok(json!({ "drawn": source }))
+        } else {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            Err(anyhow!("rcast draw failed: {stderr}"))
+        }
+    }
+}
+
+pub struct GenerateImage;
+
+impl Tool for GenerateImage {
+    fn name(&self) -> &str {
+        "generate_image"
+    }
+
+    fn description(&self) -> &str {
+        "Generate an image using flux2 and store it in the vault media directory. Path should use descriptive folders for uniqueness (e.g. 'diagrams/arch-v2.png', 'food/pepperoni-pizza.png'). Returns error if path already exists."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "prompt": { "type": "string", "description": "Text description of the image to generate." },
+                "path": { "type": "string", "description": "Relative path within media/ (e.g. 'food/pizza.png'). Must end with .png. Intermediate directories are created automatically." },
+                "reason": { "type": "string" }
+            },
+            "required": ["prompt", "path", "reason"]
+        })
+    }
+
+    fn permission(&self) -> PermissionClass {
+        PermissionClass::Execute
+    }
+
+    fn describe_call(&self, args: &Value) -> Option<String> {
+        let prompt = args.get("prompt").and_then(|v| v.as_str())?;
+        Some(prompt.chars().take(80).collect())
+    }
+
+    fn execute(&self, args: &Value, config: &AgentConfig) -> Result<Value> {
+        let prompt = args
+            .get("prompt")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow!("generate_image requires 'prompt'"))?;
+        let rel_path = args
+            .get("path")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow!("generate_image requires 'path'"))?;
+
+        // Validate path: no .., no leading /, no backslash, must end with .png
+        if rel_path.contains("..")
+            || rel_path.starts_with('/')
+            || rel_path.contains('\\')
+            || rel_path.contains('\0')
+            || !rel_path.ends_with(".png")
+        {
+            return Err(anyhow!(
+                "invalid path: must be relative, no '..', and end with .png"
+            ));
+        }
+        // Additional check: only allow alphanumeric, dash, underscore, slash, dot
+        if !rel_path
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '/' | '.'))
+        {
+            return Err(anyhow!(
+                "invalid path: only alphanumeric, dash, underscore, slash allowed"
+            ));
+        }
+
+        let media_dir = config.vault_path.join("media");
+        let full_path = media_dir.join(rel_path);
+
+        // Verify resolved path is within media/
+        if let Ok(canonical_parent) = full_path.parent().unwrap_or(&media_dir).canonicalize() {
+            let canonical_media = media_dir.canonicalize().unwrap_or_else(|_| media_dir.clone());
+            if !canonical_parent.starts_with(&canonical_media) {
+                return Err(anyhow!("path escapes media directory"));
+            }
+        }
+
+        // Check existence
+        if full_path.exists() {
+            return Err(anyhow!("exists: media/{rel_path}"));
+        }
+
+        // Create parent directories
+        if let Some(parent) = full_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // Run flux2; generation takes a while, so a cancelled run stops it
+        let output = Command::new("flux2")
+            .arg(prompt)
+            .arg(&full_path)
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .and_then(|child| wait_or_cancel(child, &config.cancel));
+
+        match output {
+            Ok(None) => {
+                let _ = fs::remove_file(&full_path);
+                Err(anyhow!("generate_image: cancelled"))
+            }
+            Ok(Some(out)) if out.status.success() => {
+                if full_path.exists() {
+                    Ok(json!({ "path": format!("media/{rel_path}") }))
+                } else {
+                    Err(anyhow!("flux2 completed but output file not found"))
+                }
+            }
+            Ok(Some(out)) => {
+                // Clean up partial file
+                let _ = fs::remove_file(&full_path);
+                let stderr = String::from_utf8_lossy(&out.stderr);
+                Err(anyhow!("flux2 failed: {stderr}"))
+            }
+            Err(e) => {
+                let _ = fs::remove_file(&full_path);
+                if e.kind() == std::io::ErrorKind::NotFound {
+                    Err(anyhow!("flux2 not found"))
+                } else {
+                    Err(anyhow!("flux2 error: {e}"))
+                }
+            }
+        }
+    }
+}
+
+/// Wait for `child`, killing it if `cancel` fires first (then None). Stderr
+/// is drained on its own thread so a chatty child can't fill the pipe.
+fn wait_or_cancel(mut child: Child, cancel: &CancelToken) -> std::io::Result<Option<Output>> {
+    let stderr = child.stderr.take();
+    let stderr_reader = std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut stderr) = stderr {
+            let _ = stderr.read_to_end(&mut buf);
+        }
+        buf
+    });
+    loop {
+        if let Some(status) = child.try_wait()? {
+            let stderr = stderr_reader.join().unwrap_or_default();
+            return Ok(Some(Output { status, stdout: Vec::new(), stderr }));
+        }
+        if cancel.is_cancelled() {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok(None);
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
@@ -0,0 +1,165 @@
+pub mod artifact;
+pub mod deep_think;
+pub mod knowledge;
+pub mod manifest;
+pub mod mcp;
+pub mod media;
+pub mod thread;
+pub mod vault;
+
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::agent::AgentConfig;
+use crate::schema::Violation;
+
+/// What a tool can affect, from least to most reach.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PermissionClass {
+    /// Reads vault files only.
+    Read,
+    /// Creates or changes files in the vault.
+    Write,
+    /// Runs local programs, reaches the network, or calls another model.
+    Execute,
+}
+
+impl PermissionClass {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PermissionClass::Read => "read",
+            PermissionClass::Write => "write",
+            PermissionClass::Execute => "execute",
+        }
+    }
+}
+
+/// A function the model can call. `execute` runs on a blocking thread inside
+/// the tokio runtime, so it may do file and process I/O directly.
+pub trait Tool: Send + Sync {
+    fn name(&self) -> &str;
+    fn description(&self) -> &str;
+    /// JSON Schema for the arguments object.
+    fn parameters(&self) -> Value;
+    fn permission(&self) -> PermissionClass;
+    fn execute(&self, args: &Value, config: &AgentConfig) -> Result<Value>;
+
+    /// Ways `args` fails `parameters()`, each located by JSON pointer.
+    fn validate(&self, args: &Value) -> Vec<Violation> {
+        crate::schema::validate(&self.parameters(), args)
+    }
+
+    /// Characters of serialised output returned to the model before the rest
+    /// spills to an artifact. None never spills.
+    fn output_budget(&self) -> Option<usize> {
+        Some(artifact::DEFAULT_OUTPUT_BUDGET)
+    }
+
+    /// Safe to run alongside other read-only calls from the same turn.
+    fn read_only(&self) -> bool {
+        self.permission() == PermissionClass::Read
+    }
+
+    /// Short detail for the direct-mode `[tool: detail]` line, e.g. the query.
+    fn describe_call(&self, _args: &Value) -> Option<String> {
+        None
+    }
+
+    /// Where the tool comes from, for listings: "builtin" or a manifest path.
+    fn source(&self) -> String {
+        "builtin".to_string()
+    }
+
+    /// Function schema in the canonical (OpenAI) tool format.
+    fn schema(&self) -> Value {
+        json!({
+            "type": "function",
+            "function": {
+                "name": self.name(),
+                "description": self.description(),
+                "parameters": self.parameters(),
+            }
+        })
+    }
+}
+
+/// The tools an agent can call, in the order they are offered to the model.
+#[derive(Clone, Default)]
+pub struct ToolRegistry {
+    tools: Vec<Arc<dyn Tool>>,
+}
+
+impl ToolRegistry {
+    /// All tools that ship with J.
+    pub fn builtin() -> Self {
+        let mut registry = Self::default();
+        registry.register(Arc::new(vault::VaultInit));
+        registry.register(Arc::new(thread::ThreadCreate));
+        registry.register(Arc::new(thread::ThreadRead));
+        registry.register(Arc::new(thread::ThreadAppend));
+        registry.register(Arc::new(knowledge::KnowledgeApply));
+        registry.register(Arc::new(knowledge::KnowledgeRevert));
+        registry.register(Arc::new(knowledge::KnowledgeHistory));
+        registry.register(Arc::new(knowledge::KnowledgeRead));
+        registry.register(Arc::new(knowledge::KnowledgeSearch));
+        registry.register(Arc::new(knowledge::KnowledgeList));
+        registry.register(Arc::new(knowledge::KnowledgeIndex));
+        registry.register(Arc::new(media::Draw));
+        registry.register(Arc::new(media::GenerateImage));
+        registry.register(Arc::new(deep_think::DeepThink));
+        registry.register(Arc::new(artifact::ArtifactRead));
+        registry
+    }
+
+    /// Built-in tools, the manifests in `<vault>/tools`, then the tools of
+    /// every configured MCP server. A manifest may replace a built-in tool by
+    /// reusing its name.
+    pub async fn load(vault: &Path) -> Self {
+        let mut registry = Self::builtin();
+        for tool in manifest::discover(vault) {
+            registry.register(Arc::new(tool));
+        }
+        for tool in mcp::mount(&crate::config::current().mcp_servers.value).await {
+            registry.register(Arc::new(tool));
+        }
+        registry
+    }
+
+    /// Add a tool, replacing any existing tool with the same name.
+    pub fn register(&mut self, tool: Arc<dyn Tool>) {
+        match self.tools.iter_mut().find(|t| t.name() == tool.name()) {
+            Some(existing) => *existing = tool,
+            None => self.tools.push(tool),
+        }
+    }
+
+    /// Look up a tool, honouring `filter` (None allows every tool).
+    pub fn get(&self, name: &str, filter: Option<&[String]>) -> Option<&Arc<dyn Tool>> {
+        self.tools
+            .iter()
+            .find(|t| t.name() == name)
+            .filter(|t| allowed(t.name(), filter))
+    }
+
+    /// Every registered tool, in registration order.
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<dyn Tool>> {
+        self.tools.iter()
+    }
+
+    /// Schemas to send to the model, honouring `filter`.
+    pub fn schemas(&self, filter: Option<&[String]>) -> Vec<Value> {
+        self.tools
+            .iter()
+            .filter(|t| allowed(t.name(), filter))
+            .map(|t| t.schema())
+            .collect()
+    }
+}
+
+fn allowed(name: &str, filter: Option<&[String]>) -> bool {
+    filter.is_none_or(|names| names.iter().any(|n| n == name))
+}
@@ -0,0 +1,192 @@
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+use super::{PermissionClass, Tool};
+use crate::agent::AgentConfig;
+use crate::thread_store::{append_event, build_event, create_thread, read_thread, EventType, Role};
+
+pub struct ThreadCreate;
+
+impl Tool for ThreadCreate {
+    fn name(&self) -> &str {
+        "thread_create"
+    }
+
+    fn description(&self) -> &str {
+        "Create a new thread file in the vault."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "vault": { "type": "string", "description": "Vault path." },
+                "thread_id": { "type": "string" },
+                "date": { "type": "string", "description": "YYYY-MM-DD" },
+                "reason": { "type": "string" }
+            },
+            "required": ["reason"]
+        })
+    }
+
+    fn permission(&self) -> PermissionClass {
+        PermissionClass::Write
+    }
+
+    fn execute(&self, args: &Value, config: &AgentConfig) -> Result<Value> {
+        let vault_path = args
+            .get("vault")
+            .and_then(|val| val.as_str())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| config.vault_path.to_path_buf());
+        let thread_id = args
+            .get("thread_id")
+            .and_then(|val| val.as_str())
+            .map(|s| s.to_string());
+        let date = args
+            .get("date")
+            .and_then(|val| val.as_str())
+            .map(|s| chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d"))
+            .transpose()?;
+        let path = create_thread(&vault_path, thread_id, date, None)?;
+        Ok(json!({ "thread_path": path }))
+    }
+}
+
+pub struct ThreadRead;
+
+impl Tool for ThreadRead {
+    fn name(&self) -> &str {
+        "thread_read"
+    }
+
+    fn description(&self) -> &str {
+        "Read events from a thread file."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "thread": { "type": "string", "description": "Thread file path." },
+                "offset": { "type": "integer" },
+                "limit": { "type": "integer" },
+                "reason": { "type": "string" }
+            },
+            "required": ["reason"]
+        })
+    }
+
+    fn permission(&self) -> PermissionClass {
+        PermissionClass::Read
+    }
+
+    fn execute(&self, args: &Value, config: &AgentConfig) -> Result<Value> {
+        // A named thread must be one of the vault's; thread paths handed out
+        // by thread_create start with the vault path, so that prefix is dropped
+        let path = match args.get("thread").and_then(|val| val.as_str()) {
+            Some(thread) => {
+                let vault = &config.vault_path;
+                let thread = Path::new(thread);
+                crate::vault::confine(vault, thread.strip_prefix(vault).unwrap_or(thread), "threads")?
+            }
+            None => config.thread_path.to_path_buf(),
+        };
+        let offset = args.get("offset").and_then(|val| val.as_u64()).map(|v| v as usize);
+        let limit = args.get("limit").and_then(|val| val.as_u64()).map(|v| v as usize);
+        let lines = read_thread(&path, offset, limit)?;
+        Ok(json!({ "count": lines.len(), "lines": lines }))
+    }
+}
+
+pub struct ThreadAppend;
+
+impl Tool for ThreadAppend {
+    fn name(&self) -> &str {
+        "thread_append"
+    }
+
+    fn description(&self) -> &str {
+        "Append an event to a thread file."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "thread": { "type": "string", "description": "Thread file path." },
+                "event_type": { "type": "string", "description": "user_message|assistant_message|tool_call|tool_result|system_note|attachment_added" },
+                "role": { "type": "string", "description": "user|assistant|tool|system" },
+                "content": { "type": "string" },
+                "content_json": { "type": "object" },
+                "tool_name": { "type": "string" },
+                "tool_args": { "type": "object" },
+                "tool_result": { "type": "object" },
+                "reason": { "type": "string" }
+            },
+            "required": ["thread", "event_type", "role", "reason"]
+        })
+    }
+
+    fn permission(&self) -> PermissionClass {
+        PermissionClass::Write
+    }
+
+    fn execute(&self, args: &Value, config: &AgentConfig) -> Result<Value> {
+        let path = args
+            .get("thread")
+            .and_then(|val| val.as_str())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| config.thread_path.to_path_buf());
+        let event_type = args
+            .get("event_type")
+            .and_then(|val| val.as_str())
+            .ok_or_else(|| anyhow!("event_type required"))?;
+        let role = args
+            .get("role")
+            .and_then(|val| val.as_str())
+            .ok_or_else(|| anyhow!("role required"))?;
+        let event_type = parse_event_type(event_type)?;
+        let role = parse_role(role)?;
+
+        let content = args.get("content").and_then(|val| val.as_str()).map(|s| Value::String(s.to_string()));
+        let content_json = args.get("content_json").cloned();
+        let content_value = content_json.or(content);
+
+        let tool_name = args.get("tool_name").and_then(|val| val.as_str()).map(|s| s.to_string());
+        let tool_args = args.get("tool_args").cloned();
+        let tool_result = args.get("tool_result").cloned();
+        let reason = args.get("reason").and_then(|val| val.as_str()).map(|s| s.to_string());
+
+        let event = build_event(None, event_type, role, content_value, tool_name, tool_args, tool_result, reason);
+        append_event(&path, event)?;
+        Ok(json!({"thread_path": path}))
+    }
+}
+
+fn parse_event_type(value: &str) -> Result<EventType> {
+    match value {
+        "user_message" => Ok(EventType::UserMessage),
+        "assistant_message" => Ok(EventType::AssistantMessage),
+        "tool_call" => Ok(EventType::ToolCall),
+        "tool_result" => Ok(EventType::ToolResult),
+        "system_note" => Ok(EventType::SystemNote),
+        "attachment_added" => Ok(EventType::AttachmentAdded),
+        "inner_monologue" => Ok(EventType::InnerMonologue),
+        "title_generated" => Ok(EventType::TitleGenerated),
+        "context_summary" => Ok(EventType::ContextSummary),
+        "permission_decision" => Ok(EventType::PermissionDecision),
+        _ => Err(anyhow!("invalid event_type: {value}")),
+    }
+}
+
+fn parse_role(value: &str) -> Result<Role> {
+    match value {
+        "user" => Ok(Role::User),
+        "assistant" => Ok(Role::Assistant),
+        "tool" => Ok(Role::Tool),
+        "system" => Ok(Role::System),
+        _ => Err(anyhow!("invalid role: {value}")),
+    }
+}
@@ -0,0 +1,44 @@
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+
+use super::{PermissionClass, Tool};
+use crate::agent::AgentConfig;
+use crate::vault::init_vault;
+
+pub struct VaultInit;
+
+impl Tool for VaultInit {
+    fn name(&self) -> &str {
+        "vault_init"
+    }
+
+    fn description(&self) -> &str {
+        "Initialize a J vault directory with required structure."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "Vault directory path." },
+                "reason": { "type": "string" }
+            },
+            "required": ["reason"]
+        })
+    }
+
+    fn permission(&self) -> PermissionClass {
+        PermissionClass::Write
+    }
+
+    fn execute(&self, args: &Value, config: &AgentConfig) -> Result<Value> {
+        let path = args
+            .get("path")
+            .and_then(|val| val.as_str())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| config.vault_path.to_path_buf());
+        init_vault(&path)?;
+        Ok(json!({ "vault_path": path }))
+    }
+}
@@ -0,0 +1,454 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::thread_store::{EventType, ThreadEvent};
+
+/// Token counts reported by the provider for one chat call. Prompt tokens
+/// served from or written to the provider's cache are kept out of
+/// `input_tokens` because they are billed at their own rates.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Usage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub cache_read_tokens: u64,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub cache_write_tokens: u64,
+}
+
+fn is_zero(n: &u64) -> bool {
+    *n == 0
+}
+
+/// USD per million tokens. Cache rates default to Anthropic's multipliers
+/// of the input price (0.1x for reads, 1.25x for writes) when not given.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Price {
+    pub input: f64,
+    pub output: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_read: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_write: Option<f64>,
+}
+
+impl Price {
+    fn new(input: f64, output: f64) -> Self {
+        Price { input, output, cache_read: None, cache_write: None }
+    }
+
+    pub fn cache_read(&self) -> f64 {
+        self.cache_read.unwrap_or(self.input * 0.1)
+    }
+
+    pub fn cache_write(&self) -> f64 {
+        self.cache_write.unwrap_or(self.input * 1.25)
+    }
+}
+
+/// Built-in list prices. Matched by longest prefix so dated snapshots
+/// (e.g. "gpt-5-mini-2025-08-07") pick up their family's price.
+const PRICES: &[(&str, f64, f64)] = &[
+    ("gpt-5-nano", 0.05, 0.40),
+    ("gpt-5-mini", 0.25, 2.00),
+    ("gpt-5", 1.25, 10.00),
+    ("gpt-4.1-nano", 0.10, 0.40),
+    ("gpt-4.1-mini", 0.40, 1.60),
+    ("gpt-4.1", 2.00, 8.00),
+    ("gpt-4o-mini", 0.15, 0.60),
+    ("gpt-4o", 2.50, 10.00),
+    ("o4-mini", 1.10, 4.40),
+    ("o3", 2.00, 8.00),
+    ("claude-opus-4", 15.00, 75.00),
+    ("claude-sonnet-4", 3.00, 15.00),
+    ("claude-3-7-sonnet", 3.00, 15.00),
+    ("claude-3-5-sonnet", 3.00, 15.00),
+    ("claude-haiku-4", 1.00, 5.00),
+    ("claude-3-5-haiku", 0.80, 4.00),
+    ("gemini-2.5-pro", 1.25, 10.00),
+    ("gemini-2.5-flash-lite", 0.10, 0.40),
+    ("gemini-2.5-flash", 0.30, 2.50),
+    ("gemini-2.0-flash-lite", 0.075, 0.30),
+    ("gemini-2.0-flash", 0.10, 0.40),
+];
+
+/// Look up the price for a model. `config/prices.yml` in the vault, loaded
+/// with the rest of the runtime config, overrides and extends the built-in
+/// table:
+///
+/// ```yaml
+/// llama3.2: { input: 0, output: 0 }
+/// gpt-5-mini: { input: 0.25, output: 2.0 }
+/// claude-sonnet-4: { input: 3, output: 15, cache_read: 0.3, cache_write: 3.75 }
+/// ```
+pub fn price_for(model: &str) -> Option<Price> {
+    let config = crate::config::current();
+    let overrides = &config.prices.value;
+    if let Some(price) = longest_prefix(overrides.iter().map(|(k, v)| (k.as_str(), *v)), model) {
+        return Some(price);
+    }
+    longest_prefix(
+        PRICES
+            .iter()
+            .map(|&(name, input, output)| (name, Price::new(input, output))),
+        model,
+    )
+}
+
+/// Cost in USD, or None when the model has no known price.
+pub fn cost_usd(model: &str, usage: &Usage) -> Option<f64> {
+    let price = price_for(model)?;
+    Some(
+        (usage.input_tokens as f64 * price.input
+            + usage.output_tokens as f64 * price.output
+            + usage.cache_read_tokens as f64 * price.cache_read()
+            + usage.cache_write_tokens as f64 * price.cache_write())
+            / 1_000_000.0,
+    )
+}
+
+fn longest_prefix<'a>(
+    entries: impl Iterator<Item = (&'a str, Price)>,
+    model: &str,
+) -> Option<Price> {
+    entries
+        .filter(|(name, _)| model.starts_with(name))
+        .max_by_key(|(name, _)| name.len())
+        .map(|(_, price)| price)
+}
+
+/// LLM calls made outside any thread, e.g. by `j backfill-summaries`, so
+/// the vault report still counts them. One JSON object per line.
+const USAGE_LOG: &str = "audit/usage.jsonl";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct LoggedCall {
+    ts: DateTime<Utc>,
+    /// What made the call, e.g. "backfill_summaries".
+    source: String,
+    model: String,
+    usage: Usage,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    cost_usd: Option<f64>,
+}
+
+/// Record the usage of a call that has no thread event to carry it.
+/// Calls without reported usage are skipped.
+pub fn record_call(vault_path: &Path, source: &str, model: &str, usage: Option<Usage>) -> Result<()> {
+    let Some(usage) = usage else { return Ok(()) };
+    let call = LoggedCall {
+        ts: Utc::now(),
+        source: source.to_string(),
+        model: model.to_string(),
+        usage,
+        cost_usd: cost_usd(model, &usage),
+    };
+    let path = vault_path.join(USAGE_LOG);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("open {}", path.display()))?;
+    writeln!(file, "{}", serde_json::to_string(&call)?)?;
+    Ok(())
+}
+
+/// Accumulated usage over some set of chat calls.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UsageTotals {
+    pub calls: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub cache_write_tokens: u64,
+    pub cost_usd: f64,
+    /// Calls whose model had no price; their tokens are counted but not costed.
+    pub unpriced_calls: u64,
+}
+
+impl UsageTotals {
+    fn add(&mut self, usage: &Usage, cost: Option<f64>) {
+        self.calls += 1;
+        self.input_tokens += usage.input_tokens;
+        self.output_tokens += usage.output_tokens;
+        self.cache_read_tokens += usage.cache_read_tokens;
+        self.cache_write_tokens += usage.cache_write_tokens;
+        match cost {
+            Some(c) => self.cost_usd += c,
+            None => self.unpriced_calls += 1,
+        }
+    }
+}
+
+/// One event that carried usage, with its cost resolved.
+struct UsageRecord {
+    date: NaiveDate,
+    model: String,
+    usage: Usage,
+    cost: Option<f64>,
+}
+
+/// Cost recorded on the event at write time, else priced now from the table.
+fn resolve_cost(event: &ThreadEvent, usage: &Usage) -> Option<f64> {
+    event
+        .cost_usd
+        .or_else(|| event.model.as_deref().and_then(|model| cost_usd(model, usage)))
+}
+
+/// Usage for one thread: the whole thread plus one entry per agent run.
+/// Runs are delimited by the gateway's `run.started` system notes; usage
+/// before the first marker (e.g. direct-mode chat) counts as its own run.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ThreadUsage {
+    pub total: UsageTotals,
+    pub runs: Vec<UsageTotals>,
+}
+
+pub fn thread_usage(thread_path: &Path) -> Result<ThreadUsage> {
+    let lines = crate::thread_store::read_thread(thread_path, None, None)?;
+    let mut result = ThreadUsage::default();
+    let mut current: Option<UsageTotals> = None;
+    for line in &lines {
+        let event = match serde_json::from_str::<ThreadEvent>(line) {
+            Ok(event) => event,
+            Err(_) => continue,
+        };
+        if matches!(event.event_type, EventType::SystemNote)
+            && event.content.as_ref().and_then(|v| v.as_str()) == Some("run.started")
+        {
+            if let Some(run) = current.take() {
+                result.runs.push(run);
+            }
+            current = Some(UsageTotals::default());
+            continue;
+        }
+        if let Some(ref usage) = event.usage {
+            let cost = resolve_cost(&event, usage);
+            result.total.add(usage, cost);
+            current.get_or_insert_with(UsageTotals::default).add(usage, cost);
+        }
+    }
+    if let Some(run) = current {
+        result.runs.push(run);
+    }
+    Ok(result)
+}
+
+/// Usage report across the vault, grouped by day and model.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UsageReport {
+    pub total: UsageTotals,
+    pub by_day: BTreeMap<NaiveDate, UsageTotals>,
+    pub by_model: BTreeMap<String, UsageTotals>,
+}
+
+/// Scan every thread, plus the log of calls made outside threads, and total
+/// usage on or after `since`.
+pub fn vault_report(vault_path: &Path, since: Option<NaiveDate>) -> Result<UsageReport> {
+    let mut report = UsageReport::default();
+    let threads_dir = vault_path.join("threads");
+    let mut files = Vec::new();
+    if threads_dir.exists() {
+        collect_jsonl(&threads_dir, &mut files)?;
+    }
+    let mut records = read_logged_calls(&vault_path.join(USAGE_LOG))?;
+    for path in files {
+        records.extend(read_records(&path)?);
+    }
+    for record in records {
+        if since.is_some_and(|since| record.date < since) {
+            continue;
+        }
+        report.total.add(&record.usage, record.cost);
+        report
+            .by_day
+            .entry(record.date)
+            .or_default()
+            .add(&record.usage, record.cost);
+        report
+            .by_model
+            .entry(record.model)
+            .or_default()
+            .add(&record.usage, record.cost);
+    }
+    Ok(report)
+}
+
+fn read_records(thread_path: &Path) -> Result<Vec<UsageRecord>> {
+    let content = fs::read_to_string(thread_path)
+        .with_context(|| format!("read thread {}", thread_path.display()))?;
+    let mut records = Vec::new();
+    for line in content.lines() {
+        // Cheap pre-filter: most events carry no usage
+        if !line.contains("\"usage\"") {
+            continue;
+        }
+        let event = match serde_json::from_str::<ThreadEvent>(line) {
+            Ok(event) => event,
+            Err(_) => continue,
+        };
+        if let Some(ref usage) = event.usage {
+            records.push(UsageRecord {
+                date: event.ts.date_naive(),
+                model: event.model.clone().unwrap_or_else(|| "unknown".to_string()),
+                usage: *usage,
+                cost: resolve_cost(&event, usage),
+            });
+        }
+    }
+    Ok(records)
+}
+
+fn read_logged_calls(path: &Path) -> Result<Vec<UsageRecord>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
+    Ok(content
+        .lines()
+        .filter_map(|line| serde_json::from_str::<LoggedCall>(line).ok())
+        .map(|call| UsageRecord {
+            date: call.ts.date_naive(),
+            cost: call.cost_usd.or_else(|| cost_usd(&call.model, &call.usage)),
+            model: call.model,
+            usage: call.usage,
+        })
+        .collect())
+}
+
+fn collect_jsonl(dir: &Path, out: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir).with_context(|| format!("read dir {}", dir.display()))? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_jsonl(&path, out)?;
+        } else if path.extension().and_then(|s| s.to_str()) == Some("jsonl") {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Parse an OpenAI-style `usage` block (`prompt_tokens` / `completion_tokens`).
+pub fn from_openai(value: &Value) -> Option<Usage> {
+    let usage = value.get("usage")?;
+    Some(Usage {
+        input_tokens: usage.get("prompt_tokens")?.as_u64()?,
+        output_tokens: usage.get("completion_tokens").and_then(|v| v.as_u64()).unwrap_or(0),
+        ..Usage::default()
+    })
+}
+
+/// Parse Gemini `usageMetadata` (`promptTokenCount` / `candidatesTokenCount`).
+pub fn from_gemini(value: &Value) -> Option<Usage> {
+    let usage = value.get("usageMetadata")?;
+    Some(Usage {
+        input_tokens: usage.get("promptTokenCount").and_then(|v| v.as_u64()).unwrap_or(0),
+        output_tokens: usage
+            .get("candidatesTokenCount")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0),
+        ..Usage::default()
+    })
+}
+
+/// Parse an Anthropic `usage` block. Cache reads and writes are reported
+/// apart from `input_tokens` and stay apart, since each has its own price.
+pub fn from_anthropic(usage: &Value) -> Usage {
+    let field = |name: &str| usage.get(name).and_then(|v| v.as_u64()).unwrap_or(0);
+    Usage {
+        input_tokens: field("input_tokens"),
+        output_tokens: field("output_tokens"),
+        cache_read_tokens: field("cache_read_input_tokens"),
+        cache_write_tokens: field("cache_creation_input_tokens"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn usage(input_tokens: u64, output_tokens: u64) -> Usage {
+        Usage { input_tokens, output_tokens, ..Usage::default() }
+    }
+
+    #[test]
+    fn dated_snapshot_uses_family_price() {
+        // gpt-5-mini: $0.25 in, $2.00 out per million
+        let cost = cost_usd("gpt-5-mini-2025-08-07", &usage(1_000_000, 500_000)).unwrap();
+        assert!((cost - 1.25).abs() < 1e-9, "cost {cost}");
+    }
+
+    #[test]
+    fn longest_prefix_wins() {
+        let nano = price_for("gpt-5-nano-2025-08-07").unwrap();
+        assert_eq!(nano.input, 0.05);
+        let base = price_for("gpt-5-2025-08-07").unwrap();
+        assert_eq!(base.input, 1.25);
+    }
+
+    #[test]
+    fn unknown_model_has_no_cost() {
+        assert!(cost_usd("mystery-model", &usage(10, 10)).is_none());
+    }
+
+    #[test]
+    fn totals_count_unpriced_calls() {
+        let mut totals = UsageTotals::default();
+        totals.add(&usage(100, 10), Some(0.5));
+        totals.add(&usage(50, 5), None);
+        assert_eq!(totals.calls, 2);
+        assert_eq!(totals.input_tokens, 150);
+        assert_eq!(totals.output_tokens, 15);
+        assert_eq!(totals.cost_usd, 0.5);
+        assert_eq!(totals.unpriced_calls, 1);
+    }
+
+    #[test]
+    fn provider_usage_blocks() {
+        let openai = json!({"usage": {"prompt_tokens": 12, "completion_tokens": 3}});
+        assert_eq!(from_openai(&openai), Some(usage(12, 3)));
+
+        let gemini = json!({"usageMetadata": {"promptTokenCount": 7}});
+        assert_eq!(from_gemini(&gemini), Some(usage(7, 0)));
+
+        // Cached prompt tokens stay out of input
+        let anthropic = json!({
+            "input_tokens": 5,
+            "cache_creation_input_tokens": 20,
+            "cache_read_input_tokens": 100,
+            "output_tokens": 9
+        });
+        assert_eq!(
+            from_anthropic(&anthropic),
+            Usage { cache_read_tokens: 100, cache_write_tokens: 20, ..usage(5, 9) }
+        );
+    }
+
+    #[test]
+    fn cache_tokens_are_priced_at_their_own_rates() {
+        // claude-sonnet-4: $3 in, so reads $0.30 and writes $3.75 per million
+        let cached = Usage { cache_read_tokens: 1_000_000, cache_write_tokens: 1_000_000, ..usage(0, 0) };
+        let cost = cost_usd("claude-sonnet-4-20250514", &cached).unwrap();
+        assert!((cost - 4.05).abs() < 1e-9, "cost {cost}");
+
+        let explicit = Price { cache_read: Some(1.0), cache_write: Some(2.0), ..Price::new(3.0, 15.0) };
+        assert_eq!((explicit.cache_read(), explicit.cache_write()), (1.0, 2.0));
+    }
+
+    #[test]
+    fn usage_without_cache_fields_still_parses() {
+        let old: Usage = serde_json::from_value(json!({"input_tokens": 3, "output_tokens": 4})).unwrap();
+        assert_eq!(old, usage(3, 4));
+        assert_eq!(serde_json::to_value(old).unwrap(), json!({"input_tokens": 3, "output_tokens": 4}));
+    }
+}
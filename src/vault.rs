@@ -1,7 +1,7 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use std::fs::{self, OpenOptions};
 use std::io::Write;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 
 pub fn init_vault(path: &Path) -> Result<()> {
     let dirs = [
@@ -24,6 +24,7 @@ pub fn init_vault(path: &Path) -> Result<()> {
         path.join("artifacts"),
         path.join("media"),
         path.join("audit"),
+        path.join("tools"),
     ];
 
     for dir in dirs {
@@ -59,11 +60,57 @@ This vault is the source of truth for J's memory and operating rules.
     write_new_file(
         &path.join("config/j.runtime.yml"),
         r#"# J runtime configuration
+# Env vars and CLI flags override these; `j config show` lists every setting.
 providers:
   default: "openai"
+  # fallback: ["anthropic", "local"]
+  # openai:
+  #   model: "gpt-5-mini-2025-08-07"
+  #   base_url: "https://api.openai.com"
+  # anthropic:
+  #   max_tokens: 8192
+
+# Per-job engine, model and parameters; unset values use the default engine.
+# Roles: chat, deep, title, ingest, summarize.
+# roles:
+#   title:
+#     model: "gpt-4.1-nano"
+#   deep:
+#     engine: "anthropic"
+#     model: "claude-sonnet-4-20250514"
+#     temperature: 0.7
+#     max_tokens: 4096
+
+# gateway:
+#   port: 9123
+
+# context:
+#   window: 32768
+#   history_tokens: 24000
 
 logging:
   level: "info"
+
+# What the agent may do without asking: allow, deny or ask, per permission
+# class (read, write, execute) with per-tool overrides. "ask" pauses the run
+# until the call is approved in the REPL or with session.approve.
+# permissions:
+#   write: ask
+#   execute: ask
+#   tools:
+#     knowledge_search: allow
+#     vault_init: deny
+
+# MCP servers whose tools the agent can call, as <server>__<tool>.
+# mcp:
+#   servers:
+#     files:
+#       command: "npx"
+#       args: ["-y", "@modelcontextprotocol/server-filesystem", "/home/me/notes"]
+#     tracker:
+#       url: "http://localhost:8931/mcp"
+#       bearer_token_env: "TRACKER_TOKEN"
+#       timeout_secs: 30
 "#,
     )?;
 
@@ -130,3 +177,107 @@ fn write_new_file(path: &Path, content: &str) -> Result<()> {
 pub fn resolve_vault(path: Option<PathBuf>) -> PathBuf {
     path.unwrap_or_else(|| PathBuf::from("j_vault"))
 }
+
+/// `path` resolved inside `<vault>/<dir>`, for paths that come from tool
+/// arguments. A relative path is taken from the vault root and must read
+/// `dir/...` with no `.` or `..`; an absolute one must point into the vault.
+/// Either way the path is canonicalized, through its nearest existing ancestor
+/// when it doesn't exist yet, so a symlink can't lead out either.
+pub fn confine(vault: &Path, path: &Path, dir: &str) -> Result<PathBuf> {
+    let outside = || anyhow!("{} is not under {dir}/", path.display());
+    if path.is_relative()
+        && !(path.starts_with(dir) && path.components().all(|c| matches!(c, Component::Normal(_))))
+    {
+        return Err(outside());
+    }
+    let vault = vault
+        .canonicalize()
+        .with_context(|| format!("resolve vault {}", vault.display()))?;
+    let root = vault.join(dir).canonicalize().unwrap_or_else(|_| vault.join(dir));
+
+    // Canonicalize what exists and re-attach the rest, which can only be
+    // plain names: `file_name` is None for `..`
+    let full = vault.join(path);
+    let mut existing = full.as_path();
+    let mut missing = Vec::new();
+    let resolved = loop {
+        match existing.canonicalize() {
+            Ok(real) => break missing.iter().rev().fold(real, |acc: PathBuf, name| acc.join(name)),
+            Err(_) => {
+                let (Some(parent), Some(name)) = (existing.parent(), existing.file_name()) else {
+                    return Err(outside());
+                };
+                missing.push(name.to_os_string());
+                existing = parent;
+            }
+        }
+    };
+    if resolved == root || !resolved.starts_with(&root) {
+        return Err(outside());
+    }
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A vault with `knowledge/a.md`, `threads/t.jsonl` and a file outside it.
+    fn sample(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("j-vault-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let vault = root.join("vault");
+        fs::create_dir_all(vault.join("knowledge")).unwrap();
+        fs::create_dir_all(vault.join("threads")).unwrap();
+        fs::write(vault.join("knowledge/a.md"), "a").unwrap();
+        fs::write(vault.join("threads/t.jsonl"), "{}").unwrap();
+        fs::write(root.join("secret.txt"), "secret").unwrap();
+        vault
+    }
+
+    fn rejects(vault: &Path, path: &str, dir: &str) -> bool {
+        confine(vault, Path::new(path), dir).is_err()
+    }
+
+    #[test]
+    fn paths_inside_the_dir_resolve() {
+        let vault = sample("inside");
+        let real = vault.canonicalize().unwrap();
+        assert_eq!(confine(&vault, Path::new("knowledge/a.md"), "knowledge").unwrap(), real.join("knowledge/a.md"));
+        // Not written yet, in a directory that doesn't exist yet either
+        assert_eq!(
+            confine(&vault, Path::new("knowledge/new/b.md"), "knowledge").unwrap(),
+            real.join("knowledge/new/b.md")
+        );
+        let absolute = real.join("threads/t.jsonl");
+        assert_eq!(confine(&vault, &absolute, "threads").unwrap(), absolute);
+        let _ = fs::remove_dir_all(vault.parent().unwrap());
+    }
+
+    #[test]
+    fn traversal_and_other_dirs_are_refused() {
+        let vault = sample("traversal");
+        for path in ["../secret.txt", "knowledge/../../secret.txt", "knowledge/../threads/t.jsonl", "./knowledge/a.md"] {
+            assert!(rejects(&vault, path, "knowledge"), "{path}");
+        }
+        assert!(rejects(&vault, "threads/t.jsonl", "knowledge"));
+        assert!(rejects(&vault, "knowledge", "knowledge"));
+        let secret = vault.parent().unwrap().join("secret.txt");
+        assert!(rejects(&vault, secret.to_str().unwrap(), "threads"));
+        assert!(rejects(&vault, "/etc/passwd", "threads"));
+        let _ = fs::remove_dir_all(vault.parent().unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_out_of_the_dir_are_refused() {
+        let vault = sample("symlink");
+        let outside = vault.parent().unwrap();
+        std::os::unix::fs::symlink(outside.join("secret.txt"), vault.join("knowledge/link.md")).unwrap();
+        std::os::unix::fs::symlink(outside, vault.join("knowledge/up")).unwrap();
+        assert!(rejects(&vault, "knowledge/link.md", "knowledge"));
+        assert!(rejects(&vault, "knowledge/up/secret.txt", "knowledge"));
+        assert!(rejects(&vault, "knowledge/up/new.md", "knowledge"));
+        let _ = fs::remove_dir_all(outside);
+    }
+}
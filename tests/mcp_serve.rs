@@ -0,0 +1,130 @@
+//! `j mcp serve` driven over stdio: tool calls go through the vault's
+//! permission policy like calls in an agent run.
+
+use serde_json::{json, Value};
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+struct Vault {
+    root: PathBuf,
+}
+
+impl Vault {
+    fn new(name: &str, runtime_config: &str) -> Self {
+        let root = std::env::temp_dir().join(format!("j-mcp-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("vault/knowledge/people")).unwrap();
+        fs::create_dir_all(root.join("vault/config")).unwrap();
+        fs::write(root.join("vault/config/j.runtime.yml"), runtime_config).unwrap();
+        Self { root }
+    }
+
+    /// Send `calls` as tools/call requests after initialize; one reply each.
+    fn call_tools(&self, calls: &[(&str, Value)]) -> Vec<Value> {
+        let mut requests = vec![json!({"jsonrpc": "2.0", "id": 0, "method": "initialize", "params": {"clientInfo": {"name": "test"}}})];
+        for (i, (name, arguments)) in calls.iter().enumerate() {
+            requests.push(json!({
+                "jsonrpc": "2.0",
+                "id": i + 1,
+                "method": "tools/call",
+                "params": {"name": name, "arguments": arguments},
+            }));
+        }
+        let input: String = requests.iter().map(|r| format!("{r}\n")).collect();
+
+        let vault = self.root.join("vault");
+        let mut child = Command::new(env!("CARGO_BIN_EXE_j"))
+            .args(["mcp", "serve", "--vault", vault.to_str().unwrap()])
+            .env_clear()
+            .env("HOME", self.root.join("home"))
+            .env("EMBEDDING_PROVIDER", "none")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("spawn j");
+        child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .skip(1)
+            .map(|reply| reply["result"].clone())
+            .collect()
+    }
+
+    fn decisions(&self) -> Vec<Value> {
+        let mut decisions = Vec::new();
+        let mut dirs = vec![self.root.join("vault/threads")];
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(&dir).into_iter().flatten().flatten() {
+                let path = entry.path();
+                if path.is_dir() {
+                    dirs.push(path);
+                    continue;
+                }
+                for line in fs::read_to_string(&path).unwrap().lines() {
+                    let event: Value = serde_json::from_str(line).unwrap();
+                    if event["type"] == "permission_decision" {
+                        decisions.push(event);
+                    }
+                }
+            }
+        }
+        decisions
+    }
+}
+
+impl Drop for Vault {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+fn apply_args() -> Value {
+    json!({
+        "patch": {
+            "doc_path": "knowledge/people/ada.md",
+            "title": "Ada",
+            "type": "person",
+            "body_append": "Ada likes numbers.",
+        },
+        "reason": "test",
+    })
+}
+
+#[test]
+fn denied_and_ask_tools_are_refused_and_logged() {
+    let vault = Vault::new(
+        "policy",
+        "permissions:\n  tools:\n    knowledge_search: deny\n    knowledge_apply: ask\n",
+    );
+    let replies = vault.call_tools(&[
+        ("knowledge_search", json!({"query": "ada", "reason": "test"})),
+        ("knowledge_apply", apply_args()),
+    ]);
+    assert_eq!(replies[0]["isError"], true);
+    assert_eq!(replies[0]["content"][0]["text"], "knowledge_search is denied by policy");
+    assert_eq!(replies[1]["isError"], true);
+    assert_eq!(
+        replies[1]["content"][0]["text"],
+        "knowledge_apply needs approval and nobody can approve it here"
+    );
+    assert!(!vault.root.join("vault/knowledge/people/ada.md").exists());
+
+    let decisions = vault.decisions();
+    let logged: Vec<(&Value, &Value)> = decisions.iter().map(|e| (&e["tool_name"], &e["content"]["policy"])).collect();
+    assert_eq!(logged, [(&json!("knowledge_search"), &json!("deny")), (&json!("knowledge_apply"), &json!("ask"))]);
+}
+
+#[test]
+fn allowed_tools_run() {
+    let vault = Vault::new("allow", "permissions:\n  write: allow\n");
+    let replies = vault.call_tools(&[("knowledge_apply", apply_args())]);
+    assert_eq!(replies[0]["isError"], false, "{}", replies[0]);
+    assert!(vault.root.join("vault/knowledge/people/ada.md").exists());
+    assert!(vault.decisions().is_empty());
+}
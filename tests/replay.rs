@@ -0,0 +1,297 @@
+//! End-to-end agent runs against scripted replay fixtures: `j chat --direct`
+//! with `LLM_ENGINE=replay`, so the whole loop runs offline.
+
+use serde_json::Value;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+
+const ADA: &str = "---
+id: mem_ada
+title: Ada
+type: person
+status: active
+tags: [math]
+confidence: 0.9
+created_at: 2026-01-01T00:00:00Z
+updated_at: 2026-01-01T00:00:00Z
+sources: []
+supersedes: []
+summary: Ada writes programs for the analytical engine.
+---
+Ada likes numbers.
+";
+
+/// A scratch home and vault holding one person doc.
+struct Sandbox {
+    root: PathBuf,
+}
+
+impl Sandbox {
+    fn new(name: &str) -> Self {
+        let root = std::env::temp_dir().join(format!("j-replay-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("home")).unwrap();
+        fs::create_dir_all(root.join("vault/knowledge/people")).unwrap();
+        fs::write(root.join("vault/knowledge/people/ada.md"), ADA).unwrap();
+        Self { root }
+    }
+
+    fn vault(&self) -> PathBuf {
+        self.root.join("vault")
+    }
+
+    /// Start `j` with only the environment the test sets, all pipes captured.
+    fn spawn(&self, args: &[&str], fixture: &str) -> Child {
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(fixture);
+        Command::new(env!("CARGO_BIN_EXE_j"))
+            .args(args)
+            .current_dir(&self.root)
+            .env_clear()
+            .env("HOME", self.root.join("home"))
+            .env("LLM_ENGINE", "replay")
+            .env("REPLAY_FIXTURE", fixture)
+            .env("EMBEDDING_PROVIDER", "none")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("spawn j")
+    }
+
+    fn j(&self, args: &[&str], fixture: &str, stdin: &str) -> Output {
+        let mut child = self.spawn(args, fixture);
+        child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+        child.wait_with_output().unwrap()
+    }
+
+    fn chat_args(&self) -> Vec<String> {
+        let vault = self.vault().to_string_lossy().to_string();
+        ["chat", "--direct", "--vault", &vault].map(String::from).to_vec()
+    }
+
+    fn chat(&self, fixture: &str, message: &str) -> Output {
+        let args = self.chat_args();
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        self.j(&args, fixture, &format!("{message}\n"))
+    }
+
+    /// Events of the single thread the run wrote, header line excluded.
+    fn thread_events(&self) -> Vec<Value> {
+        let mut files = Vec::new();
+        collect_jsonl(&self.vault().join("threads"), &mut files);
+        assert_eq!(files.len(), 1, "expected one thread, found {files:?}");
+        fs::read_to_string(&files[0])
+            .unwrap()
+            .lines()
+            .skip(1)
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+fn collect_jsonl(dir: &Path, out: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_jsonl(&path, out);
+        } else if path.extension().is_some_and(|ext| ext == "jsonl") {
+            out.push(path);
+        }
+    }
+}
+
+fn of_type<'a>(events: &'a [Value], kind: &str) -> Vec<&'a Value> {
+    events.iter().filter(|e| e["type"] == kind).collect()
+}
+
+#[test]
+fn tool_call_then_final_answer() {
+    let sandbox = Sandbox::new("tool-then-answer");
+    let output = sandbox.chat("tool_then_answer.jsonl", "who is ada");
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Ada likes numbers."));
+
+    let events = sandbox.thread_events();
+    let kinds: Vec<&str> = events.iter().map(|e| e["type"].as_str().unwrap()).collect();
+    assert_eq!(kinds, ["user_message", "tool_call", "tool_result", "assistant_message"]);
+
+    let call = of_type(&events, "tool_call")[0];
+    assert_eq!(call["tool_name"], "knowledge_read");
+    assert_eq!(call["tool_args"]["doc_path"], "knowledge/people/ada.md");
+
+    let result = of_type(&events, "tool_result")[0];
+    assert_eq!(result["tool_result"]["status"], "ok");
+    assert_eq!(result["tool_result"]["data"]["body"], "Ada likes numbers.");
+
+    let answer = of_type(&events, "assistant_message")[0];
+    assert_eq!(answer["content"], "Ada likes numbers.");
+}
+
+#[test]
+fn usage_from_fixture_is_reported() {
+    let sandbox = Sandbox::new("usage");
+    let output = sandbox.chat("tool_then_answer.jsonl", "who is ada");
+    assert!(output.status.success());
+
+    let vault = sandbox.vault();
+    let output = sandbox.j(&["usage", "--vault", vault.to_str().unwrap(), "--json"], "tool_then_answer.jsonl", "");
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["total"]["calls"], 2);
+    assert_eq!(report["total"]["input_tokens"], 300);
+    assert_eq!(report["total"]["output_tokens"], 28);
+}
+
+#[test]
+fn invalid_arguments_are_returned_to_the_model() {
+    let sandbox = Sandbox::new("invalid-args");
+    let output = sandbox.chat("invalid_args.jsonl", "read something");
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+
+    let events = sandbox.thread_events();
+    let result = of_type(&events, "tool_result")[0];
+    assert_eq!(result["tool_result"]["status"], "error");
+    let violations = result["tool_result"]["violations"].as_array().unwrap();
+    assert!(violations.iter().any(|v| v["pointer"] == "/doc_path"), "violations: {violations:?}");
+    assert_eq!(of_type(&events, "assistant_message")[0]["content"], "I could not read it.");
+}
+
+#[test]
+fn exhausted_fixture_fails_loudly() {
+    let sandbox = Sandbox::new("exhausted");
+    let output = sandbox.chat("exhausted.jsonl", "who is ada");
+    let all = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(all.contains("replay fixture exhausted"), "output: {all}");
+}
+
+/// `j chat --direct` driven line by line, for tests that interrupt a run.
+#[cfg(unix)]
+struct Repl {
+    child: Child,
+    stdin: Option<std::process::ChildStdin>,
+    stderr: std::sync::mpsc::Receiver<String>,
+    seen: String,
+}
+
+#[cfg(unix)]
+impl Repl {
+    fn start(sandbox: &Sandbox, fixture: &str) -> Self {
+        use std::io::Read;
+
+        let args = sandbox.chat_args();
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let mut child = sandbox.spawn(&args, fixture);
+        let stdin = child.stdin.take();
+        // Stderr arrives in pieces; collect it on a thread so we can wait for text
+        let mut stderr = child.stderr.take().unwrap();
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let mut buf = [0u8; 256];
+            while let Ok(n @ 1..) = stderr.read(&mut buf) {
+                let _ = tx.send(String::from_utf8_lossy(&buf[..n]).to_string());
+            }
+        });
+        Self { child, stdin, stderr: rx, seen: String::new() }
+    }
+
+    fn send(&mut self, line: &str) {
+        self.stdin.as_mut().unwrap().write_all(format!("{line}\n").as_bytes()).unwrap();
+    }
+
+    fn wait_for(&mut self, text: &str) {
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(20);
+        while !self.seen.contains(text) {
+            let left = deadline.saturating_duration_since(std::time::Instant::now());
+            match self.stderr.recv_timeout(left) {
+                Ok(chunk) => self.seen.push_str(&chunk),
+                Err(_) => panic!("waiting for {text:?}; stderr so far: {}", self.seen),
+            }
+        }
+    }
+
+    /// Ctrl-C, as the REPL sees it while a run is in progress.
+    fn interrupt(&mut self) {
+        unsafe {
+            libc::kill(self.child.id() as i32, libc::SIGINT);
+        }
+        self.wait_for("[cancelled]");
+    }
+
+    /// Close stdin and wait for the REPL to exit.
+    fn finish(mut self) {
+        drop(self.stdin.take());
+        assert!(self.child.wait().unwrap().success(), "stderr: {}", self.seen);
+    }
+}
+
+/// Ctrl-C at an approval prompt must stop the read, or the prompt would take
+/// the next REPL line as its answer.
+#[cfg(unix)]
+#[test]
+fn cancelled_approval_prompt_leaves_the_next_line_to_the_repl() {
+    let sandbox = Sandbox::new("cancel-approval");
+    let config = sandbox.vault().join("config");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(config.join("j.runtime.yml"), "permissions:\n  tools:\n    knowledge_read: ask\n").unwrap();
+
+    let mut repl = Repl::start(&sandbox, "tool_then_answer.jsonl");
+    repl.send("who is ada");
+    repl.wait_for("Allow knowledge_read");
+    repl.interrupt();
+    repl.send("and what does she like");
+    repl.finish();
+
+    let events = sandbox.thread_events();
+    let asked: Vec<&Value> = of_type(&events, "user_message").iter().map(|e| &e["content"]).collect();
+    assert_eq!(asked, ["who is ada", "and what does she like"]);
+    assert!(of_type(&events, "tool_result").is_empty());
+    let decision = of_type(&events, "permission_decision")[0];
+    assert_eq!(decision["content"]["decision"], "cancelled");
+    assert_eq!(of_type(&events, "assistant_message")[0]["content"], "Ada likes numbers.");
+}
+
+/// A cancelled batch records what each call really did: a call that finished
+/// keeps its result, and a long one that notices the cancel says so.
+#[cfg(unix)]
+#[test]
+fn cancelled_batch_keeps_results_of_finished_calls() {
+    let sandbox = Sandbox::new("cancel-batch");
+    let tools = sandbox.vault().join("tools");
+    fs::create_dir_all(&tools).unwrap();
+    fs::write(
+        tools.join("slow.yml"),
+        "name: slow\ncommand: [/bin/sh, -c, 'sleep 30']\npermission: read\n",
+    )
+    .unwrap();
+
+    let started = std::time::Instant::now();
+    let mut repl = Repl::start(&sandbox, "read_and_slow.jsonl");
+    repl.send("who is ada");
+    repl.wait_for("[slow]");
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    repl.interrupt();
+    repl.finish();
+    assert!(started.elapsed().as_secs() < 20, "the slow call was not stopped");
+
+    let events = sandbox.thread_events();
+    let results: Vec<(&Value, &Value)> = of_type(&events, "tool_result")
+        .iter()
+        .map(|e| (&e["tool_name"], &e["tool_result"]["status"]))
+        .collect();
+    assert_eq!(results, [(&Value::from("knowledge_read"), &Value::from("ok")), (&Value::from("slow"), &Value::from("error"))]);
+    let slow = of_type(&events, "tool_result")[1];
+    assert_eq!(slow["tool_result"]["error"], "slow: cancelled");
+}